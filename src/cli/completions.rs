@@ -0,0 +1,227 @@
+use std::io::Write;
+
+use clap::Command;
+use clap_complete::Shell;
+
+/// Subcommand paths whose leading positional arguments are note or source
+/// ids, with how many of them there are.
+const TARGETS: &[(&str, &str, usize)] = &[
+    ("get note", "notes", 1),
+    ("rename", "notes", 1),
+    ("graph path", "notes", 2),
+    ("graph around", "notes", 1),
+    ("related", "notes", 1),
+    ("get source", "sources", 1),
+    ("source rename", "sources", 1),
+    ("source merge", "sources", 2),
+    ("source delete", "sources", 1),
+];
+
+/// Options whose value is a note id.
+const NOTE_OPTIONS: &[&str] = &["--after", "--note"];
+
+/// Writes the static clap completions for `shell` followed by a wrapper which
+/// completes note and source ids through `spark __complete`.
+pub fn generate(shell: Shell, cmd: &mut Command, out: &mut impl Write) -> std::io::Result<()> {
+    let name = cmd.get_name().to_string();
+    clap_complete::generate(shell, cmd, &name, out);
+
+    let options = value_options(cmd).join(" ");
+    let rules = TARGETS.iter()
+        .map(|(path, kind, count)| (format!("^ {path} ([^ ]+ ){{0,{}}}$", count - 1), *kind))
+        .collect::<Vec<(String, &str)>>();
+    let note_options = NOTE_OPTIONS.join(" ");
+
+    let script = match shell {
+        Shell::Bash => bash(&name, &options, &note_options, &rules),
+        Shell::Zsh => zsh(&name, &options, &note_options, &rules),
+        Shell::Fish => fish(&name, &options, &note_options, &rules),
+        _ => return Ok(())
+    };
+
+    out.write_all(script.as_bytes())
+}
+
+/// Long and short flags of every option taking a value, so their values are
+/// not mistaken for subcommands or positional arguments.
+fn value_options(cmd: &Command) -> Vec<String> {
+    let mut options = vec![];
+    for arg in cmd.get_arguments() {
+        if arg.get_action().takes_values() && !arg.is_positional() {
+            options.extend(arg.get_long().map(|long| format!("--{long}")));
+            options.extend(arg.get_short().map(|short| format!("-{short}")));
+        }
+    }
+    for subcommand in cmd.get_subcommands() {
+        for option in value_options(subcommand) {
+            if !options.contains(&option) {
+                options.push(option);
+            }
+        }
+    }
+
+    options
+}
+
+fn bash(name: &str, options: &str, note_options: &str, rules: &[(String, &str)]) -> String {
+    let rules: String = rules.iter()
+        .map(|(re, kind)| format!("    re='{re}'; [[ $line =~ $re ]] && kind={kind}\n"))
+        .collect();
+
+    format!(r#"
+_{name}_ids() {{
+    local cur=${{COMP_WORDS[COMP_CWORD]}} line=" " vault= kind= skip= w
+    local options=" {options} " note_options=" {note_options} "
+    for w in "${{COMP_WORDS[@]:1:COMP_CWORD-1}}"; do
+        if [[ -n $skip ]]; then
+            [[ $skip == --vault ]] && vault=$w
+            skip=
+        elif [[ $options == *" $w "* ]]; then
+            skip=$w
+        elif [[ $w != -* ]]; then
+            line+="$w "
+        fi
+    done
+    if [[ -n $skip ]]; then
+        [[ $note_options == *" $skip "* ]] && kind=notes
+    else
+{rules}    fi
+    if [[ -z $kind ]]; then
+        _{name} "$@"
+        return
+    fi
+    local ids
+    ids=$("${{COMP_WORDS[0]}}" ${{vault:+--vault "$vault"}} __complete $kind 2>/dev/null | cut -f1)
+    COMPREPLY=($(compgen -W "$ids" -- "$cur"))
+}}
+
+complete -F _{name}_ids -o bashdefault -o default {name}
+"#)
+}
+
+fn zsh(name: &str, options: &str, note_options: &str, rules: &[(String, &str)]) -> String {
+    let rules: String = rules.iter()
+        .map(|(re, kind)| format!("        [[ $line =~ '{re}' ]] && kind={kind}\n"))
+        .collect();
+
+    format!(r#"
+_{name}_ids() {{
+    local line=" " vault= kind= skip= w
+    local options=" {options} " note_options=" {note_options} "
+    for w in "${{(@)words[2,CURRENT-1]}}"; do
+        if [[ -n $skip ]]; then
+            [[ $skip == --vault ]] && vault=$w
+            skip=
+        elif [[ $options == *" $w "* ]]; then
+            skip=$w
+        elif [[ $w != -* ]]; then
+            line+="$w "
+        fi
+    done
+    if [[ -n $skip ]]; then
+        [[ $note_options == *" $skip "* ]] && kind=notes
+    else
+{rules}    fi
+    if [[ -z $kind ]]; then
+        _{name} "$@"
+        return
+    fi
+    local -a items
+    items=("${{(@f)$("$words[1]" ${{vault:+--vault "$vault"}} __complete $kind 2>/dev/null)}}")
+    items=("${{(@)items/$'\t'/:}}")
+    _describe $kind items
+}}
+
+compdef _{name}_ids {name}
+"#)
+}
+
+fn fish(name: &str, options: &str, note_options: &str, rules: &[(String, &str)]) -> String {
+    let rules: String = rules.iter()
+        .map(|(re, kind)| format!("        string match -qr -- '{re}' \"$line\"; and set kind {kind}\n"))
+        .collect();
+
+    format!(r#"
+function __{name}_ids_kind
+    set -l options {options}
+    set -l note_options {note_options}
+    set -l line " "
+    set -l skip
+    set -l kind
+    for w in (commandline -opc)[2..-1]
+        if test -n "$skip"
+            set skip
+        else if contains -- $w $options
+            set skip $w
+        else if not string match -q -- '-*' $w
+            set line "$line$w "
+        end
+    end
+    if test -n "$skip"
+        contains -- $skip $note_options; and set kind notes
+    else
+{rules}    end
+    echo $kind
+end
+
+function __{name}_ids
+    set -l tokens (commandline -opc)
+    set -l vault
+    set -l i (contains -i -- --vault $tokens)
+    and set vault --vault $tokens[(math $i + 1)]
+    $tokens[1] $vault __complete (__{name}_ids_kind) 2>/dev/null
+end
+
+complete -c {name} -n 'test -n "$(__{name}_ids_kind)"' -f -a '(__{name}_ids)'
+"#)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{Arg, ArgAction};
+
+    use super::*;
+
+    fn command() -> Command {
+        Command::new("spark")
+            .arg(Arg::new("vault").long("vault").global(true))
+            .arg(Arg::new("dry-run").long("dry-run").action(ArgAction::SetTrue))
+            .subcommand(Command::new("get")
+                .subcommand(Command::new("note").arg(Arg::new("id")).arg(Arg::new("format").short('f').long("format"))))
+            .subcommand(Command::new("new").arg(Arg::new("after").long("after")).arg(Arg::new("format").short('f').long("format")))
+    }
+
+    fn script(shell: Shell) -> String {
+        let mut out = vec![];
+        generate(shell, &mut command(), &mut out).unwrap();
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn value_options_are_collected_once_from_every_subcommand() {
+        assert_eq!(value_options(&command()), ["--vault", "--format", "-f", "--after"]);
+    }
+
+    #[test]
+    fn scripts_complete_ids_after_target_subcommands() {
+        let bash = script(Shell::Bash);
+        assert!(bash.contains("re='^ get note ([^ ]+ ){0,0}$'; [[ $line =~ $re ]] && kind=notes"));
+        assert!(bash.contains("re='^ source merge ([^ ]+ ){0,1}$'; [[ $line =~ $re ]] && kind=sources"));
+        assert!(bash.contains(r#"local options=" --vault --format -f --after " note_options=" --after --note ""#));
+
+        for shell in [Shell::Zsh, Shell::Fish] {
+            let script = script(shell);
+            assert!(script.contains("__complete"), "{shell}");
+            assert!(script.contains("^ graph path ([^ ]+ ){0,1}$"), "{shell}");
+        }
+    }
+
+    #[test]
+    fn other_shells_get_the_static_completions_only() {
+        let script = script(Shell::PowerShell);
+
+        assert!(script.contains("spark"));
+        assert!(!script.contains("__complete"));
+    }
+}
@@ -1,6 +1,6 @@
 use std::io;
 
-use crate::{models::error::DbError, util::error::UtilError};
+use crate::{config::error::ConfigError, lsp::error::LspError, models::error::DbError, publish::error::PublishError, registry::error::RegistryError, server::error::ServerError, sync::error::SyncError, tui::error::TuiError, util::error::UtilError, vault::error::VaultError, watch::error::WatchError};
 
 #[derive(thiserror::Error, Debug)]
 pub enum CliError {
@@ -13,6 +13,9 @@ pub enum CliError {
     #[error("Invalid arguments")]
     InvalidArguments,
 
+    #[error("{0} does not support --dry-run")]
+    DryRunNotSupported(String),
+
     #[error("Invalid digit")]
     InvalidDigit,
 
@@ -51,24 +54,79 @@ pub enum Arguments {
 
 impl From<DbError> for CliError {
     fn from(value: DbError) -> Self {
+        CliError::Generic(value.into())
+    }
+}
+
+impl From<VaultError> for CliError {
+    fn from(value: VaultError) -> Self {
         match value {
+            VaultError::NoteNotFound => CliError::NoteNotFound,
+            VaultError::SourceNotFound => CliError::ObjectNotFound,
+            VaultError::NoteTitleEmpty => CliError::NoteTitleEmpty,
+            VaultError::InvalidReference => CliError::InvalidReference,
+            VaultError::ReferenceDoesNotExist(title) => CliError::ReferenceDoesNotExist(title),
             e => CliError::Generic(e.into())
         }
     }
 }
 
+impl From<ConfigError> for CliError {
+    fn from(value: ConfigError) -> Self {
+        CliError::Generic(value.into())
+    }
+}
+
+impl From<LspError> for CliError {
+    fn from(value: LspError) -> Self {
+        CliError::Generic(value.into())
+    }
+}
+
+impl From<PublishError> for CliError {
+    fn from(value: PublishError) -> Self {
+        CliError::Generic(value.into())
+    }
+}
+
+impl From<RegistryError> for CliError {
+    fn from(value: RegistryError) -> Self {
+        CliError::Generic(value.into())
+    }
+}
+
+impl From<ServerError> for CliError {
+    fn from(value: ServerError) -> Self {
+        CliError::Generic(value.into())
+    }
+}
+
+impl From<SyncError> for CliError {
+    fn from(value: SyncError) -> Self {
+        CliError::Generic(value.into())
+    }
+}
+
+impl From<TuiError> for CliError {
+    fn from(value: TuiError) -> Self {
+        CliError::Generic(value.into())
+    }
+}
+
 impl From<UtilError> for CliError {
     fn from(value: UtilError) -> Self {
-        match value {
-            e => CliError::Generic(e.into())
-        }
+        CliError::Generic(value.into())
+    }
+}
+
+impl From<WatchError> for CliError {
+    fn from(value: WatchError) -> Self {
+        CliError::Generic(value.into())
     }
 }
 
 impl From<io::Error> for CliError {
     fn from(value: io::Error) -> Self {
-        match value {
-            e => CliError::Generic(e.into())
-        }
+        CliError::Generic(value.into())
     }
 }
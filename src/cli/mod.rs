@@ -1,8 +1,8 @@
 use std::ops::RangeInclusive;
-use boolinator::Boolinator;
 
 use clap::{ArgMatches, error::{Error, ErrorKind, DefaultFormatter}};
 
+pub mod completions;
 pub mod error;
 pub mod subcommands;
 
@@ -22,7 +22,7 @@ pub trait ParseArgs {
     {
         args
             .get_one::<T>(name)
-            .map(|val| *val)
+            .copied()
     }
 
     fn parse_nullable_string(args: &ArgMatches, name: &str) -> Option<Option<String>> {
@@ -47,11 +47,7 @@ pub trait ParseArgs {
             .get_one::<String>(name)
             .map(String::from);
 
-        if value.is_none() {
-            return None;
-        }
-
-        let value = value.unwrap();
+        let value = value?;
 
         if value.trim() == "-" {
             return Some(None);
@@ -64,17 +60,25 @@ pub trait ParseArgs {
             })
             .unwrap();
 
-        range.contains(&value)
-            .ok_or(Error::new(ErrorKind::InvalidValue))
-            .map_err(|e:  Error<DefaultFormatter>| e.exit())
-        .ok();
+        if !range.contains(&value) {
+            let e: Error<DefaultFormatter> = Error::new(ErrorKind::InvalidValue);
+            e.exit()
+        }
 
         Some(Some(value))
     }
 
     fn parse_vector_int(args: &ArgMatches, name: &str) -> Option<Vec<i32>> {
         if let Some(values) = args.get_many::<i32>(name) {
-            return Some(values.map(|v| *v).collect())
+            return Some(values.copied().collect())
+        }
+
+        None
+    }
+
+    fn parse_vector_string(args: &ArgMatches, name: &str) -> Option<Vec<String>> {
+        if let Some(values) = args.get_many::<String>(name) {
+            return Some(values.cloned().collect())
         }
 
         None
@@ -1,8 +1,8 @@
 use std::fs;
 
-use clap::{arg, value_parser, ArgMatches, Command};
+use clap::{arg, value_parser, ArgAction, ArgMatches, Command};
 
-use crate::util::{parse, NoteFromMd, References};
+use crate::{util::{parse, NoteFromMd}, vault::SequencePosition};
 
 use super::{error::CliError, ParseArgs};
 
@@ -25,7 +25,21 @@ pub fn list_notes() -> Command {
     Command::new("notes")
         .args([
             arg!(--id "Show note id"), 
-            arg!(--title "Show note title")
+            arg!(--title "Show note title"),
+            arg!(--sequence "Show note sequence position"),
+            arg!(--tree "Show notes with a sequence position as a tree")
+        ])
+}
+
+pub fn search() -> Command {
+    Command::new("search")
+        .about("Find notes by title, alias or contents")
+        .args([
+            arg!(<query> "Text to look for, case insensitive")
+                .value_parser(value_parser!(String)),
+            arg!(--id "Show note id"), 
+            arg!(--title "Show note title"),
+            arg!(--sequence "Show note sequence position")
         ])
 }
 
@@ -33,19 +47,28 @@ pub fn list_sources() -> Command {
     Command::new("sources")
         .args([
             arg!(--id "Show source id"), 
-            arg!(--title "Show source title")
+            arg!(--title "Show source title"),
+            arg!(--"cited-by-count" "Show how many notes cite the source")
         ])
 }
 
 pub fn get() -> Command {
     Command::new("get")
         .subcommand(get_note())
+        .subcommand(get_source())
+}
+
+pub fn get_source() -> Command {
+    Command::new("source")
+        .about("Show a source and every note citing it")
+        .arg(arg!(<id> "Id or title of a source to get")
+            .value_parser(value_parser!(String)))
 }
 
 pub fn get_note() -> Command {
     Command::new("note")
         .args([
-            arg!(<id> "Id of a note to get")
+            arg!(<id> "Id, title or alias of a note to get")
                 .required(true)
                 .value_parser(value_parser!(String)),
             arg!(-p --path <path> "Will output the note there")
@@ -54,6 +77,21 @@ pub fn get_note() -> Command {
         ])
 }
 
+pub fn new() -> Command {
+    Command::new("new")
+        .about("Add a note at a position in the sequence of notes")
+        .args([
+            arg!(-p --path <path> "Path to .md file in compatible format")
+                .value_parser(value_parser!(String))
+                .required(true),
+            arg!(--after <id> "Branch off the note with this id, 1 branches into 1a, 1a into 1a1")
+                .value_parser(value_parser!(String))
+                .conflicts_with("sequence"),
+            arg!(--sequence <sequence> "Exact sequence position, like 1a2")
+                .value_parser(value_parser!(String))
+        ])
+}
+
 pub fn update() -> Command {
     Command::new("update")
         .args([
@@ -72,6 +110,234 @@ pub fn set() -> Command {
         ])
 }
 
+pub fn rename() -> Command {
+    Command::new("rename")
+        .about("Rename a note, keeping the old title as an alias")
+        .args([
+            arg!(<id> "Id, title or alias of the note")
+                .value_parser(value_parser!(String)),
+            arg!(<title> "New title")
+                .value_parser(value_parser!(String)),
+            arg!(--rewrite "Rewrite [[Old title]] mentions in the contents of every note")
+        ])
+}
+
+pub fn source() -> Command {
+    Command::new("source")
+        .about("Manage sources")
+        .arg_required_else_help(true)
+        .subcommand(Command::new("rename")
+            .args([
+                arg!(<id> "Id or title of the source")
+                    .value_parser(value_parser!(String)),
+                arg!(<title> "New title")
+                    .value_parser(value_parser!(String))
+            ]))
+        .subcommand(Command::new("merge")
+            .about("Repoint every citation of <from> to <into> and delete <from>")
+            .args([
+                arg!(<from> "Id or title of the source to merge")
+                    .value_parser(value_parser!(String)),
+                arg!(<into> "Id or title of the source to keep")
+                    .value_parser(value_parser!(String))
+            ]))
+        .subcommand(Command::new("delete")
+            .args([
+                arg!(<id> "Id or title of the source")
+                    .value_parser(value_parser!(String)),
+                arg!(--force "Delete the source even if notes cite it, removing the citations")
+            ]))
+}
+
+pub fn dedupe() -> Command {
+    Command::new("dedupe")
+        .about("Merge notes and sources whose titles differ only in case, whitespace or Unicode form")
+}
+
+pub fn related() -> Command {
+    Command::new("related")
+        .about("Suggest notes to link from shared sources, shared neighbours and similar text")
+        .args([
+            arg!(<id> "Id, title or alias of the note")
+                .value_parser(value_parser!(String)),
+            arg!(--limit <count> "How many suggestions to show")
+                .value_parser(value_parser!(usize))
+                .default_value("10")
+        ])
+}
+
+pub fn undo() -> Command {
+    Command::new("undo")
+        .about("Undo the last command which changed the vault")
+        .arg(arg!(--force "Undo even if the notes or sources it changed were changed since"))
+}
+
+pub fn redo() -> Command {
+    Command::new("redo")
+        .about("Redo the last undone command")
+        .arg(arg!(--force "Redo even if the notes or sources it changed were changed since it was undone"))
+}
+
+pub fn log() -> Command {
+    Command::new("log")
+        .about("List recent commands which can be undone, newest first")
+        .arg(arg!(--limit <count> "How many commands to show")
+            .value_parser(value_parser!(usize))
+            .default_value("20"))
+}
+
+pub fn publish() -> Command {
+    Command::new("publish")
+        .about("Render notes to a static HTML site with backlinks, a bibliography and a search index")
+        .args([
+            arg!(<outdir> "Directory to write the site to")
+                .value_parser(value_parser!(String)),
+            arg!(--note <id> "Publish only this note and the notes around it, can be repeated")
+                .value_parser(value_parser!(String))
+                .action(ArgAction::Append),
+            arg!(--depth <depth> "How many references away from --note notes to publish")
+                .value_parser(value_parser!(usize))
+                .default_value("0")
+        ])
+}
+
+pub fn watch() -> Command {
+    Command::new("watch")
+        .about("Set notes from markdown files in a directory whenever they are created or modified")
+        .args([
+            arg!(<dir> "Directory to watch, including subdirectories")
+                .value_parser(value_parser!(String)),
+            arg!(--delete "Delete the note of a file when the file is removed")
+        ])
+}
+
+pub fn sync() -> Command {
+    Command::new("sync")
+        .about("Sync notes both ways with markdown files in a directory, saving conflicting versions side by side")
+        .args([
+            arg!(<dir> "Directory of markdown files, created if missing")
+                .value_parser(value_parser!(String)),
+            arg!(--delete "Delete the notes of removed files instead of writing them again")
+        ])
+}
+
+pub fn completions() -> Command {
+    Command::new("completions")
+        .about("Print a completion script, completing note and source ids with their titles")
+        .arg(arg!(<shell> "Shell to complete for")
+            .value_parser(["bash", "zsh", "fish"]))
+}
+
+/// Used by the completion scripts, prints `id<TAB>title` lines.
+pub fn complete() -> Command {
+    Command::new("__complete")
+        .hide(true)
+        .arg(arg!(<kind> "What to complete")
+            .value_parser(["notes", "sources"]))
+}
+
+pub fn serve() -> Command {
+    Command::new("serve")
+        .about("Serve notes, sources and references as a JSON API")
+        .args([
+            arg!(--port <port> "Port to listen on")
+                .value_parser(value_parser!(u16))
+                .default_value("7878"),
+            arg!(--host <host> "Address to bind, anything but localhost exposes the vault to the network")
+                .value_parser(value_parser!(String))
+                .default_value("127.0.0.1")
+        ])
+}
+
+pub fn lsp() -> Command {
+    Command::new("lsp")
+        .about("Run a language server for note markdown files over stdio")
+}
+
+pub fn tui() -> Command {
+    Command::new("tui")
+        .about("Browse, search and edit notes interactively")
+}
+
+pub fn graph() -> Command {
+    Command::new("graph")
+        .about("Inspect the graph of internal references")
+        .arg_required_else_help(true)
+        .subcommand(Command::new("stats")
+            .about("Report orphans, hubs, outward-only notes, dead ends and connected components")
+            .args([
+                arg!(--hubs <count> "How many hubs to show at most")
+                    .value_parser(value_parser!(usize))
+                    .default_value("10"),
+                arg!(--"min-incoming" <count> "How many notes must link to a note for it to be a hub")
+                    .value_parser(value_parser!(usize))
+                    .default_value("2")
+            ]))
+        .subcommand(Command::new("path")
+            .about("Shortest chain of internal references from <from> to <to>")
+            .args([
+                arg!(<from> "Id, title or alias of the first note")
+                    .value_parser(value_parser!(String)),
+                arg!(<to> "Id, title or alias of the last note")
+                    .value_parser(value_parser!(String)),
+                arg!(--undirected "Follow references in both directions")
+            ]))
+        .subcommand(Command::new("around")
+            .about("Notes within --depth references of a note, in either direction")
+            .args([
+                arg!(<id> "Id, title or alias of the note")
+                    .value_parser(value_parser!(String)),
+                arg!(--depth <depth> "Maximum number of hops")
+                    .value_parser(value_parser!(usize))
+                    .default_value("1")
+            ]))
+}
+
+pub fn vault() -> Command {
+    Command::new("vault")
+        .about("Manage named vaults")
+        .arg_required_else_help(true)
+        .subcommand(Command::new("list"))
+        .subcommand(Command::new("add")
+            .args([
+                arg!(<name> "Name of the vault")
+                    .value_parser(value_parser!(String)),
+                arg!(<path> "Path to the vault database, created on first use")
+                    .value_parser(value_parser!(String))
+            ]))
+        .subcommand(Command::new("remove")
+            .about("Forget the vault, its database is left untouched")
+            .args([
+                arg!(<name> "Name of the vault")
+                    .value_parser(value_parser!(String))
+            ]))
+        .subcommand(Command::new("default")
+            .args([
+                arg!(<name> "Name of the vault used when --vault is not given")
+                    .value_parser(value_parser!(String))
+            ]))
+}
+
+pub fn config() -> Command {
+    Command::new("config")
+        .about("Inspect and change the configuration")
+        .arg_required_else_help(true)
+        .subcommand(Command::new("get")
+            .args([
+                arg!(<key> "Configuration key, nested keys are joined with dots")
+                    .value_parser(value_parser!(String))
+            ]))
+        .subcommand(Command::new("set")
+            .args([
+                arg!(<key> "Configuration key, nested keys are joined with dots")
+                    .value_parser(value_parser!(String)),
+                arg!(<value> "New value")
+                    .value_parser(value_parser!(String)),
+                arg!(--local "Set the value only for the selected named vault")
+            ]))
+        .subcommand(Command::new("list"))
+}
+
 impl ParseArgs for NoteFromMd {}
 impl TryFrom<&ArgMatches> for NoteFromMd {
     type Error = CliError;
@@ -88,6 +354,72 @@ impl TryFrom<&ArgMatches> for NoteFromMd {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct NewNote {
+    pub note: NoteFromMd,
+    pub position: SequencePosition
+}
+
+impl ParseArgs for NewNote {}
+
+impl TryFrom<&ArgMatches> for NewNote {
+    type Error = CliError;
+
+    fn try_from(value: &ArgMatches) -> Result<Self, Self::Error> {
+        let note = NoteFromMd::try_from(value)?;
+        let after = Self::parse_option_string(value, "after");
+        let sequence = Self::parse_option_string(value, "sequence");
+
+        let position = match (after, sequence) {
+            (Some(id), _) => SequencePosition::After(id),
+            (_, Some(sequence)) => SequencePosition::At(sequence),
+            (None, None) => SequencePosition::Next
+        };
+
+        Ok(NewNote { note, position })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ListNotes {
+    pub fields: NoteFields,
+    pub tree: bool
+}
+
+impl ParseArgs for ListNotes {}
+
+impl TryFrom<&ArgMatches> for ListNotes {
+    type Error = CliError;
+
+    fn try_from(value: &ArgMatches) -> Result<Self, Self::Error> {
+        let fields = NoteFields::try_from(value)?;
+        let tree = Self::parse_option(value, "tree")
+            .unwrap_or(false);
+
+        Ok(ListNotes { fields, tree })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchNotes {
+    pub query: String,
+    pub fields: NoteFields
+}
+
+impl ParseArgs for SearchNotes {}
+
+impl TryFrom<&ArgMatches> for SearchNotes {
+    type Error = CliError;
+
+    fn try_from(value: &ArgMatches) -> Result<Self, Self::Error> {
+        let query = Self::parse_option_string(value, "query")
+            .ok_or(CliError::InternalError)?;
+        let fields = NoteFields::try_from(value)?;
+
+        Ok(SearchNotes { query, fields })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct NoteFields {
     pub items: Vec<NoteField>
@@ -96,7 +428,18 @@ pub struct NoteFields {
 #[derive(Debug, Clone)]
 pub enum NoteField {
     Id,
-    Title
+    Title,
+    Sequence
+}
+
+impl NoteField {
+    pub fn name(&self) -> &'static str {
+        match self {
+            NoteField::Id => "id",
+            NoteField::Title => "title",
+            NoteField::Sequence => "sequence"
+        }
+    }
 }
 
 impl Default for NoteFields {
@@ -117,16 +460,17 @@ impl TryFrom<&ArgMatches> for NoteFields {
             .unwrap_or(false);
         let title = Self::parse_option(value, "title")
             .unwrap_or(false);
+        let sequence = Self::parse_option(value, "sequence")
+            .unwrap_or(false);
 
-        let list;
-
-        list = if !title && !id {
+        let list = if !title && !id && !sequence {
              NoteFields::default()
         }
         else {
              let mut items = Vec::new();
              if id { items.push(NoteField::Id) }
              if title { items.push(NoteField::Title)}
+             if sequence { items.push(NoteField::Sequence)}
 
              NoteFields { items }
         };
@@ -143,7 +487,18 @@ pub struct SourceFields {
 #[derive(Debug, Clone)]
 pub enum SourceField {
     Id,
-    Title
+    Title,
+    CitedByCount
+}
+
+impl SourceField {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SourceField::Id => "id",
+            SourceField::Title => "title",
+            SourceField::CitedByCount => "cited_by_count"
+        }
+    }
 }
 
 impl Default for SourceFields {
@@ -164,16 +519,17 @@ impl TryFrom<&ArgMatches> for SourceFields {
             .unwrap_or(false);
         let title = Self::parse_option(value, "title")
             .unwrap_or(false);
+        let cited_by_count = Self::parse_option(value, "cited-by-count")
+            .unwrap_or(false);
 
-        let list;
-
-        list = if !title && !id {
+        let list = if !title && !id && !cited_by_count {
              SourceFields::default()
         }
         else {
              let mut items = Vec::new();
              if id { items.push(SourceField::Id) }
              if title { items.push(SourceField::Title)}
+             if cited_by_count { items.push(SourceField::CitedByCount) }
 
              SourceFields { items }
         };
@@ -207,3 +563,420 @@ impl TryFrom<&ArgMatches> for GetNote {
         Ok(get_note)
     }
 }
+
+#[derive(Debug, Clone)]
+pub struct VaultEntry {
+    pub name: String,
+    pub path: String
+}
+
+impl ParseArgs for VaultEntry { }
+
+impl TryFrom<&ArgMatches> for VaultEntry {
+    type Error = CliError;
+
+    fn try_from(value: &ArgMatches) -> Result<Self, Self::Error> {
+        let name = Self::parse_option_string(value, "name")
+            .ok_or(CliError::InternalError)?;
+        let path = Self::parse_option_string(value, "path")
+            .ok_or(CliError::InternalError)?;
+
+        Ok(VaultEntry { name, path })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VaultName {
+    pub name: String
+}
+
+impl ParseArgs for VaultName { }
+
+impl TryFrom<&ArgMatches> for VaultName {
+    type Error = CliError;
+
+    fn try_from(value: &ArgMatches) -> Result<Self, Self::Error> {
+        let name = Self::parse_option_string(value, "name")
+            .ok_or(CliError::InternalError)?;
+
+        Ok(VaultName { name })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfigKey {
+    pub key: String
+}
+
+impl ParseArgs for ConfigKey { }
+
+impl TryFrom<&ArgMatches> for ConfigKey {
+    type Error = CliError;
+
+    fn try_from(value: &ArgMatches) -> Result<Self, Self::Error> {
+        let key = Self::parse_option_string(value, "key")
+            .ok_or(CliError::InternalError)?;
+
+        Ok(ConfigKey { key })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfigEntry {
+    pub key: String,
+    pub value: String,
+    pub local: bool
+}
+
+impl ParseArgs for ConfigEntry { }
+
+impl TryFrom<&ArgMatches> for ConfigEntry {
+    type Error = CliError;
+
+    fn try_from(value: &ArgMatches) -> Result<Self, Self::Error> {
+        let key = Self::parse_option_string(value, "key")
+            .ok_or(CliError::InternalError)?;
+        let entry_value = Self::parse_option_string(value, "value")
+            .ok_or(CliError::InternalError)?;
+        let local = Self::parse_option(value, "local")
+            .unwrap_or(false);
+
+        Ok(ConfigEntry { key, value: entry_value, local })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Dedupe {
+    pub dry_run: bool
+}
+
+impl ParseArgs for Dedupe { }
+
+impl TryFrom<&ArgMatches> for Dedupe {
+    type Error = CliError;
+
+    fn try_from(value: &ArgMatches) -> Result<Self, Self::Error> {
+        let dry_run = Self::parse_option(value, "dry-run")
+            .unwrap_or(false);
+
+        Ok(Dedupe { dry_run })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RenameNote {
+    pub id: String,
+    pub title: String,
+    pub rewrite: bool,
+    pub dry_run: bool
+}
+
+impl ParseArgs for RenameNote { }
+
+impl TryFrom<&ArgMatches> for RenameNote {
+    type Error = CliError;
+
+    fn try_from(value: &ArgMatches) -> Result<Self, Self::Error> {
+        let id = Self::parse_option_string(value, "id")
+            .ok_or(CliError::InternalError)?;
+        let title = Self::parse_option_string(value, "title")
+            .ok_or(CliError::InternalError)?;
+        let rewrite = Self::parse_option(value, "rewrite")
+            .unwrap_or(false);
+        let dry_run = Self::parse_option(value, "dry-run")
+            .unwrap_or(false);
+
+        Ok(RenameNote { id, title, rewrite, dry_run })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RenameSource {
+    pub id: String,
+    pub title: String
+}
+
+impl ParseArgs for RenameSource { }
+
+impl TryFrom<&ArgMatches> for RenameSource {
+    type Error = CliError;
+
+    fn try_from(value: &ArgMatches) -> Result<Self, Self::Error> {
+        let id = Self::parse_option_string(value, "id")
+            .ok_or(CliError::InternalError)?;
+        let title = Self::parse_option_string(value, "title")
+            .ok_or(CliError::InternalError)?;
+
+        Ok(RenameSource { id, title })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MergeSources {
+    pub from: String,
+    pub into: String
+}
+
+impl ParseArgs for MergeSources { }
+
+impl TryFrom<&ArgMatches> for MergeSources {
+    type Error = CliError;
+
+    fn try_from(value: &ArgMatches) -> Result<Self, Self::Error> {
+        let from = Self::parse_option_string(value, "from")
+            .ok_or(CliError::InternalError)?;
+        let into = Self::parse_option_string(value, "into")
+            .ok_or(CliError::InternalError)?;
+
+        Ok(MergeSources { from, into })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DeleteSource {
+    pub id: String,
+    pub force: bool
+}
+
+impl ParseArgs for DeleteSource { }
+
+impl TryFrom<&ArgMatches> for DeleteSource {
+    type Error = CliError;
+
+    fn try_from(value: &ArgMatches) -> Result<Self, Self::Error> {
+        let id = Self::parse_option_string(value, "id")
+            .ok_or(CliError::InternalError)?;
+        let force = Self::parse_option(value, "force")
+            .unwrap_or(false);
+
+        Ok(DeleteSource { id, force })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GetSource {
+    pub id: String
+}
+
+impl ParseArgs for GetSource { }
+
+impl TryFrom<&ArgMatches> for GetSource {
+    type Error = CliError;
+
+    fn try_from(value: &ArgMatches) -> Result<Self, Self::Error> {
+        let id = Self::parse_option_string(value, "id")
+            .ok_or(CliError::InternalError)?;
+
+        Ok(GetSource { id })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GraphStatsArgs {
+    pub hubs: usize,
+    pub min_incoming: usize
+}
+
+impl ParseArgs for GraphStatsArgs { }
+
+impl TryFrom<&ArgMatches> for GraphStatsArgs {
+    type Error = CliError;
+
+    fn try_from(value: &ArgMatches) -> Result<Self, Self::Error> {
+        let hubs = Self::parse_option(value, "hubs")
+            .unwrap_or(10);
+        let min_incoming = Self::parse_option(value, "min-incoming")
+            .unwrap_or(2);
+
+        Ok(GraphStatsArgs { hubs, min_incoming })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GraphPath {
+    pub from: String,
+    pub to: String,
+    pub undirected: bool
+}
+
+impl ParseArgs for GraphPath { }
+
+impl TryFrom<&ArgMatches> for GraphPath {
+    type Error = CliError;
+
+    fn try_from(value: &ArgMatches) -> Result<Self, Self::Error> {
+        let from = Self::parse_option_string(value, "from")
+            .ok_or(CliError::InternalError)?;
+        let to = Self::parse_option_string(value, "to")
+            .ok_or(CliError::InternalError)?;
+        let undirected = Self::parse_option(value, "undirected")
+            .unwrap_or(false);
+
+        Ok(GraphPath { from, to, undirected })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GraphAround {
+    pub id: String,
+    pub depth: usize
+}
+
+impl ParseArgs for GraphAround { }
+
+impl TryFrom<&ArgMatches> for GraphAround {
+    type Error = CliError;
+
+    fn try_from(value: &ArgMatches) -> Result<Self, Self::Error> {
+        let id = Self::parse_option_string(value, "id")
+            .ok_or(CliError::InternalError)?;
+        let depth = Self::parse_option(value, "depth")
+            .unwrap_or(1);
+
+        Ok(GraphAround { id, depth })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RelatedNotes {
+    pub id: String,
+    pub limit: usize
+}
+
+impl ParseArgs for RelatedNotes { }
+
+impl TryFrom<&ArgMatches> for RelatedNotes {
+    type Error = CliError;
+
+    fn try_from(value: &ArgMatches) -> Result<Self, Self::Error> {
+        let id = Self::parse_option_string(value, "id")
+            .ok_or(CliError::InternalError)?;
+        let limit = Self::parse_option(value, "limit")
+            .unwrap_or(10);
+
+        Ok(RelatedNotes { id, limit })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct History {
+    pub force: bool
+}
+
+impl ParseArgs for History { }
+
+impl TryFrom<&ArgMatches> for History {
+    type Error = CliError;
+
+    fn try_from(value: &ArgMatches) -> Result<Self, Self::Error> {
+        let force = Self::parse_option(value, "force")
+            .unwrap_or(false);
+
+        Ok(History { force })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OperationLog {
+    pub limit: usize
+}
+
+impl ParseArgs for OperationLog { }
+
+impl TryFrom<&ArgMatches> for OperationLog {
+    type Error = CliError;
+
+    fn try_from(value: &ArgMatches) -> Result<Self, Self::Error> {
+        let limit = Self::parse_option(value, "limit")
+            .unwrap_or(20);
+
+        Ok(OperationLog { limit })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Publish {
+    pub outdir: String,
+    pub notes: Vec<String>,
+    pub depth: usize
+}
+
+impl ParseArgs for Publish { }
+
+impl TryFrom<&ArgMatches> for Publish {
+    type Error = CliError;
+
+    fn try_from(value: &ArgMatches) -> Result<Self, Self::Error> {
+        let outdir = Self::parse_option_string(value, "outdir")
+            .ok_or(CliError::InternalError)?;
+        let notes = Self::parse_vector_string(value, "note")
+            .unwrap_or_default();
+        let depth = Self::parse_option(value, "depth")
+            .unwrap_or(0);
+
+        Ok(Publish { outdir, notes, depth })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Watch {
+    pub dir: String,
+    pub delete: bool
+}
+
+impl ParseArgs for Watch { }
+
+impl TryFrom<&ArgMatches> for Watch {
+    type Error = CliError;
+
+    fn try_from(value: &ArgMatches) -> Result<Self, Self::Error> {
+        let dir = Self::parse_option_string(value, "dir")
+            .ok_or(CliError::InternalError)?;
+        let delete = Self::parse_option(value, "delete")
+            .unwrap_or(false);
+
+        Ok(Watch { dir, delete })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Sync {
+    pub dir: String,
+    pub delete: bool
+}
+
+impl ParseArgs for Sync { }
+
+impl TryFrom<&ArgMatches> for Sync {
+    type Error = CliError;
+
+    fn try_from(value: &ArgMatches) -> Result<Self, Self::Error> {
+        let dir = Self::parse_option_string(value, "dir")
+            .ok_or(CliError::InternalError)?;
+        let delete = Self::parse_option(value, "delete")
+            .unwrap_or(false);
+
+        Ok(Sync { dir, delete })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Serve {
+    pub host: String,
+    pub port: u16
+}
+
+impl ParseArgs for Serve { }
+
+impl TryFrom<&ArgMatches> for Serve {
+    type Error = CliError;
+
+    fn try_from(value: &ArgMatches) -> Result<Self, Self::Error> {
+        let host = Self::parse_option_string(value, "host")
+            .unwrap_or("127.0.0.1".to_string());
+        let port = Self::parse_option(value, "port")
+            .unwrap_or(7878);
+
+        Ok(Serve { host, port })
+    }
+}
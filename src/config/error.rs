@@ -0,0 +1,26 @@
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("Unknown configuration key: {0}")]
+    UnknownKey(String),
+
+    #[error("Invalid value for {0}: {1}")]
+    InvalidValue(String, String),
+
+    #[error("Invalid configuration file {0}: {1}")]
+    InvalidConfig(String, String),
+
+    #[error("No named vault selected, use --vault or set a default vault")]
+    NoNamedVault,
+
+    #[error("Cannot open xdg directories")]
+    CannotOpenXdgDirectory,
+
+    #[error(transparent)]
+    Generic(#[from] anyhow::Error)
+}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(value: std::io::Error) -> Self {
+        ConfigError::Generic(value.into())
+    }
+}
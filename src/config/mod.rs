@@ -0,0 +1,283 @@
+use std::{env, fs, path::{Path, PathBuf}, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use toml::{Table, Value};
+
+use self::error::ConfigError;
+
+pub mod error;
+
+const CONFIG_FILE: &str = "config.toml";
+
+/// Settings read from `$XDG_CONFIG_HOME/spark/config.toml`, with keys from
+/// `$XDG_CONFIG_HOME/spark/vaults/<name>.toml` taking precedence for a named vault.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub output_format: OutputFormat,
+    pub editor: String,
+    pub id_format: IdFormat,
+    pub stubs: StubPolicy,
+    pub reference_order: ReferenceOrder,
+    pub markdown: MarkdownLayout,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdFormat {
+    /// 6 characters of base32.
+    #[default]
+    Short,
+    /// 12 characters of base32.
+    Long,
+    /// Random UUIDv4.
+    Uuid,
+    /// Creation time as `YYYYMMDDHHMM`.
+    Timestamp,
+}
+
+/// What happens when an internal reference names a note that doesn't exist.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StubPolicy {
+    /// Refuse to save the note.
+    #[default]
+    Error,
+    /// Create an empty note with the referenced title.
+    Create,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReferenceOrder {
+    #[default]
+    Title,
+    Id,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MarkdownLayout {
+    pub internal_list: ListStyle,
+    pub external_list: ListStyle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListStyle {
+    Numbered,
+    Bulleted,
+}
+
+impl FromStr for OutputFormat {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Value::String(s.to_string())
+            .try_into()
+            .map_err(|_| ConfigError::InvalidValue("output_format".to_string(), s.to_string()))
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            output_format: OutputFormat::default(),
+            editor: env::var("EDITOR").unwrap_or("vi".to_string()),
+            id_format: IdFormat::default(),
+            stubs: StubPolicy::default(),
+            reference_order: ReferenceOrder::default(),
+            markdown: MarkdownLayout::default(),
+        }
+    }
+}
+
+impl Default for MarkdownLayout {
+    fn default() -> Self {
+        Self {
+            internal_list: ListStyle::Numbered,
+            external_list: ListStyle::Bulleted,
+        }
+    }
+}
+
+impl Config {
+    /// Global configuration, overridden by the configuration of `vault` if provided.
+    pub fn load(vault: Option<&str>) -> Result<Self, ConfigError> {
+        let global = Self::global_path()?;
+        let mut table = Self::read_table(&global)?;
+        let config = Self::from_table(table.clone(), &global)?;
+
+        let Some(vault) = vault else {
+            return Ok(config)
+        };
+
+        // The global file is valid, so errors of the merged table come from the vault's file.
+        let path = Self::vault_path(vault)?;
+        merge(&mut table, Self::read_table(&path)?);
+
+        Self::from_table(table, &path)
+    }
+
+    /// Stores `value` under `key` in the global configuration, or in the configuration of `vault`.
+    pub fn set(key: &str, value: &str, vault: Option<&str>) -> Result<(), ConfigError> {
+        Self::default().get(key)?;
+
+        let path = match vault {
+            Some(vault) => Self::vault_path(vault)?,
+            None => Self::global_path()?,
+        };
+
+        let mut table = Self::read_table(&path)?;
+        insert(&mut table, key, Value::String(value.to_string()));
+
+        Self::from_table(table.clone(), &path)
+            .map_err(|_| ConfigError::InvalidValue(key.to_string(), value.to_string()))?;
+
+        let contents = toml::to_string(&table)
+            .map_err(|e| ConfigError::Generic(e.into()))?;
+        fs::write(path, contents)?;
+
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Result<String, ConfigError> {
+        self.list()?
+            .into_iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value)
+            .ok_or(ConfigError::UnknownKey(key.to_string()))
+    }
+
+    /// Every key with its effective value, nested tables joined with dots.
+    pub fn list(&self) -> Result<Vec<(String, String)>, ConfigError> {
+        let table = Table::try_from(self)
+            .map_err(|e| ConfigError::Generic(e.into()))?;
+
+        let mut items = vec![];
+        flatten("", &table, &mut items);
+
+        Ok(items)
+    }
+
+    fn from_table(table: Table, path: &Path) -> Result<Self, ConfigError> {
+        Value::Table(table)
+            .try_into()
+            .map_err(|e: toml::de::Error| ConfigError::InvalidConfig(path.display().to_string(), e.message().to_string()))
+    }
+
+    fn read_table(path: &Path) -> Result<Table, ConfigError> {
+        if !path.exists() {
+            return Ok(Table::new())
+        }
+
+        let contents = fs::read_to_string(path)?;
+        contents.parse()
+            .map_err(|e: toml::de::Error| ConfigError::InvalidConfig(path.display().to_string(), e.message().to_string()))
+    }
+
+    fn global_path() -> Result<PathBuf, ConfigError> {
+        Ok(Self::xdg()?.place_config_file(CONFIG_FILE)?)
+    }
+
+    fn vault_path(vault: &str) -> Result<PathBuf, ConfigError> {
+        Ok(Self::xdg()?.place_config_file(format!("vaults/{vault}.toml"))?)
+    }
+
+    fn xdg() -> Result<xdg::BaseDirectories, ConfigError> {
+        xdg::BaseDirectories::with_prefix("spark")
+            .map_err(|_| ConfigError::CannotOpenXdgDirectory)
+    }
+}
+
+fn merge(base: &mut Table, overrides: Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(value)) => merge(base, value),
+            (_, value) => { base.insert(key, value); },
+        }
+    }
+}
+
+fn insert(table: &mut Table, key: &str, value: Value) {
+    match key.split_once('.') {
+        Some((head, rest)) => {
+            let entry = table.entry(head)
+                .or_insert(Value::Table(Table::new()));
+
+            if !entry.is_table() {
+                *entry = Value::Table(Table::new());
+            }
+
+            if let Value::Table(nested) = entry {
+                insert(nested, rest, value);
+            }
+        },
+        None => { table.insert(key.to_string(), value); },
+    }
+}
+
+fn flatten(prefix: &str, table: &Table, items: &mut Vec<(String, String)>) {
+    for (key, value) in table {
+        let key = format!("{prefix}{key}");
+        match value {
+            Value::Table(nested) => flatten(&format!("{key}."), nested, items),
+            Value::String(value) => items.push((key, value.clone())),
+            value => items.push((key, value.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(text: &str) -> Table {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn merge_lets_later_layer_win_inside_nested_tables() {
+        let mut base = table("editor = 'vi'\nstubs = 'error'\n[markdown]\ninternal_list = 'numbered'\nexternal_list = 'bulleted'\n");
+
+        merge(&mut base, table("editor = 'nano'\n[markdown]\nexternal_list = 'numbered'\n"));
+
+        assert_eq!(base, table("editor = 'nano'\nstubs = 'error'\n[markdown]\ninternal_list = 'numbered'\nexternal_list = 'numbered'\n"));
+    }
+
+    #[test]
+    fn insert_creates_tables_of_dotted_keys() {
+        let mut base = table("markdown = 'flat'\n");
+
+        insert(&mut base, "markdown.internal_list", Value::String("bulleted".to_string()));
+        insert(&mut base, "editor", Value::String("nano".to_string()));
+
+        assert_eq!(base, table("editor = 'nano'\n[markdown]\ninternal_list = 'bulleted'\n"));
+    }
+
+    #[test]
+    fn flatten_joins_nested_keys_with_dots() {
+        let mut items = vec![];
+
+        flatten("", &table("editor = 'vi'\ncount = 3\n[markdown]\ninternal_list = 'numbered'\n"), &mut items);
+
+        let expected = [("count", "3"), ("editor", "vi"), ("markdown.internal_list", "numbered")]
+            .map(|(key, value)| (key.to_string(), value.to_string()));
+        assert_eq!(items, expected);
+    }
+
+    #[test]
+    fn from_table_names_the_file_with_the_invalid_value() {
+        let error = Config::from_table(table("stubs = 'sometimes'\n"), Path::new("vaults/work.toml"));
+
+        assert!(matches!(error, Err(ConfigError::InvalidConfig(path, _)) if path == "vaults/work.toml"));
+    }
+}
@@ -1,147 +1,745 @@
-use std::{fmt::Display, fs::File, io::Write};
+use std::{collections::HashMap, fmt::Display, fs::{self, File}, io::{self, IsTerminal, Write}, path::{Path, PathBuf}};
 
-use clap::ArgMatches;
-use console::style;
+use clap::{ArgMatches, Command};
+use clap_complete::Shell;
+use console::{style, Term};
+use dialoguer::FuzzySelect;
 use csv::Writer;
-use rusqlite::{Connection, Transaction};
 
-use crate::{cli::{error::CliError, subcommands::{GetNote, NoteField, NoteFields, SourceField, SourceFields}}, models::{external::ExternalReference, internal::InternalReference, note::{Note, NoteListItem}, sources::Source}, util::{generate_id, parse::note_to_md, NoteFromMd, Reference}};
+use crate::{cli::{completions, error::CliError, DefaultParser, ParseArgs, subcommands::{ConfigEntry, ConfigKey, Dedupe, DeleteSource, GetNote, GetSource, GraphAround, GraphPath, GraphStatsArgs, History, ListNotes, MergeSources, NewNote, NoteField, NoteFields, OperationLog, Publish, RelatedNotes, RenameNote, RenameSource, SearchNotes, Serve, SourceField, SourceFields, Sync, VaultEntry, VaultName, Watch}}, config::{error::ConfigError, Config, OutputFormat}, lsp, models::{note::{Note, NoteListItem}, sources::Source}, publish, registry::VaultRegistry, server, sync::{self, Action}, tui, util::{parse, sequence, NoteFromMd}, vault::{changes::NoteChanges, error::VaultError, SequencePosition, SetOutcome, Vault}, watch::{self, Change}};
 
 
 pub struct Controller {
-    pub conn: Connection,
+    pub vault: Vault,
+    /// Whether the user can be asked to pick between matching notes.
+    interactive: bool,
 }
 
 impl Controller {
-    pub fn new(conn: Connection) -> Self {
-        Self { conn }
+    pub fn new(vault: Vault) -> Self {
+        let interactive = io::stdin().is_terminal() && Term::stderr().is_term();
+
+        Self { vault, interactive }
     }
 
     pub fn handle_command(mut self, matches: ArgMatches) -> Result<&'static str, CliError> {
+        let dry_run = matches.get_flag("dry-run");
+
         match matches.subcommand() {
-            Some(("add", args)) => self.add(NoteFromMd::try_from(args)?),
+            Some(("add", args)) => self.add(NoteFromMd::try_from(args)?, dry_run),
+            Some(("new", args)) => self.new_note(NewNote::try_from(args)?, dry_run),
             Some(("list", args)) => self.list(args),
             Some(("get", args)) => self.get(args),
-            Some(("update", args)) => self.update(NoteFromMd::try_from(args)?),
-            Some(("set", args)) => self.set(NoteFromMd::try_from(args)?),
+            Some(("search", args)) => self.search(SearchNotes::try_from(args)?),
+            Some(("rename", args)) => self.rename(RenameNote::try_from(args)?),
+            Some(("source", args)) => self.source(args),
+            Some(("dedupe", args)) => self.dedupe(Dedupe::try_from(args)?),
+            Some(("graph", args)) => self.graph(args),
+            Some(("related", args)) => self.related(RelatedNotes::try_from(args)?),
+            Some(("publish", args)) => self.publish(Publish::try_from(args)?),
+            Some(("watch", args)) => self.watch(Watch::try_from(args)?),
+            Some(("sync", args)) => self.sync(Sync::try_from(args)?),
+            Some(("tui", _)) => self.tui(),
+            Some(("serve", args)) => self.serve(Serve::try_from(args)?),
+            Some(("lsp", _)) => self.lsp(),
+            Some(("__complete", args)) => self.complete(args),
+            Some(("undo", args)) => self.undo(History::try_from(args)?),
+            Some(("redo", args)) => self.redo(History::try_from(args)?),
+            Some(("log", args)) => self.log(OperationLog::try_from(args)?),
+            Some(("update", args)) => self.update(NoteFromMd::try_from(args)?, dry_run),
+            Some(("set", args)) => self.set(NoteFromMd::try_from(args)?, dry_run),
             _ => Ok("")
         }
     }
 
-    fn add(&mut self, note_from_md: NoteFromMd) -> Result<&'static str, CliError> {
-        let note: Note = (&note_from_md).into();
-        let note_id = note.id.clone();
-        if note_from_md.title.trim().is_empty() {
-            return Err(CliError::NoteTitleEmpty)
+    /// Handles `spark config`, `vault` is the named vault `--local` applies to.
+    pub fn handle_config_command(matches: &ArgMatches, vault: Option<&str>) -> Result<&'static str, CliError> {
+        match matches.subcommand() {
+            Some(("get", args)) => {
+                let key = ConfigKey::try_from(args)?;
+                println!("{}", Config::load(vault)?.get(&key.key)?);
+                Ok("")
+            },
+            Some(("set", args)) => {
+                let entry = ConfigEntry::try_from(args)?;
+                let vault = match entry.local {
+                    true => Some(vault.ok_or(ConfigError::NoNamedVault)?),
+                    false => None
+                };
+                Config::set(&entry.key, &entry.value, vault)?;
+                Ok("Configuration updated successfuly")
+            },
+            Some(("list", _)) => {
+                for (key, value) in Config::load(vault)?.list()? {
+                    println!("{key} = {value}");
+                }
+                Ok("")
+            },
+            _ => Ok("")
+        }
+    }
+
+    /// Handles `spark vault`, which works on the registry and never opens a database.
+    pub fn handle_vault_command(matches: &ArgMatches) -> Result<&'static str, CliError> {
+        let mut registry = VaultRegistry::load()?;
+
+        let message = match matches.subcommand() {
+            Some(("list", _)) => return Self::list_vaults(&registry),
+            Some(("add", args)) => {
+                let entry = VaultEntry::try_from(args)?;
+                registry.add(&entry.name, Path::new(&entry.path))?;
+                "Vault added successfuly"
+            },
+            Some(("remove", args)) => {
+                registry.remove(&VaultName::try_from(args)?.name)?;
+                "Vault removed successfuly"
+            },
+            Some(("default", args)) => {
+                registry.set_default(&VaultName::try_from(args)?.name)?;
+                "Default vault set successfuly"
+            },
+            _ => return Ok("")
+        };
+
+        registry.save()?;
+        Ok(message)
+    }
+
+    fn list_vaults(registry: &VaultRegistry) -> Result<&'static str, CliError> {
+        let mut wtr = Writer::from_writer(vec![]);
+
+        for (name, path) in &registry.vaults {
+            let path = path.to_string_lossy();
+            let default = match registry.default.as_ref() == Some(name) {
+                true => "default",
+                false => ""
+            };
+
+            wtr.write_record([name.as_str(), &path, default])
+                .map_err(|_| CliError::InternalError)?;
         }
 
-        let tx = self.conn.transaction().unwrap();
-        note.add(&tx)?;
-        note_from_md.references.internal.iter()
-            .try_for_each(|r| Self::add_internal_reference(r, &note_id, &tx))?;
-        note_from_md.references.external.iter()
-            .try_for_each(|r| Self::add_external_reference(r, &note_id, &tx))?;
-        tx.commit().unwrap();
+        Self::print_csv_record(wtr)?;
+
+        Ok("")
+    }
+
+    fn add(&mut self, note_from_md: NoteFromMd, dry_run: bool) -> Result<&'static str, CliError> {
+        let changes = self.write_note("add", note_from_md, dry_run, Vault::add_note)?;
+
+        if dry_run {
+            return self.print_changes(&changes, dry_run)
+        }
 
         Ok("Note added successfuly")
+    }
+
+    fn new_note(&mut self, new_note: NewNote, dry_run: bool) -> Result<&'static str, CliError> {
+        let position = match new_note.position {
+            SequencePosition::After(id) => SequencePosition::After(self.find_note(&id)?.id),
+            position => position
+        };
+        let changes = self.write_note("new", new_note.note, dry_run, |vault, note_from_md| vault.add_note_at(note_from_md, position))?;
 
+        if dry_run {
+            return self.print_changes(&changes, dry_run)
+        }
+        let note = changes.note;
+
+        let message = format!("Note added at {} successfuly", note.sequence.unwrap_or_default());
+        eprintln!("{}", style(message).bold().green());
+
+        Ok("")
     }
 
-    fn add_internal_reference(reference: &Reference, note_id: &str, tx: &Transaction) -> Result<(), CliError> {
-        match (&reference.id, &reference.title) {
-            (Some(id), _) => {
-                if InternalReference::exists(note_id, &id, &tx)? {
-                    return Ok(())
-                }
-                InternalReference::new(generate_id(), note_id.to_string(), id.to_string())
-                                .add(&tx)?
-            },
-            (_, Some(title)) => Self::add_internal_reference_by_title(title, note_id, tx)?,
-            (None, None) => Err(CliError::InvalidReference)?
+    fn rename(&mut self, rename: RenameNote) -> Result<&'static str, CliError> {
+        let note = self.find_note(&rename.id)?;
+        let result = match rename.dry_run {
+            true => self.vault.rename_note(&note.id, &rename.title, rename.rewrite, true)?,
+            false => {
+                let preview = self.vault.rename_note(&note.id, &rename.title, rename.rewrite, true)?;
+                let notes: Vec<String> = [note.id.clone()].into_iter()
+                    .chain(preview.rewritten.into_iter().map(|n| n.id))
+                    .collect();
+                let command = format!("rename {} to {}", note.title, rename.title);
+                self.vault.record(&command, &notes, &[], |vault| vault.rename_note(&note.id, &rename.title, rename.rewrite, false))?
+            }
+        };
+
+        let verb = if rename.dry_run { "Would rename" } else { "Renamed" };
+        println!("{verb} [{}] {} to {}", result.note.id, result.old_title, result.note.title);
+
+        let verb = if rename.dry_run { "Would rewrite" } else { "Rewrote" };
+        for note in &result.rewritten {
+            println!("{verb} mentions in [{}] {}", note.id, note.title);
         }
 
-        Ok(())
+        Ok("")
+    }
+
+    fn source(&mut self, args: &ArgMatches) -> Result<&'static str, CliError> {
+        match args.subcommand() {
+            Some(("rename", args)) => {
+                let rename = RenameSource::try_from(args)?;
+                let source = self.vault.find_source(&rename.id)?;
+                let command = format!("source rename {} to {}", source.title, rename.title);
+                self.vault.record(&command, &[], std::slice::from_ref(&source.id), |vault| vault.rename_source(&source.id, &rename.title))?;
+                Ok("Source renamed successfuly")
+            },
+            Some(("merge", args)) => {
+                let merge = MergeSources::try_from(args)?;
+                let from = self.vault.find_source(&merge.from)?;
+                let into = self.vault.find_source(&merge.into)?;
+                let command = format!("source merge {} into {}", from.title, into.title);
+                self.vault.record(&command, &[], &[from.id.clone(), into.id.clone()], |vault| vault.merge_sources(&from.id, &into.id))?;
+                Ok("Sources merged successfuly")
+            },
+            Some(("delete", args)) => {
+                let delete = DeleteSource::try_from(args)?;
+                let source = self.vault.find_source(&delete.id)?;
+                let command = format!("source delete {}", source.title);
+                self.vault.record(&command, &[], std::slice::from_ref(&source.id), |vault| vault.delete_source(&source.id, delete.force))?;
+                Ok("Source deleted successfuly")
+            },
+            _ => Ok("")
+        }
     }
 
-    fn add_internal_reference_by_title(title: &str, note_id: &str, tx: &Transaction) -> Result<(), CliError> {
-        let note = Note::get_by_title(title.to_string(), &tx)?;
-        if let Some(note) = note {
-            if InternalReference::exists(note_id, &note.id, &tx)? {
-                return Ok(())
+    fn dedupe(&mut self, dedupe: Dedupe) -> Result<&'static str, CliError> {
+        let duplicates = match dedupe.dry_run {
+            true => self.vault.find_duplicates()?,
+            false => {
+                let preview = self.vault.find_duplicates()?;
+                let notes: Vec<String> = preview.notes.iter().flatten().map(|note| note.id.clone()).collect();
+                let sources: Vec<String> = preview.sources.iter().flatten().map(|source| source.id.clone()).collect();
+                self.vault.record("dedupe", &notes, &sources, Vault::merge_duplicates)?
             }
-            InternalReference::new(generate_id(), note_id.to_string(), note.id)
-                .add(&tx)?;
+        };
+
+        let verb = if dedupe.dry_run { "Would merge" } else { "Merged" };
+        for group in &duplicates.notes {
+            let from: Vec<String> = group[1..].iter().map(|n| format!("[{}] {}", n.id, n.title)).collect();
+            println!("{verb} notes {} into [{}] {}", from.join(", "), group[0].id, group[0].title);
+        }
+        for group in &duplicates.sources {
+            let from: Vec<String> = group[1..].iter().map(|s| format!("[{}] {}", s.id, s.title)).collect();
+            println!("{verb} sources {} into [{}] {}", from.join(", "), group[0].id, group[0].title);
+        }
+
+        if duplicates.notes.is_empty() && duplicates.sources.is_empty() {
+            return Ok("No duplicates found")
         }
 
-        Err(CliError::ReferenceDoesNotExist(title.to_string()))
+        Ok("")
+    }
+
+    fn graph(&self, args: &ArgMatches) -> Result<&'static str, CliError> {
+        match args.subcommand() {
+            Some(("stats", args)) => self.graph_stats(GraphStatsArgs::try_from(args)?),
+            Some(("path", args)) => self.graph_path(GraphPath::try_from(args)?),
+            Some(("around", args)) => self.graph_around(GraphAround::try_from(args)?),
+            _ => Ok("")
+        }
     }
 
-    fn add_external_reference(reference: &Reference, note_id: &str, tx: &Transaction) -> Result<(), CliError> {
-        match (&reference.id, &reference.title) {
-            (Some(id), _) => {
-                if ExternalReference::exists(note_id, &id, &tx)? {
-                    return Ok(())
+    fn graph_path(&self, args: GraphPath) -> Result<&'static str, CliError> {
+        let from = self.find_note(&args.from)?;
+        let to = self.find_note(&args.to)?;
+
+        match self.vault.graph()?.path(&from.id, &to.id, args.undirected) {
+            Some(path) => self.print_notes(&path, &NoteFields::default())?,
+            None => return Ok("No path found")
+        }
+
+        Ok("")
+    }
+
+    fn graph_around(&self, args: GraphAround) -> Result<&'static str, CliError> {
+        let note = self.find_note(&args.id)?;
+        let around = self.vault.graph()?.around(&note.id, args.depth);
+
+        let distances: Vec<String> = around.iter()
+            .map(|(_, distance)| distance.to_string())
+            .collect();
+        let records = around.iter()
+            .zip(&distances)
+            .map(|((note, _), distance)| vec![note.id.as_str(), note.title.as_str(), distance.as_str()])
+            .collect();
+
+        self.print_records(&["id", "title", "distance"], records)?;
+
+        Ok("")
+    }
+
+    fn graph_stats(&self, args: GraphStatsArgs) -> Result<&'static str, CliError> {
+        let stats = self.vault.graph_stats(args.hubs, args.min_incoming)?;
+
+        match self.vault.config().output_format {
+            OutputFormat::Csv => {
+                println!("{} notes, {} links", stats.notes, stats.links);
+
+                println!("Orphans ({}):", stats.orphans.len());
+                for note in &stats.orphans {
+                    println!("  {note}");
+                }
+
+                println!("Hubs, linked to by at least {} notes ({}):", args.min_incoming, stats.hubs.len());
+                for hub in &stats.hubs {
+                    println!("  {} <- {}", hub.note, hub.incoming);
+                }
+
+                println!("Outward only ({}):", stats.outward_only.len());
+                for note in &stats.outward_only {
+                    println!("  {note}");
+                }
+
+                println!("Dead ends ({}):", stats.dead_ends.len());
+                for note in &stats.dead_ends {
+                    println!("  {note}");
                 }
-                ExternalReference::new(generate_id(), note_id.to_string(), id.to_string())
-                                .add(&tx)?
+
+                let sizes: Vec<String> = stats.components.iter().map(|c| c.len().to_string()).collect();
+                println!("Components ({}): {}", stats.components.len(), sizes.join(", "));
             },
-            (_, Some(title)) => Self::add_external_reference_by_title(title, note_id, tx)?,
-            (None, None) => Err(CliError::InvalidReference)?
+            OutputFormat::Json => {
+                let item = |note: &NoteListItem| serde_json::json!({ "id": note.id, "title": note.title });
+                let object = serde_json::json!({
+                    "notes": stats.notes,
+                    "links": stats.links,
+                    "orphans": stats.orphans.iter().map(item).collect::<Vec<_>>(),
+                    "hubs": stats.hubs.iter()
+                        .map(|hub| serde_json::json!({ "id": hub.note.id, "title": hub.note.title, "incoming": hub.incoming }))
+                        .collect::<Vec<_>>(),
+                    "outward_only": stats.outward_only.iter().map(item).collect::<Vec<_>>(),
+                    "dead_ends": stats.dead_ends.iter().map(item).collect::<Vec<_>>(),
+                    "components": stats.components.iter()
+                        .map(|component| component.iter().map(item).collect::<Vec<_>>())
+                        .collect::<Vec<_>>()
+                });
+                let contents = serde_json::to_string_pretty(&object)
+                    .map_err(|_| CliError::InternalError)?;
+                println!("{contents}");
+            }
+        }
+
+        Ok("")
+    }
+
+    /// Note by id, title or alias, or by a prefix of either. When several notes
+    /// match the user picks one.
+    fn find_note(&self, query: &str) -> Result<Note, CliError> {
+        let mut notes = self.vault.match_notes(query)?;
+
+        let note = match notes.len() {
+            0 => return Err(CliError::NoteNotFound),
+            1 => notes.remove(0),
+            _ => self.pick_note(query, notes)?
         };
 
-        Ok(())
+        Ok(self.vault.get_note(&note.id)?)
     }
 
-    fn add_external_reference_by_title(title: &str, note_id: &str, tx: &Transaction) -> Result<(), CliError> {
-        let source = Source::get_by_title(title.to_string(), &tx)?;
-        let source = source.unwrap_or(Source::create(generate_id(), title.to_string(), &tx)?);
+    fn pick_note(&self, query: &str, mut notes: Vec<NoteListItem>) -> Result<NoteListItem, CliError> {
+        if !self.interactive {
+            return Err(CliError::CannotInteract)
+        }
 
-        if ExternalReference::exists(note_id, &source.id, &tx)? {
-            return Ok(())
+        notes.sort_by(|a, b| a.title.cmp(&b.title));
+        let items: Vec<String> = notes.iter()
+            .map(|note| format!("[{}] {}", note.id, note.title))
+            .collect();
+
+        let selection = FuzzySelect::new()
+            .with_prompt(format!("Several notes match \"{query}\""))
+            .items(&items)
+            .default(0)
+            .interact_opt()
+            .map_err(|_| CliError::CannotReadUserInput)?;
+
+        match selection {
+            Some(index) => Ok(notes.swap_remove(index)),
+            None => Err(CliError::NoteNotFound)
         }
+    }
 
-        ExternalReference::new(generate_id(), note_id.to_string(), source.id)
-            .add(&tx)?;
+    pub fn handle_completions_command(args: &ArgMatches, mut command: Command) -> Result<&'static str, CliError> {
+        let shell = DefaultParser::parse_option_string(args, "shell")
+            .ok_or(CliError::InternalError)?
+            .parse::<Shell>()
+            .map_err(|_| CliError::InvalidArguments)?;
 
-        Ok(())
+        completions::generate(shell, &mut command, &mut io::stdout())?;
+
+        Ok("")
+    }
+
+    fn complete(&self, args: &ArgMatches) -> Result<&'static str, CliError> {
+        let lines: Vec<String> = match DefaultParser::parse_option_string(args, "kind").as_deref() {
+            Some("notes") => self.vault.list_notes()?
+                .into_iter()
+                .map(|note| format!("{}\t{}", note.id, note.title))
+                .collect(),
+            Some("sources") => self.vault.list_sources()?
+                .into_iter()
+                .map(|source| format!("{}\t{}", source.id, source.title))
+                .collect(),
+            _ => vec![]
+        };
+
+        for line in lines {
+            println!("{line}");
+        }
+
+        Ok("")
+    }
+
+    fn undo(&mut self, history: History) -> Result<&'static str, CliError> {
+        match self.vault.undo(history.force)? {
+            Some(operation) => {
+                eprintln!("{}", style(format!("Undid {}", operation.command)).bold().green());
+                Ok("")
+            },
+            None => Ok("Nothing to undo")
+        }
+    }
+
+    fn redo(&mut self, history: History) -> Result<&'static str, CliError> {
+        match self.vault.redo(history.force)? {
+            Some(operation) => {
+                eprintln!("{}", style(format!("Redid {}", operation.command)).bold().green());
+                Ok("")
+            },
+            None => Ok("Nothing to redo")
+        }
+    }
+
+    fn log(&self, args: OperationLog) -> Result<&'static str, CliError> {
+        let operations = self.vault.operations(args.limit)?;
+
+        let ids: Vec<String> = operations.iter().map(|operation| operation.id.to_string()).collect();
+        let records = operations.iter()
+            .zip(&ids)
+            .map(|(operation, id)| vec![
+                id.as_str(),
+                operation.created_at.as_str(),
+                operation.command.as_str(),
+                if operation.undone { "undone" } else { "" }
+            ])
+            .collect();
+
+        match self.vault.config().output_format {
+            OutputFormat::Csv => Self::print_csv(records)?,
+            OutputFormat::Json => Self::print_json(&["id", "created_at", "command", "undone"], records)?
+        }
+
+        Ok("")
+    }
+
+    fn publish(&self, args: Publish) -> Result<&'static str, CliError> {
+        let mut roots = vec![];
+        for query in &args.notes {
+            roots.push(self.find_note(query)?.id);
+        }
+
+        let published = publish::publish(&self.vault, Path::new(&args.outdir), &roots, args.depth)?;
+
+        let message = format!("Published {} notes and {} sources to {} successfuly", published.notes, published.sources, args.outdir);
+        eprintln!("{}", style(message).bold().green());
+
+        Ok("")
+    }
+
+    fn watch(&mut self, args: Watch) -> Result<&'static str, CliError> {
+        let dir = Path::new(&args.dir);
+
+        // Notes of the files already there, so removing one can delete its note.
+        let mut ids: HashMap<PathBuf, String> = HashMap::new();
+        for path in watch::markdown_files(dir)? {
+            let note = Self::read_note(&path).ok()
+                .and_then(|note_from_md| self.vault.find_note(note_from_md.id.as_ref().unwrap_or(&note_from_md.title)).ok());
+            if let Some(note) = note {
+                ids.insert(path, note.id);
+            }
+        }
+
+        eprintln!("Watching {}", dir.display());
+
+        watch::watch(dir, |change| match change {
+            Change::Written(path) => {
+                let changes = Self::read_note(&path)
+                    .and_then(|note_from_md| self.write_note("watch set", note_from_md, false, |vault, note_from_md| match vault.set_note(note_from_md)? {
+                        SetOutcome::Added(note) | SetOutcome::Updated(note) => Ok(note)
+                    }));
+
+                let changes = match changes {
+                    Ok(changes) => changes,
+                    Err(e) => {
+                        eprintln!("{}", style(format!("{}: {e}", path.display())).red());
+                        return
+                    }
+                };
+
+                let verb = if changes.created() { "Added" } else { "Updated" };
+                let note = changes.note;
+                eprintln!("{}", style(format!("{verb} [{}] {} from {}", note.id, note.title, path.display())).green());
+                ids.insert(path, note.id);
+            },
+            Change::Removed(path) => {
+                let Some(id) = ids.remove(&path) else {
+                    return
+                };
+                if !args.delete || ids.values().any(|other| *other == id) {
+                    return
+                }
+
+                let deleted = self.vault.get_note(&id).and_then(|note| {
+                    let command = format!("watch delete {}", note.title);
+                    self.vault.record(&command, std::slice::from_ref(&note.id), &[], |vault| vault.delete_note(&note.id))
+                });
+                match deleted {
+                    Ok(note) => eprintln!("{}", style(format!("Deleted [{}] {}", note.id, note.title)).yellow()),
+                    Err(e) => eprintln!("{}", style(format!("{}: {e}", path.display())).red())
+                }
+            },
+            Change::Failed(message) => eprintln!("{}", style(format!("Watch error: {message}")).red())
+        })?;
+
+        Ok("")
+    }
+
+    fn sync(&mut self, args: Sync) -> Result<&'static str, CliError> {
+        // Sync may change any note, those it doesn't change are not logged.
+        let notes: Vec<String> = self.vault.list_notes()?.into_iter().map(|note| note.id).collect();
+        let command = format!("sync {}", args.dir);
+        // Files are only changed once the database changes are committed.
+        let pending = self.vault.record(&command, &notes, &[], |vault| sync::sync(vault, Path::new(&args.dir), args.delete))?;
+        let report = pending.apply()?;
+
+        let mut conflicts = 0;
+        for synced in &report {
+            let path = synced.path.display();
+            let title = &synced.title;
+            let line = match &synced.action {
+                Action::Exported => style(format!("Exported {title} to {path}")).green(),
+                Action::Imported => style(format!("Imported {title} from {path}")).green(),
+                Action::Added => style(format!("Added {title} from {path}")).green(),
+                Action::Deleted => style(format!("Deleted {title}, {path} was removed")).yellow(),
+                Action::Removed => style(format!("Removed {path}, {title} was deleted from the database")).yellow(),
+                Action::Conflict(copy) | Action::Unresolved(copy) => {
+                    conflicts += 1;
+                    style(format!("Conflict in {title}: {path} and the database version in {} both changed, delete the latter to keep the file", copy.display())).red()
+                },
+                Action::Failed(message) => {
+                    conflicts += 1;
+                    style(format!("{path}: {message}")).red()
+                }
+            };
+            eprintln!("{line}");
+        }
+
+        match (report.is_empty(), conflicts) {
+            (true, _) => Ok("Already in sync"),
+            (false, 0) => Ok("Synced successfuly"),
+            _ => Ok("Synced with problems left to resolve")
+        }
+    }
+
+    fn read_note(path: &Path) -> Result<NoteFromMd, CliError> {
+        let contents = fs::read_to_string(path)
+            .map_err(|msg| CliError::CannotOpenFile(msg.to_string()))?;
+
+        Ok(parse::md_to_new_note(contents)?)
+    }
+
+    fn lsp(&self) -> Result<&'static str, CliError> {
+        lsp::run(&self.vault)?;
+
+        Ok("")
+    }
+
+    fn serve(&mut self, serve: Serve) -> Result<&'static str, CliError> {
+        server::serve(&mut self.vault, &serve.host, serve.port)?;
+
+        Ok("")
+    }
+
+    fn tui(&mut self) -> Result<&'static str, CliError> {
+        if !console::user_attended() {
+            return Err(CliError::CannotInteract)
+        }
+
+        tui::run(&mut self.vault)?;
+
+        Ok("")
+    }
+
+    fn related(&self, args: RelatedNotes) -> Result<&'static str, CliError> {
+        let note = self.find_note(&args.id)?;
+        let related = self.vault.related(&note.id, args.limit)?;
+
+        if related.is_empty() {
+            return Ok("No related notes found")
+        }
+
+        match self.vault.config().output_format {
+            OutputFormat::Csv => {
+                let reasons: Vec<(String, String)> = related.iter()
+                    .map(|related| {
+                        let mut reasons = vec![];
+                        if !related.shared_sources.is_empty() {
+                            let titles: Vec<&str> = related.shared_sources.iter().map(|s| s.title.as_str()).collect();
+                            reasons.push(format!("shared sources: {}", titles.join(", ")));
+                        }
+                        if !related.shared_neighbours.is_empty() {
+                            let titles: Vec<&str> = related.shared_neighbours.iter().map(|n| n.title.as_str()).collect();
+                            reasons.push(format!("shared neighbours: {}", titles.join(", ")));
+                        }
+                        if related.similarity > 0.0 {
+                            reasons.push(format!("similar text ({:.2}): {}", related.similarity, related.terms.join(", ")));
+                        }
+                        (format!("{:.2}", related.score), reasons.join("; "))
+                    })
+                    .collect();
+                let records = related.iter()
+                    .zip(&reasons)
+                    .map(|(related, (score, reasons))| vec![
+                        related.note.id.as_str(),
+                        related.note.title.as_str(),
+                        score.as_str(),
+                        reasons.as_str()
+                    ])
+                    .collect();
+
+                Self::print_csv(records)?;
+            },
+            OutputFormat::Json => {
+                let objects: Vec<serde_json::Value> = related.iter()
+                    .map(|related| serde_json::json!({
+                        "id": related.note.id,
+                        "title": related.note.title,
+                        "score": related.score,
+                        "shared_sources": related.shared_sources.iter()
+                            .map(|s| serde_json::json!({ "id": s.id, "title": s.title }))
+                            .collect::<Vec<_>>(),
+                        "shared_neighbours": related.shared_neighbours.iter()
+                            .map(|n| serde_json::json!({ "id": n.id, "title": n.title }))
+                            .collect::<Vec<_>>(),
+                        "similarity": related.similarity,
+                        "terms": related.terms
+                    }))
+                    .collect();
+                let contents = serde_json::to_string_pretty(&objects)
+                    .map_err(|_| CliError::InternalError)?;
+                println!("{contents}");
+            }
+        }
+
+        Ok("")
     }
 
     fn list(&self, args: &ArgMatches) -> Result<&'static str, CliError> {
         match args.subcommand() {
-            Some(("notes", args)) => self.list_notes(NoteFields::try_from(args)?),
+            Some(("notes", args)) => self.list_notes(ListNotes::try_from(args)?),
             Some(("sources", args)) => self.list_sources(SourceFields::try_from(args)?),
             _ => Ok("")
         }
     }
 
-    fn list_notes(&self, fields: NoteFields) -> Result<&'static str, CliError> {
-        let mut wtr = Writer::from_writer(vec![]);
-        let notes = Note::list(&self.conn)?;
+    fn list_notes(&self, list_notes: ListNotes) -> Result<&'static str, CliError> {
+        if list_notes.tree {
+            return self.list_notes_tree()
+        }
 
-        for NoteListItem { id, title } in notes {
-            let record = Self::note_item_to_record(&id, &title, &fields);
+        let notes = self.vault.list_notes()?;
+        self.print_notes(&notes, &list_notes.fields)?;
 
-            if Self::handle_single_column(&record) {
-                continue; 
-            }
+        Ok("")
+    }
 
-            wtr.write_record(&record)
-                .map_err(|_| CliError::InternalError)?;
-        }
+    fn search(&self, search: SearchNotes) -> Result<&'static str, CliError> {
+        let notes = self.vault.search(&search.query)?;
+        self.print_notes(&notes, &search.fields)?;
 
-        Self::print_csv_record(wtr)?;
+        Ok("")
+    }
 
+    fn print_notes(&self, notes: &[NoteListItem], fields: &NoteFields) -> Result<(), CliError> {
+        let records = notes.iter()
+            .map(|note| Self::note_item_to_record(note, fields))
+            .collect();
+        let names: Vec<&str> = fields.items.iter()
+            .map(NoteField::name)
+            .collect();
+
+        self.print_records(&names, records)
+    }
+
+    fn list_notes_tree(&self) -> Result<&'static str, CliError> {
+        let notes = self.vault.list_sequence()?;
+
+        match self.vault.config().output_format {
+            OutputFormat::Csv => {
+                for note in notes {
+                    let position = note.sequence.unwrap_or_default();
+                    let indent = "  ".repeat(sequence::depth(&position));
+                    println!("{indent}{position} [{}] {}", note.id, note.title);
+                }
+            },
+            OutputFormat::Json => {
+                let notes: Vec<serde_json::Value> = notes.into_iter()
+                    .map(|note| {
+                        let position = note.sequence.unwrap_or_default();
+                        serde_json::json!({
+                            "sequence": position,
+                            "depth": sequence::depth(&position),
+                            "parent": sequence::parent(&position),
+                            "id": note.id,
+                            "title": note.title,
+                        })
+                    })
+                    .collect();
+
+                let contents = serde_json::to_string_pretty(&notes)
+                    .map_err(|_| CliError::InternalError)?;
+                println!("{contents}");
+            }
+        }
 
         Ok("")
     }
 
     fn list_sources(&self, fields: SourceFields) -> Result<&'static str, CliError> {
-        let mut wtr = Writer::from_writer(vec![]);
-        let notes = Source::list(&self.conn)?;
+        let sources = self.vault.list_sources()?;
+        let counts = match fields.items.iter().any(|item| matches!(item, SourceField::CitedByCount)) {
+            true => sources.iter()
+                .map(|source| Ok(self.vault.citing_notes(&source.id)?.len().to_string()))
+                .collect::<Result<Vec<String>, CliError>>()?,
+            false => vec![String::new(); sources.len()]
+        };
+        let records = sources.iter()
+            .zip(&counts)
+            .map(|(Source { id, title }, count)| Self::source_to_record(id, title, count, &fields))
+            .collect();
+        let names: Vec<&str> = fields.items.iter()
+            .map(SourceField::name)
+            .collect();
+
+        self.print_records(&names, records)?;
 
-        for Source { id, title } in notes {
-            let record = Self::source_to_record(&id, &title, &fields);
+        Ok("")
+    }
 
+    fn print_records(&self, names: &[&str], records: Vec<Vec<&str>>) -> Result<(), CliError> {
+        match self.vault.config().output_format {
+            OutputFormat::Csv => Self::print_csv(records),
+            OutputFormat::Json => Self::print_json(names, records),
+        }
+    }
+
+    fn print_csv(records: Vec<Vec<&str>>) -> Result<(), CliError> {
+        let mut wtr = Writer::from_writer(vec![]);
+
+        for record in records {
             if Self::handle_single_column(&record) {
                 continue; 
             }
@@ -150,30 +748,44 @@ impl Controller {
                 .map_err(|_| CliError::InternalError)?;
         }
 
-        Self::print_csv_record(wtr)?;
+        Self::print_csv_record(wtr)
+    }
 
+    fn print_json(names: &[&str], records: Vec<Vec<&str>>) -> Result<(), CliError> {
+        let objects: Vec<serde_json::Map<String, serde_json::Value>> = records.into_iter()
+            .map(|record| names.iter()
+                .zip(record)
+                .map(|(name, value)| (name.to_string(), value.into()))
+                .collect())
+            .collect();
 
-        Ok("")
+        let contents = serde_json::to_string_pretty(&objects)
+            .map_err(|_| CliError::InternalError)?;
+        println!("{contents}");
+
+        Ok(())
     }
 
-    fn note_item_to_record<'a>(id: &'a str, title: &'a str, fields: &NoteFields) -> Vec<&'a str> {
+    fn note_item_to_record<'a>(note: &'a NoteListItem, fields: &NoteFields) -> Vec<&'a str> {
         let mut record = vec![];
         for item in &fields.items {
             match item {
-                NoteField::Id => record.push(id),
-                NoteField::Title => record.push(title)
+                NoteField::Id => record.push(note.id.as_str()),
+                NoteField::Title => record.push(note.title.as_str()),
+                NoteField::Sequence => record.push(note.sequence.as_deref().unwrap_or_default())
             }
         }
 
         record
     }
 
-    fn source_to_record<'a>(id: &'a str, title: &'a str, fields: &SourceFields) -> Vec<&'a str> {
+    fn source_to_record<'a>(id: &'a str, title: &'a str, count: &'a str, fields: &SourceFields) -> Vec<&'a str> {
         let mut record = vec![];
         for item in &fields.items {
             match item {
                 SourceField::Id => record.push(id),
-                SourceField::Title => record.push(title)
+                SourceField::Title => record.push(title),
+                SourceField::CitedByCount => record.push(count)
             }
         }
 
@@ -205,17 +817,44 @@ impl Controller {
     fn get(&self, args: &ArgMatches) -> Result<&'static str, CliError> {
         match args.subcommand() {
             Some(("note", args)) => self.get_note(GetNote::try_from(args)?),
+            Some(("source", args)) => self.get_source(GetSource::try_from(args)?),
             _ => Ok("")
         }
     }
 
+    fn get_source(&self, get_source: GetSource) -> Result<&'static str, CliError> {
+        let source = self.vault.find_source(&get_source.id)?;
+        let notes = self.vault.citing_notes(&source.id)?;
+
+        match self.vault.config().output_format {
+            OutputFormat::Csv => {
+                println!("[{}] {}", source.id, source.title);
+                println!("Cited by {} note(s)", notes.len());
+                for note in &notes {
+                    println!("  {note}");
+                }
+            },
+            OutputFormat::Json => {
+                let cited_by: Vec<serde_json::Value> = notes.iter()
+                    .map(|note| serde_json::json!({ "id": note.id, "title": note.title }))
+                    .collect();
+                let object = serde_json::json!({
+                    "id": source.id,
+                    "title": source.title,
+                    "cited_by": cited_by
+                });
+                let contents = serde_json::to_string_pretty(&object)
+                    .map_err(|_| CliError::InternalError)?;
+                println!("{contents}");
+            }
+        }
+
+        Ok("")
+    }
+
     fn get_note(&self, get_note: GetNote) -> Result<&'static str, CliError> {
-        let note = Note::get_by_id(get_note.id, &self.conn)?
-            .ok_or(CliError::NoteNotFound)?;
-        let internal = InternalReference::get_by_note_id(&note.id, &self.conn)?;
-        let external = ExternalReference::get_by_note_id(&note.id, &self.conn)?;
-        
-        let md_note = note_to_md(note, internal, external);
+        let note = self.find_note(&get_note.id)?;
+        let md_note = self.vault.note_to_md(&note.id)?;
 
         let mut file = File::create(&get_note.path)?;
         file.write_all(md_note.as_bytes())?;
@@ -227,70 +866,105 @@ impl Controller {
         Ok("")
     }
     
-    fn update(&mut self, note_from_md: NoteFromMd) -> Result<&'static str, CliError> {
-        let note: Note = (&note_from_md).into();
-        if Note::get_by_id(note.id.clone(), &self.conn)?.is_none() {
-            return Err(CliError::NoteNotFound)
-        }
-
-        let note_id = note.id.clone();
-
-        let tx = self.conn.transaction().unwrap();
-        note.update(&tx)?;
-
-        InternalReference::delete_by_note_id(&note_id, &tx)?;
-
-        note_from_md.references.internal.iter()
-            .try_for_each(|r| Self::add_internal_reference(r, &note_id, &tx))?;
-
-        ExternalReference::delete_by_note_id(&note_id, &tx)?;
-        note_from_md.references.external.iter()
-            .try_for_each(|r| Self::add_external_reference(r, &note_id, &tx))?;
+    fn update(&mut self, note_from_md: NoteFromMd, dry_run: bool) -> Result<&'static str, CliError> {
+        let changes = self.write_note("update", note_from_md, dry_run, Vault::update_note)?;
 
-        tx.commit().unwrap();
+        self.print_changes(&changes, dry_run)
+    }
 
-        Ok("Note updated successfuly")
+    fn set(&mut self, note_from_md: NoteFromMd, dry_run: bool) -> Result<&'static str, CliError> {
+        let changes = self.write_note("set", note_from_md, dry_run, |vault, note_from_md| match vault.set_note(note_from_md)? {
+            SetOutcome::Added(note) | SetOutcome::Updated(note) => Ok(note)
+        })?;
 
+        self.print_changes(&changes, dry_run)
     }
 
-    fn set(&mut self, mut note_from_md: NoteFromMd) -> Result<&'static str, CliError> {
-        let msg = "Note set successfuly";
-
-        match note_from_md.id {
-            Some(ref id) => {
-                let note = Note::get_by_id(id.clone(), &self.conn)?;
+    /// Adds or updates the note through `write`, logged as `command` followed
+    /// by the title so it can be undone.
+    fn write_note<F>(&mut self, command: &str, note_from_md: NoteFromMd, dry_run: bool, write: F) -> Result<NoteChanges, CliError>
+        where F: FnOnce(&mut Vault, NoteFromMd) -> Result<Note, VaultError> {
+        let existing: Vec<String> = self.vault.existing_note(&note_from_md)?
+            .map(|note| note.id)
+            .into_iter()
+            .collect();
+        let command = format!("{command} {}", note_from_md.title);
+
+        Ok(self.vault.record(&command, &existing, &[], |vault| vault.write_note(note_from_md, dry_run, write))?)
+    }
 
-                match note {
-                    Some(_) => self.update(note_from_md)?,
-                    None => self.add(note_from_md)?
-                };
-            },
-            None => {
-                let note = Note::get_by_title(note_from_md.title.clone(), &self.conn)?;
-
-                match note {
-                    Some(note) => {
-                        note_from_md.id = Some(note.id);
-                        self.update(note_from_md)?
-                    },
-                    None => self.add(note_from_md)?,
+    /// Prints what adding or updating a note changed, or would change with `dry_run`.
+    fn print_changes(&self, changes: &NoteChanges, dry_run: bool) -> Result<&'static str, CliError> {
+        let note = &changes.note;
+
+        match self.vault.config().output_format {
+            OutputFormat::Csv => {
+                let verb = match (dry_run, changes.created()) {
+                    (true, true) => "Would add",
+                    (true, false) => "Would update",
+                    (false, true) => "Added",
+                    (false, false) => "Updated",
                 };
-            },
-        }
+                println!("{verb} [{}] {}", note.id, note.title);
 
+                if let Some((old, new)) = changes.title_change() {
+                    println!("  title: {old} -> {new}");
+                }
 
+                let (added, removed) = changes.line_changes();
+                if added > 0 || removed > 0 {
+                    println!("  body: {added} line(s) added, {removed} removed");
+                }
 
-        Ok(msg)
-    }
-}
+                let notes = |notes: &[Note]| notes.iter().map(|n| format!("[{}] {}", n.id, n.title)).collect::<Vec<String>>();
+                let sources = |sources: &[Source]| sources.iter().map(|s| format!("[{}] {}", s.id, s.title)).collect::<Vec<String>>();
+                let lines = [
+                    ("references added", notes(&changes.internal_added)),
+                    ("references removed", notes(&changes.internal_removed)),
+                    ("sources added", sources(&changes.external_added)),
+                    ("sources removed", sources(&changes.external_removed)),
+                    ("new sources", sources(&changes.new_sources)),
+                ];
+                for (label, items) in lines {
+                    if !items.is_empty() {
+                        println!("  {label}: {}", items.join(", "));
+                    }
+                }
 
-impl From<&NoteFromMd> for Note {
-    fn from(value: &NoteFromMd) -> Self {
-        Self {
-            id: value.id.clone().unwrap_or(generate_id()),
-            title: value.title.clone(),
-            contents: value.contents.clone()
+                if changes.is_empty() {
+                    println!("  nothing changed");
+                }
+            },
+            OutputFormat::Json => {
+                let notes = |notes: &[Note]| notes.iter()
+                    .map(|n| serde_json::json!({ "id": n.id, "title": n.title }))
+                    .collect::<Vec<_>>();
+                let sources = |sources: &[Source]| sources.iter()
+                    .map(|s| serde_json::json!({ "id": s.id, "title": s.title }))
+                    .collect::<Vec<_>>();
+                let (lines_added, lines_removed) = changes.line_changes();
+
+                let object = serde_json::json!({
+                    "id": note.id,
+                    "title": note.title,
+                    "created": changes.created(),
+                    "dry_run": dry_run,
+                    "old_title": changes.title_change().map(|(old, _)| old),
+                    "lines_added": lines_added,
+                    "lines_removed": lines_removed,
+                    "references_added": notes(&changes.internal_added),
+                    "references_removed": notes(&changes.internal_removed),
+                    "sources_added": sources(&changes.external_added),
+                    "sources_removed": sources(&changes.external_removed),
+                    "new_sources": sources(&changes.new_sources),
+                });
+                let contents = serde_json::to_string_pretty(&object)
+                    .map_err(|_| CliError::InternalError)?;
+                println!("{contents}");
+            }
         }
+
+        Ok("")
     }
 }
 
@@ -301,3 +975,80 @@ impl Display for NoteListItem {
         write!(f, "{id} | {title}")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn controller(mds: &[&str]) -> Controller {
+        let mut vault = Vault::in_memory();
+        for md in mds {
+            vault.add_note(parse::md_to_new_note(md.to_string()).unwrap()).unwrap();
+        }
+
+        Controller { vault, interactive: false }
+    }
+
+    #[test]
+    fn find_note_takes_the_only_match() {
+        let controller = controller(&["# Zettel one\n", "# Other\n"]);
+
+        assert_eq!(controller.find_note("zett").unwrap().title, "Zettel one");
+        assert!(matches!(controller.find_note("missing"), Err(CliError::NoteNotFound)));
+    }
+
+    #[test]
+    fn find_note_cannot_ask_without_a_terminal() {
+        let controller = controller(&["# Zettel one\n", "# Zettel two\n"]);
+
+        assert!(matches!(controller.find_note("zettel"), Err(CliError::CannotInteract)));
+        assert_eq!(controller.find_note("Zettel two").unwrap().title, "Zettel two");
+    }
+
+    /// Everything a command could change: the notes, their references and the undo log.
+    fn store(controller: &Controller) -> String {
+        let vault = &controller.vault;
+        let notes: Vec<String> = vault.list_notes().unwrap().iter()
+            .map(|note| format!("{:?} {:?}", vault.get_note(&note.id).unwrap(), vault.references_of(&note.id).unwrap()))
+            .collect();
+
+        format!("{notes:?} {:?} {}", vault.list_sources().unwrap(), vault.operations(10).unwrap().len())
+    }
+
+    #[test]
+    fn dry_run_leaves_the_store_unchanged() {
+        let mds = ["# [ABC234] A\n\nv1\n", "# B\n"];
+        let note = |md: &str| parse::md_to_new_note(md.to_string()).unwrap();
+        let edited = "# [ABC234] A2\n\nv2\n## References\n### Internal\n1. B\n### External\n1. New source\n";
+
+        let mut controller = controller(&mds);
+        let before = store(&controller);
+
+        controller.add(note("# C\n## References\n### External\n1. New source\n"), true).unwrap();
+        controller.new_note(NewNote { note: note("# D\n"), position: SequencePosition::Next }, true).unwrap();
+        controller.update(note(edited), true).unwrap();
+        controller.set(note(edited), true).unwrap();
+        controller.set(note("# E\n"), true).unwrap();
+
+        assert_eq!(store(&controller), before);
+    }
+
+    #[test]
+    fn dry_run_fails_like_the_command_would() {
+        for md in ["# [ABC234] Other\n", "# A\n", "# C\n## References\n### Internal\n1. Missing\n"] {
+            let mut controller = controller(&["# [ABC234] A\n"]);
+            let before = store(&controller);
+            let mut add = |dry_run| controller.add(parse::md_to_new_note(md.to_string()).unwrap(), dry_run)
+                .map_err(|e| e.to_string());
+
+            let dry_run = add(true);
+            assert!(dry_run.is_err(), "{md}");
+            assert_eq!(dry_run, add(false));
+            assert_eq!(store(&controller), before);
+        }
+
+        let mut controller = controller(&[]);
+        let update = controller.update(parse::md_to_new_note("# [ABC234] A\n".to_string()).unwrap(), true);
+        assert!(matches!(update, Err(CliError::NoteNotFound)));
+    }
+}
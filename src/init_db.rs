@@ -1,42 +1,14 @@
-use dotenvy::dotenv;
-use xdg;
-use std::env;
-use rusqlite::{Connection, Result};
+use console::style;
+use rusqlite::Connection;
 
-pub fn setup_database() -> Connection {
-    let mut conn = setup_conn();
-    enable_fk(&mut conn);
-    create_tables(&mut conn);
+use crate::util::title::normalize;
 
-    conn
-}
-
-fn setup_conn() -> Connection {
-    dotenv().ok();
-
-
-    let database_url;
-    if cfg!(debug_assertions) {
-        dotenv().ok();
-
-        database_url = env::var("DATABASE_URL")
-            .expect("DATABASE_URL must be set");
-    }
-    else {
-        let xdg_dirs = xdg::BaseDirectories::new()
-            .expect("Cannot open base xdg directory");
-
-        let path = xdg_dirs.create_data_directory("spark")
-            .expect("Cannot create data directory");
-
-        database_url = path.join("spark.db")
-            .into_os_string()
-            .into_string()
-            .expect("Invalid utf8 in database path");
-    }
-
-    let conn = Connection::open(&database_url)
+pub fn setup_database(database_url: &str) -> Connection {
+    let conn = Connection::open(database_url)
         .unwrap_or_else(|_| panic!("Error connecting to {}", database_url));
+    enable_fk(&conn);
+    create_tables(&conn);
+    migrate(&conn);
 
     conn
 }
@@ -51,23 +23,104 @@ fn create_tables(conn: &Connection) {
     conn.execute("CREATE TABLE IF NOT EXISTS notes (
         id TEXT PRIMARY KEY,
         title text not null unique,
-        contents text not null
-    )", ()).expect(&msg);
+        title_key text,
+        contents text not null,
+        sequence text,
+        revision integer not null default 0
+    )", ()).expect(msg);
 
     conn.execute("CREATE TABLE IF NOT EXISTS internal_references (
         id TEXT PRIMARY KEY,
         note_id text references notes(id) not null,
         reference_id text references notes(id) not null
-    )", ()).expect(&msg);
+    )", ()).expect(msg);
 
     conn.execute("CREATE TABLE IF NOT EXISTS sources (
         id TEXT PRIMARY KEY,
-        title text not null unique
-    )", ()).expect(&msg);
+        title text not null unique,
+        title_key text
+    )", ()).expect(msg);
+
+    conn.execute("CREATE TABLE IF NOT EXISTS aliases (
+        id TEXT PRIMARY KEY,
+        note_id text references notes(id) not null,
+        alias text not null,
+        alias_key text,
+        unique(note_id, alias)
+    )", ()).expect(msg);
 
     conn.execute("CREATE TABLE IF NOT EXISTS external_references (
         id TEXT PRIMARY KEY,
         note_id text references notes(id) not null,
         reference_id text references sources(id) not null
-    )", ()).expect(&msg);
+    )", ()).expect(msg);
+
+    conn.execute("CREATE TABLE IF NOT EXISTS operations (
+        id integer primary key autoincrement,
+        command text not null,
+        created_at text not null default current_timestamp,
+        undone integer not null default 0,
+        before text not null,
+        after text not null
+    )", ()).expect(msg);
+}
+
+/// Brings databases created by older versions up to date with `create_tables`.
+fn migrate(conn: &Connection) {
+    let msg = "Cannot migrate tables!";
+    add_column_if_missing(conn, "notes", "sequence", "text");
+    add_column_if_missing(conn, "notes", "title_key", "text");
+    add_column_if_missing(conn, "sources", "title_key", "text");
+    add_column_if_missing(conn, "aliases", "alias_key", "text");
+    add_column_if_missing(conn, "notes", "revision", "integer not null default 0");
+
+    fill_normalized(conn, "notes", "title", "title_key");
+    fill_normalized(conn, "sources", "title", "title_key");
+    fill_normalized(conn, "aliases", "alias", "alias_key");
+
+    conn.execute("CREATE UNIQUE INDEX IF NOT EXISTS notes_sequence ON notes(sequence)", ())
+        .expect(msg);
+    conn.execute("CREATE INDEX IF NOT EXISTS aliases_alias_key ON aliases(alias_key)", ())
+        .expect(msg);
+
+    if create_title_indexes(conn).is_err() {
+        let warning = "Warning: some notes or sources have titles which only differ in case, spacing or punctuation, \
+            so titles are not checked for uniqueness until `spark dedupe` merges them";
+        eprintln!("{}", style(warning).yellow());
+    }
+}
+
+/// Makes normalised titles unique, fails while near-duplicate titles from
+/// before normalisation remain.
+pub fn create_title_indexes(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute("CREATE UNIQUE INDEX IF NOT EXISTS notes_title_key ON notes(title_key)", ())?;
+    conn.execute("CREATE UNIQUE INDEX IF NOT EXISTS sources_title_key ON sources(title_key)", ())?;
+
+    Ok(())
+}
+
+fn fill_normalized(conn: &Connection, table: &str, column: &str, key: &str) {
+    let msg = "Cannot migrate tables!";
+    let rows: Vec<(String, String)> = conn.prepare(&format!("select id, {column} from {table} where {key} is null"))
+        .and_then(|mut statement| statement
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect())
+        .expect(msg);
+
+    for (id, value) in rows {
+        conn.execute(&format!("update {table} set {key} = ?1 where id = ?2"), (normalize(&value), id))
+            .expect(msg);
+    }
+}
+
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) {
+    let msg = "Cannot migrate tables!";
+    let exists = conn.prepare(&format!("select 1 from pragma_table_info('{table}') where name = ?1"))
+        .and_then(|mut statement| statement.exists([column]))
+        .expect(msg);
+
+    if !exists {
+        conn.execute(&format!("ALTER TABLE {table} ADD COLUMN {column} {definition}"), ())
+            .expect(msg);
+    }
 }
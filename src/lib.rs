@@ -1,5 +1,15 @@
 pub mod init_db;
 pub mod cli;
+pub mod config;
 pub mod controller;
+pub mod lsp;
 pub mod models;
+pub mod publish;
+pub mod registry;
+pub mod server;
+pub mod storage;
+pub mod sync;
+pub mod tui;
 pub mod util;
+pub mod vault;
+pub mod watch;
@@ -0,0 +1,205 @@
+use crate::util::extract_id;
+
+/// Part of a note markdown file a line belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    Body,
+    Aliases,
+    References,
+    Internal,
+    External,
+}
+
+/// A list item of the `### Internal` or `### External` section.
+#[derive(Debug, Clone)]
+pub struct Item {
+    pub section: Section,
+    pub line: u32,
+    /// UTF-16 columns of the item text, after the list marker.
+    pub start: u32,
+    pub end: u32,
+    pub text: String,
+    pub id: Option<String>,
+}
+
+/// Section of every line, following the headings of the layout `note_to_md` writes.
+pub fn sections(text: &str) -> Vec<Section> {
+    let mut section = Section::Body;
+
+    text.lines()
+        .map(|line| {
+            let heading = line.trim();
+            if heading.starts_with("# ") {
+                section = Section::Body;
+            }
+            else if heading.starts_with("## Aliases") {
+                section = Section::Aliases;
+            }
+            else if heading.starts_with("## References") {
+                section = Section::References;
+            }
+            else if heading.starts_with("### Internal") {
+                section = Section::Internal;
+            }
+            else if heading.starts_with("### External") {
+                section = Section::External;
+            }
+
+            section
+        })
+        .collect()
+}
+
+pub fn section_at(text: &str, line: u32) -> Section {
+    sections(text).get(line as usize)
+        .copied()
+        .unwrap_or(Section::Body)
+}
+
+/// Reference items of both reference sections.
+pub fn items(text: &str) -> Vec<Item> {
+    text.lines()
+        .zip(sections(text))
+        .enumerate()
+        .filter(|(_, (_, section))| matches!(section, Section::Internal | Section::External))
+        .filter_map(|(line, (content, section))| {
+            let text = list_item(content)?.trim_end();
+            if text.is_empty() {
+                return None
+            }
+
+            let start = utf16_len(&content[..content.len() - list_item(content)?.len()]);
+            Some(Item {
+                section,
+                line: line as u32,
+                start,
+                end: start + utf16_len(text),
+                text: text.to_string(),
+                id: extract_id(text),
+            })
+        })
+        .collect()
+}
+
+/// Title of a reference item, without its `[ID]`.
+pub fn item_title(item: &Item) -> String {
+    match &item.id {
+        Some(id) => item.text.replace(&format!("[{id}]"), "").trim().to_string(),
+        None => item.text.trim().to_string(),
+    }
+}
+
+/// The `[ID]` under the cursor, or the only one on the line.
+pub fn id_at(text: &str, line: u32, character: u32) -> Option<String> {
+    let content = text.lines().nth(line as usize)?;
+
+    let mut ids = vec![];
+    let mut offset = 0;
+    while let Some(open) = content[offset..].find('[') {
+        let open = offset + open;
+        let Some(close) = content[open..].find(']') else {
+            break
+        };
+        let close = open + close;
+
+        if let Some(id) = extract_id(&content[open..=close]) {
+            ids.push((utf16_len(&content[..open]), utf16_len(&content[..=close]), id));
+        }
+        offset = close + 1;
+    }
+
+    let under_cursor = ids.iter()
+        .find(|(start, end, _)| (*start..=*end).contains(&character))
+        .map(|(_, _, id)| id.clone());
+
+    match (under_cursor, ids.len()) {
+        (Some(id), _) => Some(id),
+        (None, 1) => ids.pop().map(|(_, _, id)| id),
+        _ => None
+    }
+}
+
+/// Text of a list item without its `-`, `*`, `+` or `1.` marker.
+pub fn list_item(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start();
+
+    if let Some(rest) = trimmed.strip_prefix("- ").or(trimmed.strip_prefix("* ")).or(trimmed.strip_prefix("+ ")) {
+        return Some(rest.trim_start())
+    }
+
+    let digits = trimmed.chars().take_while(char::is_ascii_digit).count();
+    if digits > 0 {
+        return trimmed[digits..].strip_prefix(". ").map(str::trim_start)
+    }
+
+    None
+}
+
+pub fn utf16_len(text: &str) -> u32 {
+    text.encode_utf16().count() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTE: &str = "# [ABC234] Café\n\nSee [DEF567] and [GHI234].\n## Aliases\n - Coffee\n\n## References\n### Internal\n1. [DEF567] Other\n2. Ünïcode title\n\n### External\n - [JKL234] Book\n";
+
+    #[test]
+    fn sections_follow_headings() {
+        let sections = sections(NOTE);
+
+        assert_eq!(sections[2], Section::Body);
+        assert_eq!(sections[4], Section::Aliases);
+        assert_eq!(sections[8], Section::Internal);
+        assert_eq!(sections[12], Section::External);
+        assert_eq!(section_at(NOTE, 6), Section::References);
+        assert_eq!(section_at(NOTE, 100), Section::Body);
+    }
+
+    #[test]
+    fn items_span_the_text_after_the_marker_in_utf16_columns() {
+        let items = items(NOTE);
+
+        let spans: Vec<(u32, u32, u32, Option<&str>)> = items.iter()
+            .map(|item| (item.line, item.start, item.end, item.id.as_deref()))
+            .collect();
+        assert_eq!(spans, [(8, 3, 17, Some("DEF567")), (9, 3, 16, None), (12, 3, 16, Some("JKL234"))]);
+        assert_eq!(items[1].section, Section::Internal);
+        assert_eq!(item_title(&items[0]), "Other");
+        assert_eq!(item_title(&items[1]), "Ünïcode title");
+    }
+
+    #[test]
+    fn id_at_picks_the_id_under_the_cursor() {
+        assert_eq!(id_at(NOTE, 2, 4).as_deref(), Some("DEF567"));
+        assert_eq!(id_at(NOTE, 2, 12).as_deref(), Some("DEF567"));
+        assert_eq!(id_at(NOTE, 2, 20).as_deref(), Some("GHI234"));
+        assert_eq!(id_at(NOTE, 2, 0), None);
+    }
+
+    #[test]
+    fn id_at_falls_back_to_the_only_id_of_the_line() {
+        assert_eq!(id_at(NOTE, 0, 14).as_deref(), Some("ABC234"));
+        assert_eq!(id_at(NOTE, 4, 2), None);
+        assert_eq!(id_at(NOTE, 100, 0), None);
+    }
+
+    #[test]
+    fn id_at_counts_utf16_columns() {
+        let text = "𝄞 [ABC234] 𝄞 [DEF567]";
+
+        assert_eq!(id_at(text, 0, 3).as_deref(), Some("ABC234"));
+        assert_eq!(id_at(text, 0, 15).as_deref(), Some("DEF567"));
+        assert_eq!(utf16_len("𝄞é"), 3);
+    }
+
+    #[test]
+    fn list_item_strips_markers() {
+        assert_eq!(list_item("  - [ABC234] A"), Some("[ABC234] A"));
+        assert_eq!(list_item("* A"), Some("A"));
+        assert_eq!(list_item("12.  A"), Some("A"));
+        assert_eq!(list_item("12) A"), None);
+        assert_eq!(list_item("-A"), None);
+    }
+}
@@ -0,0 +1,34 @@
+use crate::vault::error::VaultError;
+
+#[derive(thiserror::Error, Debug)]
+pub enum LspError {
+    #[error("Language server protocol error: {0}")]
+    Protocol(String),
+
+    #[error("Cannot open xdg directories")]
+    CannotOpenXdgDirectory,
+
+    #[error(transparent)]
+    Vault(#[from] VaultError),
+
+    #[error(transparent)]
+    Generic(#[from] anyhow::Error)
+}
+
+impl From<lsp_server::ProtocolError> for LspError {
+    fn from(value: lsp_server::ProtocolError) -> Self {
+        LspError::Protocol(value.to_string())
+    }
+}
+
+impl From<std::io::Error> for LspError {
+    fn from(value: std::io::Error) -> Self {
+        LspError::Generic(value.into())
+    }
+}
+
+impl From<serde_json::Error> for LspError {
+    fn from(value: serde_json::Error) -> Self {
+        LspError::Generic(value.into())
+    }
+}
@@ -0,0 +1,309 @@
+use std::{collections::HashMap, fs};
+
+use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
+use lsp_types::{CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionTextEdit, Diagnostic, DiagnosticSeverity, DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams, DidSaveTextDocumentParams, GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams, Location, MarkupContent, MarkupKind, Position, PublishDiagnosticsParams, Range, ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit, Url};
+
+use crate::{config::StubPolicy, util::parse::md_to_new_note, vault::{error::VaultError, Vault}};
+
+use self::{document::{Item, Section}, error::LspError};
+
+pub mod document;
+pub mod error;
+
+/// Lines of a note shown when hovering its id.
+const HOVER_LINES: usize = 20;
+
+/// Runs a language server for note markdown files over stdin and stdout.
+pub fn run(vault: &Vault) -> Result<(), LspError> {
+    let (connection, io_threads) = Connection::stdio();
+
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["[".to_string()]),
+            ..Default::default()
+        }),
+        definition_provider: Some(lsp_types::OneOf::Left(true)),
+        hover_provider: Some(lsp_types::HoverProviderCapability::Simple(true)),
+        ..Default::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+
+    let mut server = LspServer { vault, documents: HashMap::new() };
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    break
+                }
+
+                let response = server.handle_request(request);
+                connection.sender.send(Message::Response(response))
+                    .map_err(|e| LspError::Protocol(e.to_string()))?;
+            },
+            Message::Notification(notification) => {
+                if let Some(diagnostics) = server.handle_notification(notification)? {
+                    let notification = Notification::new("textDocument/publishDiagnostics".to_string(), diagnostics);
+                    connection.sender.send(Message::Notification(notification))
+                        .map_err(|e| LspError::Protocol(e.to_string()))?;
+                }
+            },
+            Message::Response(_) => ()
+        }
+    }
+
+    // The writer thread only stops once every sender is gone.
+    drop(connection);
+    io_threads.join()?;
+
+    Ok(())
+}
+
+struct LspServer<'a> {
+    vault: &'a Vault,
+    /// Text of every open document.
+    documents: HashMap<Url, String>,
+}
+
+impl LspServer<'_> {
+    fn handle_request(&self, request: Request) -> Response {
+        let id = request.id.clone();
+        let result = match request.method.as_str() {
+            "textDocument/completion" => self.parse_and(request, Self::completion),
+            "textDocument/definition" => self.parse_and(request, Self::definition),
+            "textDocument/hover" => self.parse_and(request, Self::hover),
+            method => return Response::new_err(id, lsp_server::ErrorCode::MethodNotFound as i32, format!("Unsupported method {method}"))
+        };
+
+        Self::respond(id, result)
+    }
+
+    fn parse_and<P, R>(&self, request: Request, f: impl Fn(&Self, P) -> Result<R, LspError>) -> Result<serde_json::Value, LspError>
+        where P: serde::de::DeserializeOwned, R: serde::Serialize
+    {
+        let params = serde_json::from_value(request.params)?;
+
+        Ok(serde_json::to_value(f(self, params)?)?)
+    }
+
+    fn respond(id: RequestId, result: Result<serde_json::Value, LspError>) -> Response {
+        match result {
+            Ok(value) => Response::new_ok(id, value),
+            Err(e) => Response::new_err(id, lsp_server::ErrorCode::InternalError as i32, e.to_string())
+        }
+    }
+
+    /// Keeps track of open documents, answering with their diagnostics when they change.
+    fn handle_notification(&mut self, notification: Notification) -> Result<Option<PublishDiagnosticsParams>, LspError> {
+        let uri = match notification.method.as_str() {
+            "textDocument/didOpen" => {
+                let params: DidOpenTextDocumentParams = serde_json::from_value(notification.params)?;
+                self.documents.insert(params.text_document.uri.clone(), params.text_document.text);
+                params.text_document.uri
+            },
+            "textDocument/didChange" => {
+                let params: DidChangeTextDocumentParams = serde_json::from_value(notification.params)?;
+                if let Some(change) = params.content_changes.into_iter().last() {
+                    self.documents.insert(params.text_document.uri.clone(), change.text);
+                }
+                params.text_document.uri
+            },
+            "textDocument/didSave" => {
+                let params: DidSaveTextDocumentParams = serde_json::from_value(notification.params)?;
+                if let Some(text) = params.text {
+                    self.documents.insert(params.text_document.uri.clone(), text);
+                }
+                params.text_document.uri
+            },
+            "textDocument/didClose" => {
+                let params: DidCloseTextDocumentParams = serde_json::from_value(notification.params)?;
+                self.documents.remove(&params.text_document.uri);
+                return Ok(Some(PublishDiagnosticsParams::new(params.text_document.uri, vec![], None)))
+            },
+            _ => return Ok(None)
+        };
+
+        let diagnostics = match self.documents.get(&uri) {
+            Some(text) => self.diagnostics(text)?,
+            None => vec![]
+        };
+
+        Ok(Some(PublishDiagnosticsParams::new(uri, diagnostics, None)))
+    }
+
+    fn document(&self, uri: &Url) -> &str {
+        self.documents.get(uri).map(String::as_str).unwrap_or_default()
+    }
+
+    /// `[ID] Title` of notes inside `### Internal`, of sources inside `### External`.
+    fn completion(&self, params: CompletionParams) -> Result<Vec<CompletionItem>, LspError> {
+        let position = params.text_document_position.position;
+        let text = self.document(&params.text_document_position.text_document.uri);
+
+        let (candidates, kind, detail) = match document::section_at(text, position.line) {
+            Section::Internal => (
+                self.vault.list_notes()?.into_iter().map(|note| (note.id, note.title)).collect::<Vec<_>>(),
+                CompletionItemKind::REFERENCE,
+                "note"
+            ),
+            Section::External => (
+                self.vault.list_sources()?.into_iter().map(|source| (source.id, source.title)).collect(),
+                CompletionItemKind::FILE,
+                "source"
+            ),
+            _ => return Ok(vec![])
+        };
+
+        // Replace everything typed after the list marker, so picking an item
+        // also drops a partially typed `[` or title.
+        let line = text.lines().nth(position.line as usize).unwrap_or_default();
+        let replaced = document::list_item(line)
+            .map(|item| document::utf16_len(&line[..line.len() - item.len()]))
+            .filter(|start| *start <= position.character)
+            .map(|start| Range::new(Position::new(position.line, start), position));
+
+        let items = candidates.into_iter()
+            .map(|(id, title)| {
+                let label = format!("[{id}] {title}");
+                CompletionItem {
+                    filter_text: Some(format!("[{id}] {title} {title}")),
+                    kind: Some(kind),
+                    detail: Some(detail.to_string()),
+                    text_edit: replaced.map(|range| CompletionTextEdit::Edit(TextEdit::new(range, label.clone()))),
+                    label,
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        Ok(items)
+    }
+
+    /// Location of the referenced note, rendered with `note_to_md` into the cache directory.
+    fn definition(&self, params: GotoDefinitionParams) -> Result<Option<GotoDefinitionResponse>, LspError> {
+        let position = params.text_document_position_params.position;
+        let text = self.document(&params.text_document_position_params.text_document.uri);
+
+        let Some(id) = document::id_at(text, position.line, position.character) else {
+            return Ok(None)
+        };
+        let md = match self.vault.note_to_md(&id) {
+            Ok(md) => md,
+            Err(VaultError::NoteNotFound) => return Ok(None),
+            Err(e) => return Err(e.into())
+        };
+
+        let path = xdg::BaseDirectories::with_prefix("spark")
+            .map_err(|_| LspError::CannotOpenXdgDirectory)?
+            .place_cache_file(format!("notes/{id}.md"))?;
+        fs::write(&path, md)?;
+
+        let uri = Url::from_file_path(&path)
+            .map_err(|_| LspError::CannotOpenXdgDirectory)?;
+
+        Ok(Some(GotoDefinitionResponse::Scalar(Location::new(uri, Range::default()))))
+    }
+
+    fn hover(&self, params: HoverParams) -> Result<Option<Hover>, LspError> {
+        let position = params.text_document_position_params.position;
+        let text = self.document(&params.text_document_position_params.text_document.uri);
+
+        let Some(id) = document::id_at(text, position.line, position.character) else {
+            return Ok(None)
+        };
+
+        let value = match (self.vault.get_note(&id), self.vault.get_source(&id)) {
+            (Ok(note), _) => {
+                let preview: Vec<&str> = note.contents.trim().lines().take(HOVER_LINES).collect();
+                format!("**{}**\n\n{}", note.title, preview.join("\n"))
+            },
+            (_, Ok(source)) => {
+                let cited_by = self.vault.citing_notes(&source.id)?.len();
+                format!("**{}**\n\nSource cited by {cited_by} note(s)", source.title)
+            },
+            (Err(VaultError::NoteNotFound), Err(VaultError::SourceNotFound)) => return Ok(None),
+            (Err(VaultError::NoteNotFound), Err(e)) | (Err(e), _) => return Err(e.into())
+        };
+
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent { kind: MarkupKind::Markdown, value }),
+            range: None,
+        }))
+    }
+
+    /// Structure errors from the markdown parser and references the vault cannot resolve.
+    fn diagnostics(&self, text: &str) -> Result<Vec<Diagnostic>, LspError> {
+        let mut diagnostics = vec![];
+
+        match md_to_new_note(text.to_string()) {
+            Ok(note) if note.title.trim().is_empty() => diagnostics.push(Self::diagnostic(
+                Range::default(),
+                DiagnosticSeverity::ERROR,
+                "The note has no title, start it with `# [ID] Title`".to_string()
+            )),
+            Ok(_) => (),
+            Err(e) => diagnostics.push(Self::diagnostic(
+                Range::default(),
+                DiagnosticSeverity::ERROR,
+                format!("{e} Expected `# [ID] Title`, contents, an optional `## Aliases` list, then `## References` with `### Internal` and `### External` lists")
+            ))
+        }
+
+        for item in document::items(text) {
+            if let Some((severity, message)) = self.check_item(&item)? {
+                let range = Range::new(Position::new(item.line, item.start), Position::new(item.line, item.end));
+                diagnostics.push(Self::diagnostic(range, severity, message));
+            }
+        }
+
+        Ok(diagnostics)
+    }
+
+    fn check_item(&self, item: &Item) -> Result<Option<(DiagnosticSeverity, String)>, LspError> {
+        let title = document::item_title(item);
+
+        let problem = match (item.section, &item.id) {
+            (Section::Internal, Some(id)) => match self.vault.get_note(id) {
+                Ok(note) if !title.is_empty() && note.title != title =>
+                    Some((DiagnosticSeverity::HINT, format!("Note {id} is titled {}", note.title))),
+                Ok(_) => None,
+                Err(VaultError::NoteNotFound) => Some((DiagnosticSeverity::ERROR, format!("There is no note with id {id}"))),
+                Err(e) => return Err(e.into())
+            },
+            (Section::Internal, None) => match self.vault.find_note(&title) {
+                Ok(_) => None,
+                Err(VaultError::NoteNotFound) => match self.vault.config().stubs {
+                    StubPolicy::Error => Some((DiagnosticSeverity::ERROR, format!("There is no note titled {title}"))),
+                    StubPolicy::Create => Some((DiagnosticSeverity::WARNING, format!("There is no note titled {title}, an empty note will be created"))),
+                },
+                Err(e @ VaultError::AmbiguousAlias(..)) => Some((DiagnosticSeverity::ERROR, e.to_string())),
+                Err(e) => return Err(e.into())
+            },
+            (Section::External, Some(id)) => match self.vault.get_source(id) {
+                Ok(source) if !title.is_empty() && source.title != title =>
+                    Some((DiagnosticSeverity::HINT, format!("Source {id} is titled {}", source.title))),
+                Ok(_) => None,
+                Err(VaultError::SourceNotFound) => Some((DiagnosticSeverity::ERROR, format!("There is no source with id {id}"))),
+                Err(e) => return Err(e.into())
+            },
+            (Section::External, None) => match self.vault.find_source(&title) {
+                Ok(_) => None,
+                Err(VaultError::SourceNotFound) => Some((DiagnosticSeverity::INFORMATION, format!("Source {title} will be created"))),
+                Err(e) => return Err(e.into())
+            },
+            _ => None
+        };
+
+        Ok(problem)
+    }
+
+    fn diagnostic(range: Range, severity: DiagnosticSeverity, message: String) -> Diagnostic {
+        Diagnostic {
+            range,
+            severity: Some(severity),
+            source: Some("spark".to_string()),
+            message,
+            ..Default::default()
+        }
+    }
+}
@@ -1,24 +1,85 @@
 use std::process::ExitCode;
 use console::style;
-use clap::command;
-use spark::{cli::{error::CliError, subcommands} , controller::Controller, init_db::setup_database};
+use clap::{arg, command, value_parser, ArgMatches, Command};
+use spark::{cli::{error::CliError, subcommands} , config::Config, controller::Controller, init_db::setup_database, registry::VaultRegistry, storage::sqlite::SqliteStorage, vault::Vault};
 
 
 fn main() -> ExitCode {
-    let matches = command!()
+    let matches = command().get_matches();
+
+    exit(run(matches))
+}
+
+/// The whole command tree, also used to generate shell completions.
+fn command() -> Command {
+    command!()
         .arg_required_else_help(true)
+        .arg(arg!(--vault <vault> "Name of a registered vault or path to a database, overrides SPARK_DB")
+            .value_parser(value_parser!(String))
+            .global(true))
+        .arg(arg!(--format <format> "Output format, overrides output_format from the configuration")
+            .value_parser(["csv", "json"])
+            .global(true))
+        .arg(arg!(--"dry-run" "Check and show what add, new, update, set, rename or dedupe would change without writing")
+            .global(true))
         .subcommand(subcommands::add())
+        .subcommand(subcommands::new())
         .subcommand(subcommands::list())
         .subcommand(subcommands::get())
+        .subcommand(subcommands::search())
         .subcommand(subcommands::update())
         .subcommand(subcommands::set())
-        .get_matches();
+        .subcommand(subcommands::rename())
+        .subcommand(subcommands::undo())
+        .subcommand(subcommands::redo())
+        .subcommand(subcommands::log())
+        .subcommand(subcommands::source())
+        .subcommand(subcommands::dedupe())
+        .subcommand(subcommands::graph())
+        .subcommand(subcommands::related())
+        .subcommand(subcommands::publish())
+        .subcommand(subcommands::watch())
+        .subcommand(subcommands::sync())
+        .subcommand(subcommands::tui())
+        .subcommand(subcommands::serve())
+        .subcommand(subcommands::lsp())
+        .subcommand(subcommands::vault())
+        .subcommand(subcommands::config())
+        .subcommand(subcommands::completions())
+        .subcommand(subcommands::complete())
+}
+
+fn run(matches: ArgMatches) -> Result<&'static str, CliError> {
+    let dry_run_supported = matches!(matches.subcommand_name(), Some("add" | "new" | "update" | "set" | "rename" | "dedupe"));
+    if matches.get_flag("dry-run") && !dry_run_supported {
+        return Err(CliError::DryRunNotSupported(matches.subcommand_name().unwrap_or_default().to_string()))
+    }
+
+    if let Some(("vault", args)) = matches.subcommand() {
+        return Controller::handle_vault_command(args)
+    }
+
+    if let Some(("completions", args)) = matches.subcommand() {
+        return Controller::handle_completions_command(args, command())
+    }
+
+    let registry = VaultRegistry::load()?;
+    let vault = matches.get_one::<String>("vault").map(String::as_str);
+    let vault_name = registry.vault_name(vault);
+
+    if let Some(("config", args)) = matches.subcommand() {
+        return Controller::handle_config_command(args, vault_name.as_deref())
+    }
+
+    let mut config = Config::load(vault_name.as_deref())?;
+    if let Some(format) = matches.get_one::<String>("format") {
+        config.output_format = format.parse()?;
+    }
 
-    let conn = setup_database();
-    let contr = Controller::new(conn);
-    let result = contr.handle_command(matches);
+    let database_url = registry.database_url(vault)?;
+    let vault = Vault::new(SqliteStorage::new(setup_database(&database_url)), config);
 
-    exit(result)
+    Controller::new(vault).handle_command(matches)
 }
 
 fn exit(result: Result<&'static str, CliError>) -> ExitCode {
@@ -0,0 +1,67 @@
+use rusqlite::Connection;
+
+use crate::util::title::normalize;
+
+use super::{error::DbError, note::Note};
+
+#[derive(Debug, Clone)]
+pub struct Alias {
+    pub id: String,
+    pub note_id: String,
+    pub alias: String,
+}
+
+impl Alias {
+    pub fn new(id: String, note_id: String, alias: String) -> Self {
+        Self {
+            id,
+            note_id,
+            alias
+        }
+    }
+
+    pub fn add(&self, conn: &Connection) -> Result<(), DbError> {
+        conn.execute(
+            "INSERT INTO aliases (id, note_id, alias, alias_key) VALUES (?1, ?2, ?3, ?4)",
+            (&self.id, &self.note_id, &self.alias, normalize(&self.alias)),
+        )?;
+
+        Ok(())
+    }
+
+    pub fn get_by_note_id(note_id: &str, conn: &Connection) -> Result<Vec<String>, DbError> {
+        let mut stmt = conn.prepare("SELECT alias FROM aliases where note_id = ?1 order by alias")?;
+
+        let aliases: Result<Vec<String>, rusqlite::Error> = stmt.query_map([note_id], |row| {
+            row.get::<usize, String>(0)
+        })?.collect();
+
+        Ok(aliases?)
+    }
+
+    pub fn get_notes_by_alias(alias: &str, conn: &Connection) -> Result<Vec<Note>, DbError> {
+        let mut stmt = conn.prepare("SELECT distinct note_id FROM aliases where alias_key = ?1")?;
+
+        let note_ids = stmt.query_map([normalize(alias)], |row| {
+            row.get::<usize, String>(0)
+        })?;
+
+        let mut notes = vec![];
+        for note_id in note_ids {
+            let note = Note::get_by_id(note_id?, conn)?
+                .ok_or(DbError::InternalError)?;
+            notes.push(note);
+        }
+
+        Ok(notes)
+    }
+
+    pub fn delete_by_note_id(note_id: &str, conn: &Connection) -> Result<(), DbError> {
+        conn.execute(
+            "DELETE FROM aliases WHERE note_id = ?1", 
+            (&note_id,),
+            )?;
+
+        Ok(())
+    }
+}
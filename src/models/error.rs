@@ -3,14 +3,36 @@ pub enum DbError {
     #[error("Something went wrong, contact the developer")]
     InternalError,
 
+    #[error("Constraint violated on {0}")]
+    ConstraintViolation(String),
+
     #[error(transparent)]
     Generic(#[from] anyhow::Error)
 }
 
+/// Name `ConstraintViolation` uses for violated foreign keys.
+pub const FOREIGN_KEY: &str = "foreign key";
+
 impl From<rusqlite::Error> for DbError {
    fn from(value: rusqlite::Error) -> Self {
        match value {
-           e => DbError::Generic(e.into())
+           rusqlite::Error::SqliteFailure(error, Some(message)) if error.code == rusqlite::ErrorCode::ConstraintViolation => {
+               DbError::ConstraintViolation(constraint(&message))
+           },
+           value => DbError::Generic(value.into())
        }
    } 
 }
+
+/// The table of a violated unique constraint, like `UNIQUE constraint failed:
+/// notes.title`, or `FOREIGN_KEY` as SQLite doesn't name the foreign key.
+fn constraint(message: &str) -> String {
+    if message.starts_with("FOREIGN KEY") {
+        return FOREIGN_KEY.to_string()
+    }
+
+    message.split_once(": ")
+        .and_then(|(_, columns)| columns.split('.').next())
+        .unwrap_or(message)
+        .to_string()
+}
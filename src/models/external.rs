@@ -1,8 +1,9 @@
 use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
 
-use super::{error::DbError, sources::Source};
+use super::{error::DbError, note::Note, sources::Source};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExternalReference {
     pub id: String,
     pub note_id: String,
@@ -30,7 +31,7 @@ impl ExternalReference {
         let mut stmt = conn.prepare("SELECT reference_id FROM external_references where note_id = ?1")?;
 
         let references = stmt.query_map([note_id], |row| {
-            Ok(row.get::<usize, String>(0)?)
+            row.get::<usize, String>(0)
         })?;
 
         let mut sources = vec![];
@@ -44,6 +45,22 @@ impl ExternalReference {
         Ok(sources)
     }
 
+    pub fn list(conn: &Connection) -> Result<Vec<ExternalReference>, DbError> {
+        let mut stmt = conn.prepare("SELECT id, note_id, reference_id FROM external_references")?;
+
+        let references = stmt.query_map([], |row| {
+            Ok(ExternalReference{
+                id: row.get(0)?,
+                note_id: row.get(1)?,
+                reference_id: row.get(2)?,
+            })
+        })?;
+
+        let references: Result<Vec<ExternalReference>, rusqlite::Error> = references.collect();
+
+        Ok(references?)
+    }
+
     pub fn delete_by_note_id(note_id: &str, conn: &Connection) -> Result<(), DbError> {
         conn.execute(
             "DELETE FROM external_references WHERE note_id = ?1", 
@@ -53,6 +70,33 @@ impl ExternalReference {
         Ok(())
     }
 
+    pub fn get_by_reference_id(reference_id: &str, conn: &Connection) -> Result<Vec<Note>, DbError> {
+        let mut stmt = conn.prepare("SELECT note_id FROM external_references where reference_id = ?1")?;
+
+        let references = stmt.query_map([reference_id], |row| {
+            row.get::<usize, String>(0)
+        })?;
+
+        let mut notes = vec![];
+        for note_id in references {
+            let note = Note::get_by_id(note_id?, conn)?
+                .ok_or(DbError::InternalError)?;
+            notes.push(note);
+        }
+
+        notes.sort_by(|a, b| a.title.cmp(&b.title));
+        Ok(notes)
+    }
+
+    pub fn delete_by_reference_id(reference_id: &str, conn: &Connection) -> Result<(), DbError> {
+        conn.execute(
+            "DELETE FROM external_references WHERE reference_id = ?1", 
+            (&reference_id,),
+            )?;
+
+        Ok(())
+    }
+
     pub fn exists(note_id: &str, reference_id: &str, conn: &Connection) -> Result<bool, DbError> {
         let mut statement = conn.prepare("select * from external_references where note_id = ?1 and reference_id = ?2")?;
         let exists = statement.exists([note_id, reference_id])?;
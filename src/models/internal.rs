@@ -1,8 +1,9 @@
 use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
 
 use super::{error::DbError, note::Note};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InternalReference {
     pub id: String,
     pub note_id: String,
@@ -31,7 +32,7 @@ impl InternalReference {
         let mut stmt = conn.prepare("SELECT reference_id FROM internal_references where note_id = ?1")?;
 
         let references = stmt.query_map([note_id], |row| {
-            Ok(row.get::<usize, String>(0)?)
+            row.get::<usize, String>(0)
         })?;
 
         let mut notes = vec![];
@@ -46,8 +47,27 @@ impl InternalReference {
         Ok(notes)
     }
 
+    pub fn get_by_reference_id(reference_id: &str, conn: &Connection) -> Result<Vec<Note>, DbError> {
+        let mut stmt = conn.prepare("SELECT note_id FROM internal_references where reference_id = ?1")?;
+
+        let references = stmt.query_map([reference_id], |row| {
+            row.get::<usize, String>(0)
+        })?;
+
+        let mut notes = vec![];
+        for note_id in references {
+            let note = Note::get_by_id(note_id?, conn)?
+                .ok_or(DbError::InternalError)?;
+            notes.push(note);
+        }
+
+        notes.sort_by(|a, b| a.title.cmp(&b.title));
+
+        Ok(notes)
+    }
+
     pub fn get_by_note_id_raw(note_id: &str, conn: &Connection) -> Result<Vec<InternalReference>, DbError> {
-        let mut stmt = conn.prepare("SELECT reference_id FROM internal_references where note_id = ?1")?;
+        let mut stmt = conn.prepare("SELECT id, note_id, reference_id FROM internal_references where note_id = ?1")?;
 
         let references = stmt.query_map([note_id], |row| {
             Ok(InternalReference{
@@ -62,6 +82,22 @@ impl InternalReference {
         Ok(references?)
     }
 
+    pub fn list(conn: &Connection) -> Result<Vec<InternalReference>, DbError> {
+        let mut stmt = conn.prepare("SELECT id, note_id, reference_id FROM internal_references")?;
+
+        let references = stmt.query_map([], |row| {
+            Ok(InternalReference{
+                id: row.get(0)?,
+                note_id: row.get(1)?,
+                reference_id: row.get(2)?,
+            })
+        })?;
+
+        let references: Result<Vec<InternalReference>, rusqlite::Error> = references.collect();
+
+        Ok(references?)
+    }
+
     pub fn delete(self, conn: &Connection) -> Result<(), DbError> {
         conn.execute(
             "DELETE FROM internal_references WHERE id = ?1", 
@@ -80,6 +116,15 @@ impl InternalReference {
         Ok(())
     }
 
+    pub fn delete_by_reference_id(reference_id: &str, conn: &Connection) -> Result<(), DbError> {
+        conn.execute(
+            "DELETE FROM internal_references WHERE reference_id = ?1", 
+            (&reference_id,),
+            )?;
+
+        Ok(())
+    }
+
     pub fn exists(note_id: &str, reference_id: &str, conn: &Connection) -> Result<bool, DbError> {
         let mut statement = conn.prepare("select * from internal_references where note_id = ?1 and reference_id = ?2")?;
         let exists = statement.exists([note_id, reference_id])?;
@@ -2,4 +2,6 @@ pub mod note;
 pub mod sources;
 pub mod internal;
 pub mod external;
+pub mod aliases;
+pub mod operation;
 pub mod error;
@@ -1,25 +1,32 @@
-use rusqlite::Connection;
+use rusqlite::{Connection, Row};
+use serde::{Deserialize, Serialize};
+
+use crate::util::title::normalize;
 
 use super::error::DbError;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Note {
     pub id: String,
     pub title: String,
     pub contents: String,
+    pub sequence: Option<String>,
+    /// Incremented by every update, used to detect concurrent edits.
+    pub revision: i64,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize)]
 pub struct NoteListItem {
     pub id: String,
-    pub title: String
+    pub title: String,
+    pub sequence: Option<String>,
 }
 
 impl Note {
     pub fn add(&self, conn: &Connection) -> Result<(), DbError> {
         conn.execute(
-            "INSERT INTO notes (id, title, contents) VALUES (?1, ?2, ?3)",
-            (&self.id, &self.title, &self.contents),
+            "INSERT INTO notes (id, title, title_key, contents, sequence, revision) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            (&self.id, &self.title, normalize(&self.title), &self.contents, &self.sequence, self.revision),
         )?;
 
         Ok(())
@@ -33,13 +40,7 @@ impl Note {
     }
 
     pub fn get_by_title(title: String, conn: &Connection) -> Result<Option<Note>, DbError> {
-        let note = conn.query_row("select * from notes where title = ?1", [title], |row| {
-            Ok(Note {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                contents: row.get(2)?
-            })
-        });
+        let note = conn.query_row("select id, title, contents, sequence, revision from notes where title_key = ?1", [normalize(&title)], Self::from_row);
         
         match note {
             Ok(note) => Ok(Some(note)),
@@ -49,13 +50,17 @@ impl Note {
     }
 
     pub fn get_by_id(id: String, conn: &Connection) -> Result<Option<Note>, DbError> {
-        let note = conn.query_row("select * from notes where id = ?1", [id], |row| {
-            Ok(Note {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                contents: row.get(2)?
-            })
-        });
+        let note = conn.query_row("select id, title, contents, sequence, revision from notes where id = ?1", [id], Self::from_row);
+        
+        match note {
+            Ok(note) => Ok(Some(note)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into())
+        }
+    }
+
+    pub fn get_by_sequence(sequence: String, conn: &Connection) -> Result<Option<Note>, DbError> {
+        let note = conn.query_row("select id, title, contents, sequence, revision from notes where sequence = ?1", [sequence], Self::from_row);
         
         match note {
             Ok(note) => Ok(Some(note)),
@@ -65,11 +70,31 @@ impl Note {
     }
 
     pub fn list(conn: &Connection) -> Result<Vec<NoteListItem>, DbError> {
-        let mut stmt = conn.prepare("select id, title from notes")?;
+        let mut stmt = conn.prepare("select id, title, sequence from notes order by rowid")?;
         let notes: Result<Vec<NoteListItem>, rusqlite::Error> = stmt.query_map([], |row| {
             Ok(NoteListItem {
                 id: row.get(0)?,
                 title: row.get(1)?,
+                sequence: row.get(2)?,
+            })
+        })?.collect();
+
+        Ok(notes?)
+    }
+
+    /// Notes whose title, alias or contents contain `query`, ignoring ascii case.
+    pub fn search(query: &str, conn: &Connection) -> Result<Vec<NoteListItem>, DbError> {
+        let mut stmt = conn.prepare("select distinct notes.id, notes.title, notes.sequence from notes
+            left join aliases on aliases.note_id = notes.id
+            where notes.title like ?1 or notes.contents like ?1 or aliases.alias like ?1
+                or notes.title_key like ?2 or aliases.alias_key like ?2")?;
+        let pattern = format!("%{}%", query);
+        let key_pattern = format!("%{}%", normalize(query));
+        let notes: Result<Vec<NoteListItem>, rusqlite::Error> = stmt.query_map([pattern, key_pattern], |row| {
+            Ok(NoteListItem {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                sequence: row.get(2)?,
             })
         })?.collect();
 
@@ -78,10 +103,29 @@ impl Note {
 
     pub fn update(&self, conn: &Connection) -> Result<(), DbError> {
         conn.execute(
-            "update notes set title = ?1, contents = ?2 where id = ?3",
-            (&self.title, &self.contents, &self.id)
+            "update notes set title = ?1, title_key = ?2, contents = ?3, sequence = ?4, revision = revision + 1 where id = ?5",
+            (&self.title, normalize(&self.title), &self.contents, &self.sequence, &self.id)
         )?;
 
         Ok(())
     }
+
+    pub fn delete(id: &str, conn: &Connection) -> Result<(), DbError> {
+        conn.execute(
+            "DELETE FROM notes WHERE id = ?1", 
+            (&id,),
+            )?;
+
+        Ok(())
+    }
+
+    fn from_row(row: &Row) -> Result<Note, rusqlite::Error> {
+        Ok(Note {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            contents: row.get(2)?,
+            sequence: row.get(3)?,
+            revision: row.get(4)?,
+        })
+    }
 }
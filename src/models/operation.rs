@@ -0,0 +1,89 @@
+use rusqlite::{Connection, Row};
+use serde::Serialize;
+
+use super::error::DbError;
+
+/// Entry of the operation log, `before` and `after` are the JSON snapshots
+/// `Vault::undo` and `Vault::redo` restore.
+#[derive(Debug, Clone, Serialize)]
+pub struct Operation {
+    pub id: i64,
+    pub command: String,
+    pub created_at: String,
+    pub undone: bool,
+    #[serde(skip)]
+    pub before: String,
+    #[serde(skip)]
+    pub after: String,
+}
+
+impl Operation {
+    const COLUMNS: &'static str = "id, command, created_at, undone, before, after";
+
+    fn from_row(row: &Row) -> Result<Self, rusqlite::Error> {
+        Ok(Operation {
+            id: row.get(0)?,
+            command: row.get(1)?,
+            created_at: row.get(2)?,
+            undone: row.get(3)?,
+            before: row.get(4)?,
+            after: row.get(5)?,
+        })
+    }
+
+    pub fn add(command: &str, before: &str, after: &str, conn: &Connection) -> Result<i64, DbError> {
+        conn.execute(
+            "INSERT INTO operations (command, before, after) VALUES (?1, ?2, ?3)",
+            (command, before, after),
+        )?;
+
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// The `limit` most recent operations, newest first.
+    pub fn list(limit: usize, conn: &Connection) -> Result<Vec<Operation>, DbError> {
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM operations order by id desc limit ?1", Self::COLUMNS))?;
+
+        let operations: Result<Vec<Operation>, rusqlite::Error> = stmt.query_map([limit as i64], Self::from_row)?
+            .collect();
+
+        Ok(operations?)
+    }
+
+    /// Newest operation which is not undone.
+    pub fn last_done(conn: &Connection) -> Result<Option<Operation>, DbError> {
+        let operation = conn.query_row(&format!("SELECT {} FROM operations where undone = 0 order by id desc limit 1", Self::COLUMNS), [], Self::from_row);
+
+        match operation {
+            Ok(operation) => Ok(Some(operation)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into())
+        }
+    }
+
+    /// Oldest undone operation, the next one to redo.
+    pub fn first_undone(conn: &Connection) -> Result<Option<Operation>, DbError> {
+        let operation = conn.query_row(&format!("SELECT {} FROM operations where undone = 1 order by id limit 1", Self::COLUMNS), [], Self::from_row);
+
+        match operation {
+            Ok(operation) => Ok(Some(operation)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into())
+        }
+    }
+
+    pub fn set_undone(id: i64, undone: bool, conn: &Connection) -> Result<(), DbError> {
+        conn.execute(
+            "UPDATE operations SET undone = ?1 WHERE id = ?2",
+            (undone, id),
+        )?;
+
+        Ok(())
+    }
+
+    pub fn delete_undone(conn: &Connection) -> Result<(), DbError> {
+        conn.execute("DELETE FROM operations WHERE undone = 1", ())?;
+
+        Ok(())
+    }
+}
@@ -1,8 +1,11 @@
 use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::util::title::normalize;
 
 use super::error::DbError;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Source {
     pub id: String,
     pub title: String,
@@ -26,15 +29,33 @@ impl Source {
 
     pub fn add(&self, conn: &Connection) -> Result<(), DbError> {
         conn.execute(
-            "INSERT INTO sources (id, title) VALUES (?1, ?2)",
-            (&self.id, &self.title),
+            "INSERT INTO sources (id, title, title_key) VALUES (?1, ?2, ?3)",
+            (&self.id, &self.title, normalize(&self.title)),
+        )?;
+
+        Ok(())
+    }
+
+    pub fn update(&self, conn: &Connection) -> Result<(), DbError> {
+        conn.execute(
+            "update sources set title = ?1, title_key = ?2 where id = ?3",
+            (&self.title, normalize(&self.title), &self.id)
         )?;
 
         Ok(())
     }
 
+    pub fn delete(id: &str, conn: &Connection) -> Result<(), DbError> {
+        conn.execute(
+            "DELETE FROM sources WHERE id = ?1", 
+            (&id,),
+            )?;
+
+        Ok(())
+    }
+
     pub fn list(conn: &Connection) -> Result<Vec<Source>, DbError> {
-        let mut stmt = conn.prepare("select id, title from sources")?;
+        let mut stmt = conn.prepare("select id, title from sources order by rowid")?;
         let notes: Result<Vec<Source>, rusqlite::Error> = stmt.query_map([], |row| {
             Ok(Source {
                 id: row.get(0)?,
@@ -46,7 +67,7 @@ impl Source {
     }
 
     pub fn get_by_id(id: String, conn: &Connection) -> Result<Option<Source>, DbError> {
-        let source = conn.query_row("select * from sources where id = ?1", [id], |row| {
+        let source = conn.query_row("select id, title from sources where id = ?1", [id], |row| {
             Ok(Source {
                 id: row.get(0)?,
                 title: row.get(1)?,
@@ -61,7 +82,7 @@ impl Source {
     }
 
     pub fn get_by_title(title: String, conn: &Connection) -> Result<Option<Source>, DbError> {
-        let source = conn.query_row("select * from sources where title = ?1", [title], |row| {
+        let source = conn.query_row("select id, title from sources where title_key = ?1", [normalize(&title)], |row| {
             Ok(Source {
                 id: row.get(0)?,
                 title: row.get(1)?
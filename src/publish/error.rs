@@ -0,0 +1,13 @@
+use crate::vault::error::VaultError;
+
+#[derive(thiserror::Error, Debug)]
+pub enum PublishError {
+    #[error("Cannot write {0}: {1}")]
+    CannotWrite(String, String),
+
+    #[error(transparent)]
+    Vault(#[from] VaultError),
+
+    #[error(transparent)]
+    Generic(#[from] anyhow::Error)
+}
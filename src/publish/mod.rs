@@ -0,0 +1,410 @@
+use std::{collections::{BTreeMap, BTreeSet}, fs, path::Path, sync::LazyLock};
+
+use regex::{Captures, Regex};
+
+use crate::{models::{note::Note, sources::Source}, util::{id::ID_PATTERN, wiki::replace_links}, vault::{error::VaultError, Vault}};
+
+use self::error::PublishError;
+
+pub mod error;
+
+/// An `[ID]` mention, `link` telling it apart from ids which are already markdown links.
+static MENTION: LazyLock<Regex> = LazyLock::new(|| Regex::new(&format!(r"{ID_PATTERN}(?<link>\()?")).expect("Mention pattern is valid"));
+
+const STYLE: &str = "body { max-width: 46rem; margin: 2rem auto; padding: 0 1rem; font-family: sans-serif; line-height: 1.5; }
+nav a { margin-right: 1rem; }
+h2 { margin-top: 2rem; font-size: 1.1rem; }
+.muted { color: #777; }
+#results li, .index li { margin: .2rem 0; }
+";
+
+const SEARCH_SCRIPT: &str = "const input = document.getElementById('search');
+const results = document.getElementById('results');
+input.addEventListener('input', () => {
+  const query = input.value.trim().toLowerCase();
+  results.innerHTML = '';
+  if (!query) return;
+  for (const note of SPARK_SEARCH) {
+    const haystack = [note.title, ...note.aliases, note.text].join('\\n').toLowerCase();
+    if (!haystack.includes(query)) continue;
+    const item = document.createElement('li');
+    const link = document.createElement('a');
+    link.href = note.url;
+    link.textContent = note.title;
+    item.appendChild(link);
+    results.appendChild(item);
+  }
+});
+";
+
+/// What `publish` wrote.
+#[derive(Debug)]
+pub struct Published {
+    pub notes: usize,
+    pub sources: usize,
+}
+
+/// A note selected for publishing with everything its page shows.
+struct Page {
+    note: Note,
+    aliases: Vec<String>,
+    references: Vec<Note>,
+    sources: Vec<Source>,
+    backlinks: Vec<Note>,
+}
+
+/// Writes a static site for the notes with ids in `roots` and every note
+/// within `depth` references of them, or for the whole vault when `roots`
+/// is empty. References to notes outside the selection are not linked.
+pub fn publish(vault: &Vault, outdir: &Path, roots: &[String], depth: usize) -> Result<Published, PublishError> {
+    let selected = select(vault, roots, depth)?;
+
+    let mut pages = vec![];
+    for id in &selected {
+        let note = vault.get_note(id)?;
+        let references = vault.references_of(id)?;
+        pages.push(Page {
+            aliases: vault.aliases_of(id)?,
+            references: references.internal,
+            sources: references.external,
+            backlinks: vault.backlinks_of(id)?.into_iter().filter(|n| selected.contains(&n.id)).collect(),
+            note,
+        });
+    }
+    pages.sort_by_key(|page| page.note.title.to_lowercase());
+
+    for page in &pages {
+        let html = note_page(vault, page, &selected);
+        write(&outdir.join("notes").join(format!("{}.html", page.note.id)), &html)?;
+    }
+
+    let mut bibliography: BTreeMap<String, (Source, Vec<&Note>)> = BTreeMap::new();
+    for page in &pages {
+        for source in &page.sources {
+            bibliography.entry(source.id.clone())
+                .or_insert_with(|| (source.clone(), vec![]))
+                .1.push(&page.note);
+        }
+    }
+    let mut bibliography: Vec<(Source, Vec<&Note>)> = bibliography.into_values().collect();
+    bibliography.sort_by_key(|(source, _)| source.title.to_lowercase());
+
+    write(&outdir.join("index.html"), &index_page(&pages))?;
+    write(&outdir.join("sources.html"), &sources_page(&bibliography))?;
+    write(&outdir.join("style.css"), STYLE)?;
+
+    let index = search_index(&pages);
+    write(&outdir.join("search.json"), &index)?;
+    write(&outdir.join("search.js"), &format!("const SPARK_SEARCH = {index};\n{SEARCH_SCRIPT}"))?;
+
+    Ok(Published { notes: pages.len(), sources: bibliography.len() })
+}
+
+fn select(vault: &Vault, roots: &[String], depth: usize) -> Result<BTreeSet<String>, PublishError> {
+    let graph = vault.graph()?;
+
+    if roots.is_empty() {
+        return Ok(graph.notes().map(|note| note.id.clone()).collect())
+    }
+
+    let mut selected = BTreeSet::new();
+    for root in roots {
+        graph.note(root).ok_or(VaultError::NoteNotFound)?;
+        selected.insert(root.clone());
+        selected.extend(graph.around(root, depth).into_iter().map(|(note, _)| note.id));
+    }
+
+    Ok(selected)
+}
+
+fn note_page(vault: &Vault, page: &Page, selected: &BTreeSet<String>) -> String {
+    let note = &page.note;
+    let mut body = format!("<h1>{}</h1>\n", escape(&note.title));
+
+    if !page.aliases.is_empty() {
+        body.push_str(&format!("<p class=\"muted\">Also known as {}</p>\n", escape(&page.aliases.join(", "))));
+    }
+
+    body.push_str(&comrak::markdown_to_html(&link_mentions(vault, &note.contents, selected), &comrak::Options::default()));
+
+    if !page.references.is_empty() {
+        body.push_str("<h2>References</h2>\n<ul>\n");
+        for reference in &page.references {
+            body.push_str(&format!("<li>{}</li>\n", note_link(reference, selected)));
+        }
+        body.push_str("</ul>\n");
+    }
+
+    if !page.sources.is_empty() {
+        body.push_str("<h2>Sources</h2>\n<ul>\n");
+        for source in &page.sources {
+            body.push_str(&format!("<li><a href=\"../sources.html#{}\">{}</a></li>\n", source.id, escape(&source.title)));
+        }
+        body.push_str("</ul>\n");
+    }
+
+    if !page.backlinks.is_empty() {
+        body.push_str("<h2>Backlinks</h2>\n<ul>\n");
+        for backlink in &page.backlinks {
+            body.push_str(&format!("<li>{}</li>\n", note_link(backlink, selected)));
+        }
+        body.push_str("</ul>\n");
+    }
+
+    layout(&note.title, "../", &body)
+}
+
+fn index_page(pages: &[Page]) -> String {
+    let mut body = String::from("<h1>Notes</h1>\n<input id=\"search\" type=\"search\" placeholder=\"Search\" autofocus>\n<ul id=\"results\"></ul>\n");
+
+    let mut letter = None;
+    for page in pages {
+        let first = page.note.title.chars().next().map(|c| c.to_uppercase().to_string());
+        if first != letter {
+            if letter.is_some() {
+                body.push_str("</ul>\n");
+            }
+            body.push_str(&format!("<h2>{}</h2>\n<ul class=\"index\">\n", escape(first.as_deref().unwrap_or_default())));
+            letter = first;
+        }
+        body.push_str(&format!("<li><a href=\"notes/{}.html\">{}</a></li>\n", page.note.id, escape(&page.note.title)));
+    }
+    if letter.is_some() {
+        body.push_str("</ul>\n");
+    }
+    body.push_str("<script src=\"search.js\"></script>\n");
+
+    layout("Notes", "", &body)
+}
+
+fn sources_page(bibliography: &[(Source, Vec<&Note>)]) -> String {
+    let mut body = String::from("<h1>Sources</h1>\n<ul>\n");
+
+    for (source, notes) in bibliography {
+        let cited_by: Vec<String> = notes.iter()
+            .map(|note| format!("<a href=\"notes/{}.html\">{}</a>", note.id, escape(&note.title)))
+            .collect();
+        body.push_str(&format!(
+            "<li id=\"{}\">{} <span class=\"muted\">cited by {}</span></li>\n",
+            source.id, escape(&source.title), cited_by.join(", ")
+        ));
+    }
+    body.push_str("</ul>\n");
+
+    layout("Sources", "", &body)
+}
+
+fn search_index(pages: &[Page]) -> String {
+    let entries: Vec<serde_json::Value> = pages.iter()
+        .map(|page| serde_json::json!({
+            "id": page.note.id,
+            "title": page.note.title,
+            "aliases": page.aliases,
+            "url": format!("notes/{}.html", page.note.id),
+            "text": page.note.contents.trim(),
+        }))
+        .collect();
+
+    serde_json::Value::from(entries).to_string()
+}
+
+/// Turns `[[Title]]` and `[ID]` mentions of published notes into markdown
+/// links, mentions of other notes into plain text. Code is left as written.
+fn link_mentions(vault: &Vault, contents: &str, selected: &BTreeSet<String>) -> String {
+    outside_code(contents, |text| {
+        let text = replace_links(text, |title, label| {
+            let text = label.unwrap_or(title);
+            match vault.find_note(title) {
+                Ok(note) if selected.contains(&note.id) => format!("[{text}]({}.html)", note.id),
+                _ => text.to_string()
+            }
+        });
+
+        MENTION.replace_all(&text, |caps: &Captures| {
+            let id = &caps["id"];
+            match vault.get_note(id) {
+                Ok(note) if caps.name("link").is_none() && selected.contains(id) => format!("[{}]({id}.html)", note.title),
+                _ => caps[0].to_string()
+            }
+        }).into_owned()
+    })
+}
+
+/// Applies `f` to the parts of markdown `text` outside fenced code blocks
+/// and inline code spans.
+fn outside_code(text: &str, f: impl Fn(&str) -> String) -> String {
+    let mut result = String::new();
+    let mut prose = String::new();
+    let mut fence: Option<String> = None;
+
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim();
+        match &fence {
+            Some(open) => {
+                result.push_str(line);
+                if trimmed.starts_with(open.as_str()) && trimmed.chars().all(|c| open.starts_with(c)) {
+                    fence = None;
+                }
+            },
+            None => match fence_marker(trimmed) {
+                Some(marker) => {
+                    result.push_str(&outside_spans(&prose, &f));
+                    prose.clear();
+                    result.push_str(line);
+                    fence = Some(marker);
+                },
+                None => prose.push_str(line)
+            }
+        }
+    }
+    result.push_str(&outside_spans(&prose, &f));
+
+    result
+}
+
+/// Run of at least three backticks or tildes opening a fenced code block.
+fn fence_marker(line: &str) -> Option<String> {
+    let first = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let marker: String = line.chars().take_while(|c| *c == first).collect();
+
+    (marker.len() >= 3).then_some(marker)
+}
+
+/// Applies `f` to the parts of `text` outside code spans, a span being
+/// closed by a run of as many backticks as opened it.
+fn outside_spans(text: &str, f: impl Fn(&str) -> String) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+
+    while let Some(open) = rest.find('`') {
+        let ticks = backticks(&rest[open..]);
+        let code = open + ticks;
+
+        let mut close = None;
+        let mut offset = code;
+        while let Some(found) = rest[offset..].find('`') {
+            let run = backticks(&rest[offset + found..]);
+            if run == ticks {
+                close = Some(offset + found + run);
+                break
+            }
+            offset += found + run;
+        }
+
+        match close {
+            Some(close) => {
+                result.push_str(&f(&rest[..open]));
+                result.push_str(&rest[open..close]);
+                rest = &rest[close..];
+            },
+            None => {
+                result.push_str(&f(&rest[..code]));
+                rest = &rest[code..];
+            }
+        }
+    }
+    result.push_str(&f(rest));
+
+    result
+}
+
+fn backticks(text: &str) -> usize {
+    text.chars().take_while(|c| *c == '`').count()
+}
+
+fn note_link(note: &Note, selected: &BTreeSet<String>) -> String {
+    match selected.contains(&note.id) {
+        true => format!("<a href=\"{}.html\">{}</a>", note.id, escape(&note.title)),
+        false => escape(&note.title)
+    }
+}
+
+fn layout(title: &str, root: &str, body: &str) -> String {
+    format!("<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">
+<title>{}</title>
+<link rel=\"stylesheet\" href=\"{root}style.css\">
+</head>
+<body>
+<nav><a href=\"{root}index.html\">Notes</a><a href=\"{root}sources.html\">Sources</a></nav>
+{body}</body>
+</html>
+", escape(title))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn write(path: &Path, contents: &str) -> Result<(), PublishError> {
+    let cannot_write = |e: std::io::Error| PublishError::CannotWrite(path.display().to_string(), e.to_string());
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(cannot_write)?;
+    }
+
+    fs::write(path, contents).map_err(cannot_write)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::parse::md_to_new_note;
+
+    use super::*;
+
+    /// Vault with `Idea` and `Hidden`, only `Idea` being published.
+    fn vault() -> (Vault, Note, Note, BTreeSet<String>) {
+        let mut vault = Vault::in_memory();
+        let idea = vault.add_note(md_to_new_note("# Idea\n".to_string()).unwrap()).unwrap();
+        let hidden = vault.add_note(md_to_new_note("# Hidden\n".to_string()).unwrap()).unwrap();
+        let selected = BTreeSet::from([idea.id.clone()]);
+
+        (vault, idea, hidden, selected)
+    }
+
+    #[test]
+    fn mentions_of_published_notes_are_linked() {
+        let (vault, idea, _, selected) = vault();
+        let id = &idea.id;
+
+        let linked = link_mentions(&vault, &format!("[[Idea]], [[idea|this]] and [{id}]."), &selected);
+
+        assert_eq!(linked, format!("[Idea]({id}.html), [this]({id}.html) and [Idea]({id}.html)."));
+    }
+
+    #[test]
+    fn mentions_of_other_notes_become_text() {
+        let (vault, _, hidden, selected) = vault();
+        let id = &hidden.id;
+
+        let linked = link_mentions(&vault, &format!("[[Hidden]], [[Missing|gone]] and [{id}]."), &selected);
+
+        assert_eq!(linked, format!("Hidden, gone and [{id}]."));
+    }
+
+    #[test]
+    fn existing_links_are_left_alone() {
+        let (vault, idea, _, selected) = vault();
+        let text = format!("[{}](https://example.com) and [Idea](elsewhere.html)", idea.id);
+
+        assert_eq!(link_mentions(&vault, &text, &selected), text);
+    }
+
+    #[test]
+    fn code_is_left_alone() {
+        let (vault, idea, _, selected) = vault();
+        let id = &idea.id;
+        let text = format!("`[[Idea]]` ``a ` [{id}]`` [[Idea]]\n```md\n[[Idea]] [{id}]\n```\n~~~~\n[[Idea]]\n~~~\n~~~~\nafter [[Idea]] `unclosed [[Idea]]\n");
+
+        let linked = link_mentions(&vault, &text, &selected);
+
+        assert_eq!(linked, format!("`[[Idea]]` ``a ` [{id}]`` [Idea]({id}.html)\n```md\n[[Idea]] [{id}]\n```\n~~~~\n[[Idea]]\n~~~\n~~~~\nafter [Idea]({id}.html) `unclosed [Idea]({id}.html)\n"));
+    }
+}
@@ -0,0 +1,26 @@
+#[derive(thiserror::Error, Debug)]
+pub enum RegistryError {
+    #[error("Vault named {0} is already registered!")]
+    VaultExists(String),
+
+    #[error("Vault named {0} is not registered!")]
+    VaultNotFound(String),
+
+    #[error("Cannot open xdg directories")]
+    CannotOpenXdgDirectory,
+
+    #[error("Invalid vault registry: {0}")]
+    InvalidRegistry(String),
+
+    #[error("Invalid utf8 in database path")]
+    InvalidPath,
+
+    #[error(transparent)]
+    Generic(#[from] anyhow::Error)
+}
+
+impl From<std::io::Error> for RegistryError {
+    fn from(value: std::io::Error) -> Self {
+        RegistryError::Generic(value.into())
+    }
+}
@@ -0,0 +1,215 @@
+use std::{collections::BTreeMap, env, fs, path::{Path, PathBuf}};
+
+use dotenvy::dotenv;
+use serde::{Deserialize, Serialize};
+
+use self::error::RegistryError;
+
+pub mod error;
+
+const REGISTRY_FILE: &str = "vaults.toml";
+const DATABASE_FILE: &str = "spark.db";
+const DATABASE_ENV: &str = "SPARK_DB";
+
+/// Named vaults, stored in `$XDG_CONFIG_HOME/spark/vaults.toml`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct VaultRegistry {
+    pub default: Option<String>,
+    #[serde(default)]
+    pub vaults: BTreeMap<String, PathBuf>,
+}
+
+impl VaultRegistry {
+    pub fn load() -> Result<Self, RegistryError> {
+        Self::load_from(&Self::registry_path()?)
+    }
+
+    pub fn save(&self) -> Result<(), RegistryError> {
+        self.save_to(&Self::registry_path()?)
+    }
+
+    fn load_from(path: &Path) -> Result<Self, RegistryError> {
+        if !path.exists() {
+            return Ok(Self::default())
+        }
+
+        let contents = fs::read_to_string(path)?;
+        toml::from_str(&contents)
+            .map_err(|e| RegistryError::InvalidRegistry(e.message().to_string()))
+    }
+
+    fn save_to(&self, path: &Path) -> Result<(), RegistryError> {
+        let contents = toml::to_string(self)
+            .map_err(|e| RegistryError::InvalidRegistry(e.to_string()))?;
+        fs::write(path, contents)?;
+
+        Ok(())
+    }
+
+    pub fn add(&mut self, name: &str, path: &Path) -> Result<(), RegistryError> {
+        if self.vaults.contains_key(name) {
+            return Err(RegistryError::VaultExists(name.to_string()))
+        }
+
+        let path = match path.is_absolute() {
+            true => path.to_path_buf(),
+            false => env::current_dir()?.join(path),
+        };
+
+        self.vaults.insert(name.to_string(), path);
+        Ok(())
+    }
+
+    /// Forgets the vault, the database itself is left untouched.
+    pub fn remove(&mut self, name: &str) -> Result<PathBuf, RegistryError> {
+        let path = self.vaults.remove(name)
+            .ok_or(RegistryError::VaultNotFound(name.to_string()))?;
+
+        if self.default.as_deref() == Some(name) {
+            self.default = None;
+        }
+
+        Ok(path)
+    }
+
+    pub fn set_default(&mut self, name: &str) -> Result<(), RegistryError> {
+        if !self.vaults.contains_key(name) {
+            return Err(RegistryError::VaultNotFound(name.to_string()))
+        }
+
+        self.default = Some(name.to_string());
+        Ok(())
+    }
+
+    /// Picks the database location, in order: `vault` (a registered name or a path),
+    /// `SPARK_DB`, the default vault, `DATABASE_URL` in debug builds and finally
+    /// `spark.db` in the xdg data directory.
+    pub fn database_url(&self, vault: Option<&str>) -> Result<String, RegistryError> {
+        let path = match vault {
+            Some(vault) => self.resolve(vault),
+            None => self.fallback_path()?,
+        };
+
+        path.into_os_string()
+            .into_string()
+            .map_err(|_| RegistryError::InvalidPath)
+    }
+
+    /// Name of the vault `database_url` would pick, if it is a registered one.
+    pub fn vault_name(&self, vault: Option<&str>) -> Option<String> {
+        let name = match vault {
+            Some(vault) => Some(vault.to_string()),
+            None => env::var(DATABASE_ENV).ok().or(self.default.clone()),
+        };
+
+        name.filter(|name| self.vaults.contains_key(name))
+    }
+
+    fn resolve(&self, vault: &str) -> PathBuf {
+        self.vaults.get(vault)
+            .cloned()
+            .unwrap_or_else(|| PathBuf::from(vault))
+    }
+
+    fn fallback_path(&self) -> Result<PathBuf, RegistryError> {
+        if let Ok(path) = env::var(DATABASE_ENV) {
+            return Ok(self.resolve(&path))
+        }
+
+        if let Some(path) = self.default.as_ref().and_then(|name| self.vaults.get(name)) {
+            return Ok(path.clone())
+        }
+
+        if cfg!(debug_assertions) {
+            dotenv().ok();
+
+            if let Ok(path) = env::var("DATABASE_URL") {
+                return Ok(PathBuf::from(path))
+            }
+        }
+
+        let path = Self::xdg()?
+            .place_data_file(DATABASE_FILE)?;
+
+        Ok(path)
+    }
+
+    fn registry_path() -> Result<PathBuf, RegistryError> {
+        let path = Self::xdg()?
+            .place_config_file(REGISTRY_FILE)?;
+
+        Ok(path)
+    }
+
+    fn xdg() -> Result<xdg::BaseDirectories, RegistryError> {
+        xdg::BaseDirectories::with_prefix("spark")
+            .map_err(|_| RegistryError::CannotOpenXdgDirectory)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file() -> PathBuf {
+        env::temp_dir().join(format!("spark-vaults-{:016x}.toml", rand::random::<u64>()))
+    }
+
+    #[test]
+    fn registered_vaults_survive_saving_and_resolve_by_name() {
+        let file = temp_file();
+        let mut registry = VaultRegistry::default();
+        registry.add("work", Path::new("/data/work.db")).unwrap();
+        registry.add("notes", Path::new("notes.db")).unwrap();
+        registry.set_default("work").unwrap();
+        registry.save_to(&file).unwrap();
+
+        let loaded = VaultRegistry::load_from(&file);
+        fs::remove_file(&file).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.default.as_deref(), Some("work"));
+        assert_eq!(loaded.database_url(Some("work")).unwrap(), "/data/work.db");
+        assert_eq!(loaded.vaults["notes"], env::current_dir().unwrap().join("notes.db"));
+        assert_eq!(loaded.vault_name(Some("notes")).as_deref(), Some("notes"));
+    }
+
+    #[test]
+    fn missing_registry_file_is_empty() {
+        let registry = VaultRegistry::load_from(&temp_file()).unwrap();
+
+        assert!(registry.vaults.is_empty());
+        assert!(registry.default.is_none());
+    }
+
+    #[test]
+    fn names_are_registered_once() {
+        let mut registry = VaultRegistry::default();
+        registry.add("work", Path::new("/data/work.db")).unwrap();
+
+        let duplicate = registry.add("work", Path::new("/data/other.db"));
+
+        assert!(matches!(duplicate, Err(RegistryError::VaultExists(name)) if name == "work"));
+        assert_eq!(registry.vaults["work"], PathBuf::from("/data/work.db"));
+    }
+
+    #[test]
+    fn unknown_vaults_are_paths() {
+        let mut registry = VaultRegistry::default();
+
+        assert!(matches!(registry.remove("work"), Err(RegistryError::VaultNotFound(name)) if name == "work"));
+        assert!(matches!(registry.set_default("work"), Err(RegistryError::VaultNotFound(_))));
+        assert_eq!(registry.database_url(Some("other.db")).unwrap(), "other.db");
+        assert_eq!(registry.vault_name(Some("other.db")), None);
+    }
+
+    #[test]
+    fn removing_the_default_vault_clears_the_default() {
+        let mut registry = VaultRegistry::default();
+        registry.add("work", Path::new("/data/work.db")).unwrap();
+        registry.set_default("work").unwrap();
+
+        assert_eq!(registry.remove("work").unwrap(), PathBuf::from("/data/work.db"));
+        assert!(registry.default.is_none());
+    }
+}
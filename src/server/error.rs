@@ -0,0 +1,14 @@
+#[derive(thiserror::Error, Debug)]
+pub enum ServerError {
+    #[error("Cannot listen on {0}: {1}")]
+    CannotBind(String, String),
+
+    #[error(transparent)]
+    Generic(#[from] anyhow::Error)
+}
+
+impl From<std::io::Error> for ServerError {
+    fn from(value: std::io::Error) -> Self {
+        ServerError::Generic(value.into())
+    }
+}
@@ -0,0 +1,242 @@
+use std::io::Cursor;
+
+use serde::{Deserialize, Serialize};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{util::NoteFromMd, vault::{error::VaultError, Vault}};
+
+use self::error::ServerError;
+
+pub mod error;
+
+/// Body of `PUT /notes/{id}`: the note as `NoteFromMd` plus the revision the
+/// edit is based on.
+#[derive(Debug, Deserialize)]
+struct UpdateNote {
+    #[serde(flatten)]
+    note: NoteFromMd,
+    revision: i64,
+}
+
+/// Error answered to a request, rendered as `{"error": message}`.
+struct ApiError {
+    status: u16,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: u16, message: impl ToString) -> Self {
+        Self { status, message: message.to_string() }
+    }
+}
+
+impl From<VaultError> for ApiError {
+    fn from(value: VaultError) -> Self {
+        let status = match value {
+            VaultError::NoteNotFound | VaultError::SourceNotFound => 404,
+            VaultError::RevisionMismatch(..) | VaultError::TitleTaken(_) | VaultError::SequenceTaken(_) => 409,
+            VaultError::NoteTitleEmpty | VaultError::InvalidId(_) | VaultError::InvalidReference | VaultError::ReferenceDoesNotExist(_)
+                | VaultError::AmbiguousAlias(..) | VaultError::InvalidSequence(_) => 400,
+            _ => 500
+        };
+
+        Self::new(status, value)
+    }
+}
+
+type ApiResult = Result<(u16, serde_json::Value), ApiError>;
+
+/// Serves the JSON API until the process is stopped, one request at a time.
+///
+/// Routes:
+/// - `GET /notes`, `POST /notes`
+/// - `GET /notes/{id}`, `PUT /notes/{id}`
+/// - `GET /notes/{id}/references`, `GET /notes/{id}/backlinks`
+/// - `GET /sources`, `GET /sources/{id}`, `GET /sources/{id}/notes`
+/// - `GET /search?q={query}`
+pub fn serve(vault: &mut Vault, host: &str, port: u16) -> Result<(), ServerError> {
+    let address = format!("{host}:{port}");
+    let server = Server::http(&address)
+        .map_err(|e| ServerError::CannotBind(address.clone(), e.to_string()))?;
+
+    eprintln!("Listening on http://{address}");
+
+    for mut request in server.incoming_requests() {
+        let (status, body) = match handle(vault, &mut request) {
+            Ok(response) => response,
+            Err(e) => (e.status, serde_json::json!({ "error": e.message }))
+        };
+
+        eprintln!("{} {} {status}", request.method(), request.url());
+        request.respond(json(status, &body))?;
+    }
+
+    Ok(())
+}
+
+fn handle(vault: &mut Vault, request: &mut Request) -> ApiResult {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    let segments: Vec<String> = path.split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(decode)
+        .collect();
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+
+    match (request.method(), segments.as_slice()) {
+        (Method::Get, ["notes"]) => ok(vault.list_notes()?),
+        (Method::Post, ["notes"]) => {
+            let note_from_md: NoteFromMd = body(request)?;
+            let command = format!("serve add {}", note_from_md.title);
+            Ok((201, to_value(vault.record(&command, &[], &[], |vault| vault.add_note(note_from_md))?)?))
+        },
+        (Method::Get, ["notes", id]) => ok(vault.get_note(id)?),
+        (Method::Put, ["notes", id]) => {
+            let UpdateNote { mut note, revision } = body(request)?;
+            note.id = Some(id.to_string());
+            let command = format!("serve update {}", note.title);
+            ok(vault.record(&command, &[id.to_string()], &[], |vault| vault.update_note_at_revision(note, revision))?)
+        },
+        (Method::Get, ["notes", id, "references"]) => ok(vault.references_of(id)?),
+        (Method::Get, ["notes", id, "backlinks"]) => ok(vault.backlinks_of(id)?),
+        (Method::Get, ["sources"]) => ok(vault.list_sources()?),
+        (Method::Get, ["sources", id]) => ok(vault.get_source(id)?),
+        (Method::Get, ["sources", id, "notes"]) => {
+            vault.get_source(id)?;
+            ok(vault.citing_notes(id)?)
+        },
+        (Method::Get, ["search"]) => {
+            let query = query.split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(key, _)| *key == "q")
+                .map(|(_, value)| decode_query(value))
+                .ok_or(ApiError::new(400, "Missing query parameter q"))?;
+            ok(vault.search(&query)?)
+        },
+        (_, ["notes"] | ["notes", _] | ["notes", _, "references" | "backlinks"]
+            | ["sources"] | ["sources", _] | ["sources", _, "notes"] | ["search"]) => Err(ApiError::new(405, "Method not allowed")),
+        _ => Err(ApiError::new(404, "Not found"))
+    }
+}
+
+fn ok(value: impl Serialize) -> ApiResult {
+    Ok((200, to_value(value)?))
+}
+
+fn to_value(value: impl Serialize) -> Result<serde_json::Value, ApiError> {
+    serde_json::to_value(value).map_err(|e| ApiError::new(500, e))
+}
+
+fn body<T: for<'de> Deserialize<'de>>(request: &mut Request) -> Result<T, ApiError> {
+    let mut text = String::new();
+    request.as_reader().read_to_string(&mut text)
+        .map_err(|e| ApiError::new(400, e))?;
+
+    serde_json::from_str(&text).map_err(|e| ApiError::new(400, e))
+}
+
+fn json(status: u16, body: &serde_json::Value) -> Response<Cursor<Vec<u8>>> {
+    let header = Header::from_bytes("Content-Type", "application/json")
+        .expect("Static header is valid");
+
+    Response::from_data(body.to_string())
+        .with_status_code(status)
+        .with_header(header)
+}
+
+/// Decodes `+` as a space, which only query strings use, and `%XX` escapes.
+fn decode_query(text: &str) -> String {
+    decode(&text.replace('+', " "))
+}
+
+/// Decodes `%XX` escapes of urls.
+fn decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                let byte = std::str::from_utf8(&bytes[i + 1..i + 3]).ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                match byte {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    },
+                    None => decoded.push(b'%')
+                }
+            },
+            byte => decoded.push(byte)
+        }
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use tiny_http::TestRequest;
+
+    use super::*;
+
+    fn request(vault: &mut Vault, method: Method, path: &str, body: &'static str) -> (u16, serde_json::Value) {
+        let mut request = TestRequest::new().with_method(method).with_path(path).with_body(body).into();
+
+        match handle(vault, &mut request) {
+            Ok(response) => response,
+            Err(e) => (e.status, serde_json::json!({ "error": e.message })),
+        }
+    }
+
+    #[test]
+    fn post_refuses_ids_which_are_not_ids() {
+        let mut vault = Vault::in_memory();
+
+        let (status, body) = request(&mut vault, Method::Post, "/notes", r#"{"id": "../../x", "title": "Escape"}"#);
+
+        assert_eq!(status, 400);
+        assert_eq!(body["error"], "Invalid note id: ../../x");
+        assert!(vault.list_notes().unwrap().is_empty());
+    }
+
+    #[test]
+    fn post_adds_and_put_updates_at_revision() {
+        let mut vault = Vault::in_memory();
+
+        let (status, note) = request(&mut vault, Method::Post, "/notes", r#"{"id": "ABC234", "title": "A", "contents": "v1"}"#);
+        assert_eq!(status, 201);
+        assert_eq!(note["id"], "ABC234");
+
+        let (status, _) = request(&mut vault, Method::Put, "/notes/ABC234", r#"{"title": "A", "contents": "v2", "revision": 0}"#);
+        assert_eq!(status, 200);
+        let (status, _) = request(&mut vault, Method::Put, "/notes/ABC234", r#"{"title": "A", "contents": "v3", "revision": 0}"#);
+        assert_eq!(status, 409);
+        assert_eq!(vault.get_note("ABC234").unwrap().contents, "v2");
+    }
+
+    #[test]
+    fn unknown_routes_and_methods_are_refused() {
+        let mut vault = Vault::in_memory();
+
+        assert_eq!(request(&mut vault, Method::Get, "/notes/ABC234/x/y", "").0, 404);
+        assert_eq!(request(&mut vault, Method::Delete, "/notes/ABC234", "").0, 405);
+        assert_eq!(request(&mut vault, Method::Get, "/search", "").0, 400);
+    }
+
+    #[test]
+    fn decode_keeps_plus_signs_of_paths() {
+        assert_eq!(decode("C++%20notes"), "C++ notes");
+        assert_eq!(decode("caf%C3%A9"), "café");
+        assert_eq!(decode("100%"), "100%");
+        assert_eq!(decode("%zz"), "%zz");
+    }
+
+    #[test]
+    fn decode_query_reads_plus_signs_as_spaces() {
+        assert_eq!(decode_query("about+a"), "about a");
+        assert_eq!(decode_query("C%2B%2B"), "C++");
+    }
+}
@@ -0,0 +1,376 @@
+use crate::models::{aliases::Alias, error::{DbError, FOREIGN_KEY}, external::ExternalReference, internal::InternalReference, note::{Note, NoteListItem}, operation::Operation, sources::Source};
+
+use crate::util::title::normalize;
+
+use super::Storage;
+
+/// Storage keeping the whole vault in memory, nothing is written to disk.
+#[derive(Default)]
+pub struct MemoryStorage {
+    state: MemoryState,
+    snapshots: Vec<MemoryState>,
+}
+
+#[derive(Default, Clone)]
+struct MemoryState {
+    notes: Vec<Note>,
+    sources: Vec<Source>,
+    aliases: Vec<Alias>,
+    internal: Vec<InternalReference>,
+    external: Vec<ExternalReference>,
+    operations: Vec<Operation>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn find_note(&self, id: &str) -> Option<&Note> {
+        self.state.notes.iter().find(|note| note.id == id)
+    }
+
+    fn find_source(&self, id: &str) -> Option<&Source> {
+        self.state.sources.iter().find(|source| source.id == id)
+    }
+
+    fn same_title(a: &str, b: &str) -> bool {
+        normalize(a) == normalize(b)
+    }
+
+    fn same_sequence(a: &Note, b: &Note) -> bool {
+        a.sequence.is_some() && a.sequence == b.sequence
+    }
+
+    fn sorted_notes<'a>(&self, ids: impl Iterator<Item = &'a String>) -> Result<Vec<Note>, DbError> {
+        let mut notes = vec![];
+        for id in ids {
+            let note = self.find_note(id)
+                .ok_or(DbError::InternalError)?;
+            notes.push(note.clone());
+        }
+
+        notes.sort_by(|a, b| a.title.cmp(&b.title));
+        Ok(notes)
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn begin(&mut self) -> Result<(), DbError> {
+        self.snapshots.push(self.state.clone());
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<(), DbError> {
+        self.snapshots.pop()
+            .ok_or(DbError::InternalError)?;
+        Ok(())
+    }
+
+    fn rollback(&mut self) -> Result<(), DbError> {
+        self.state = self.snapshots.pop()
+            .ok_or(DbError::InternalError)?;
+        Ok(())
+    }
+
+    fn id_taken(&self, id: &str) -> Result<bool, DbError> {
+        let taken = self.find_note(id).is_some()
+            || self.find_source(id).is_some()
+            || self.state.aliases.iter().any(|a| a.id == id)
+            || self.state.internal.iter().any(|r| r.id == id)
+            || self.state.external.iter().any(|r| r.id == id);
+
+        Ok(taken)
+    }
+
+    fn add_note(&mut self, note: &Note) -> Result<(), DbError> {
+        if self.state.notes.iter().any(|n| n.id == note.id || Self::same_title(&n.title, &note.title) || Self::same_sequence(n, note)) {
+            return Err(DbError::ConstraintViolation("notes".to_string()))
+        }
+
+        self.state.notes.push(note.clone());
+        Ok(())
+    }
+
+    fn update_note(&mut self, note: &Note) -> Result<(), DbError> {
+        if self.state.notes.iter().any(|n| n.id != note.id && (Self::same_title(&n.title, &note.title) || Self::same_sequence(n, note))) {
+            return Err(DbError::ConstraintViolation("notes".to_string()))
+        }
+
+        if let Some(existing) = self.state.notes.iter_mut().find(|n| n.id == note.id) {
+            *existing = Note { revision: existing.revision + 1, ..note.clone() };
+        }
+
+        Ok(())
+    }
+
+    fn get_note(&self, id: &str) -> Result<Option<Note>, DbError> {
+        Ok(self.find_note(id).cloned())
+    }
+
+    fn get_note_by_title(&self, title: &str) -> Result<Option<Note>, DbError> {
+        Ok(self.state.notes.iter().find(|note| Self::same_title(&note.title, title)).cloned())
+    }
+
+    fn get_note_by_sequence(&self, sequence: &str) -> Result<Option<Note>, DbError> {
+        Ok(self.state.notes.iter().find(|note| note.sequence.as_deref() == Some(sequence)).cloned())
+    }
+
+    fn list_notes(&self) -> Result<Vec<NoteListItem>, DbError> {
+        let notes = self.state.notes.iter()
+            .map(|note| NoteListItem { id: note.id.clone(), title: note.title.clone(), sequence: note.sequence.clone() })
+            .collect();
+
+        Ok(notes)
+    }
+
+    fn delete_note(&mut self, id: &str) -> Result<(), DbError> {
+        let referenced = self.state.internal.iter().any(|r| r.note_id == id || r.reference_id == id)
+            || self.state.external.iter().any(|r| r.note_id == id)
+            || self.state.aliases.iter().any(|a| a.note_id == id);
+        if referenced {
+            return Err(DbError::ConstraintViolation(FOREIGN_KEY.to_string()))
+        }
+
+        self.state.notes.retain(|note| note.id != id);
+        Ok(())
+    }
+
+    fn search_notes(&self, query: &str) -> Result<Vec<NoteListItem>, DbError> {
+        let query = normalize(query);
+        let matches = |text: &str| normalize(text).contains(&query);
+
+        let notes = self.state.notes.iter()
+            .filter(|note| matches(&note.title)
+                || matches(&note.contents)
+                || self.state.aliases.iter().any(|a| a.note_id == note.id && matches(&a.alias)))
+            .map(|note| NoteListItem { id: note.id.clone(), title: note.title.clone(), sequence: note.sequence.clone() })
+            .collect();
+
+        Ok(notes)
+    }
+
+    fn add_alias(&mut self, alias: &Alias) -> Result<(), DbError> {
+        if self.find_note(&alias.note_id).is_none() {
+            return Err(DbError::ConstraintViolation(FOREIGN_KEY.to_string()))
+        }
+        if self.state.aliases.iter().any(|a| a.id == alias.id || (a.note_id == alias.note_id && a.alias == alias.alias)) {
+            return Err(DbError::ConstraintViolation("aliases".to_string()))
+        }
+
+        self.state.aliases.push(alias.clone());
+        Ok(())
+    }
+
+    fn aliases_of(&self, note_id: &str) -> Result<Vec<String>, DbError> {
+        let mut aliases: Vec<String> = self.state.aliases.iter()
+            .filter(|a| a.note_id == note_id)
+            .map(|a| a.alias.clone())
+            .collect();
+
+        aliases.sort();
+        Ok(aliases)
+    }
+
+    fn notes_with_alias(&self, alias: &str) -> Result<Vec<Note>, DbError> {
+        let mut ids: Vec<&String> = self.state.aliases.iter()
+            .filter(|a| Self::same_title(&a.alias, alias))
+            .map(|a| &a.note_id)
+            .collect();
+        ids.dedup();
+
+        self.sorted_notes(ids.into_iter())
+    }
+
+    fn delete_aliases_of(&mut self, note_id: &str) -> Result<(), DbError> {
+        self.state.aliases.retain(|a| a.note_id != note_id);
+        Ok(())
+    }
+
+    fn add_source(&mut self, source: &Source) -> Result<(), DbError> {
+        if self.state.sources.iter().any(|s| s.id == source.id || Self::same_title(&s.title, &source.title)) {
+            return Err(DbError::ConstraintViolation("sources".to_string()))
+        }
+
+        self.state.sources.push(source.clone());
+        Ok(())
+    }
+
+    fn update_source(&mut self, source: &Source) -> Result<(), DbError> {
+        if self.state.sources.iter().any(|s| s.id != source.id && Self::same_title(&s.title, &source.title)) {
+            return Err(DbError::ConstraintViolation("sources".to_string()))
+        }
+
+        if let Some(existing) = self.state.sources.iter_mut().find(|s| s.id == source.id) {
+            *existing = source.clone();
+        }
+
+        Ok(())
+    }
+
+    fn get_source(&self, id: &str) -> Result<Option<Source>, DbError> {
+        Ok(self.find_source(id).cloned())
+    }
+
+    fn get_source_by_title(&self, title: &str) -> Result<Option<Source>, DbError> {
+        Ok(self.state.sources.iter().find(|source| Self::same_title(&source.title, title)).cloned())
+    }
+
+    fn list_sources(&self) -> Result<Vec<Source>, DbError> {
+        Ok(self.state.sources.clone())
+    }
+
+    fn delete_source(&mut self, id: &str) -> Result<(), DbError> {
+        if self.state.external.iter().any(|r| r.reference_id == id) {
+            return Err(DbError::ConstraintViolation(FOREIGN_KEY.to_string()))
+        }
+
+        self.state.sources.retain(|source| source.id != id);
+        Ok(())
+    }
+
+    fn add_internal_reference(&mut self, reference: &InternalReference) -> Result<(), DbError> {
+        if self.find_note(&reference.note_id).is_none() || self.find_note(&reference.reference_id).is_none() {
+            return Err(DbError::ConstraintViolation(FOREIGN_KEY.to_string()))
+        }
+        if self.state.internal.iter().any(|r| r.id == reference.id) {
+            return Err(DbError::ConstraintViolation("internal_references".to_string()))
+        }
+
+        self.state.internal.push(reference.clone());
+        Ok(())
+    }
+
+    fn internal_reference_exists(&self, note_id: &str, reference_id: &str) -> Result<bool, DbError> {
+        let exists = self.state.internal.iter()
+            .any(|r| r.note_id == note_id && r.reference_id == reference_id);
+
+        Ok(exists)
+    }
+
+    fn internal_references_of(&self, note_id: &str) -> Result<Vec<Note>, DbError> {
+        let ids = self.state.internal.iter()
+            .filter(|r| r.note_id == note_id)
+            .map(|r| &r.reference_id);
+
+        self.sorted_notes(ids)
+    }
+
+    fn backlinks_of(&self, note_id: &str) -> Result<Vec<Note>, DbError> {
+        let ids = self.state.internal.iter()
+            .filter(|r| r.reference_id == note_id)
+            .map(|r| &r.note_id);
+
+        self.sorted_notes(ids)
+    }
+
+    fn list_internal_references(&self) -> Result<Vec<InternalReference>, DbError> {
+        Ok(self.state.internal.clone())
+    }
+
+    fn delete_internal_references_of(&mut self, note_id: &str) -> Result<(), DbError> {
+        self.state.internal.retain(|r| r.note_id != note_id);
+        Ok(())
+    }
+
+    fn delete_internal_references_to(&mut self, note_id: &str) -> Result<(), DbError> {
+        self.state.internal.retain(|r| r.reference_id != note_id);
+        Ok(())
+    }
+
+    fn add_external_reference(&mut self, reference: &ExternalReference) -> Result<(), DbError> {
+        if self.find_note(&reference.note_id).is_none() || self.find_source(&reference.reference_id).is_none() {
+            return Err(DbError::ConstraintViolation(FOREIGN_KEY.to_string()))
+        }
+        if self.state.external.iter().any(|r| r.id == reference.id) {
+            return Err(DbError::ConstraintViolation("external_references".to_string()))
+        }
+
+        self.state.external.push(reference.clone());
+        Ok(())
+    }
+
+    fn external_reference_exists(&self, note_id: &str, source_id: &str) -> Result<bool, DbError> {
+        let exists = self.state.external.iter()
+            .any(|r| r.note_id == note_id && r.reference_id == source_id);
+
+        Ok(exists)
+    }
+
+    fn external_references_of(&self, note_id: &str) -> Result<Vec<Source>, DbError> {
+        let mut sources = vec![];
+        for reference in self.state.external.iter().filter(|r| r.note_id == note_id) {
+            let source = self.find_source(&reference.reference_id)
+                .ok_or(DbError::InternalError)?;
+            sources.push(source.clone());
+        }
+
+        sources.sort_by(|a, b| a.title.cmp(&b.title));
+        Ok(sources)
+    }
+
+    fn list_external_references(&self) -> Result<Vec<ExternalReference>, DbError> {
+        Ok(self.state.external.clone())
+    }
+
+    fn delete_external_references_of(&mut self, note_id: &str) -> Result<(), DbError> {
+        self.state.external.retain(|r| r.note_id != note_id);
+        Ok(())
+    }
+
+    fn notes_citing(&self, source_id: &str) -> Result<Vec<Note>, DbError> {
+        let ids = self.state.external.iter()
+            .filter(|r| r.reference_id == source_id)
+            .map(|r| &r.note_id);
+
+        self.sorted_notes(ids)
+    }
+
+    fn delete_external_references_to(&mut self, source_id: &str) -> Result<(), DbError> {
+        self.state.external.retain(|r| r.reference_id != source_id);
+        Ok(())
+    }
+
+    fn add_operation(&mut self, command: &str, before: &str, after: &str) -> Result<i64, DbError> {
+        let id = self.state.operations.last().map_or(1, |operation| operation.id + 1);
+        self.state.operations.push(Operation {
+            id,
+            command: command.to_string(),
+            created_at: String::new(),
+            undone: false,
+            before: before.to_string(),
+            after: after.to_string(),
+        });
+
+        Ok(id)
+    }
+
+    fn list_operations(&self, limit: usize) -> Result<Vec<Operation>, DbError> {
+        Ok(self.state.operations.iter().rev().take(limit).cloned().collect())
+    }
+
+    fn last_done_operation(&self) -> Result<Option<Operation>, DbError> {
+        Ok(self.state.operations.iter().rev().find(|operation| !operation.undone).cloned())
+    }
+
+    fn first_undone_operation(&self) -> Result<Option<Operation>, DbError> {
+        Ok(self.state.operations.iter().find(|operation| operation.undone).cloned())
+    }
+
+    fn set_operation_undone(&mut self, id: i64, undone: bool) -> Result<(), DbError> {
+        self.state.operations.iter_mut()
+            .filter(|operation| operation.id == id)
+            .for_each(|operation| operation.undone = undone);
+        Ok(())
+    }
+
+    fn delete_undone_operations(&mut self) -> Result<(), DbError> {
+        self.state.operations.retain(|operation| !operation.undone);
+        Ok(())
+    }
+
+    fn enforce_unique_titles(&mut self) -> Result<(), DbError> {
+        // `add_note`, `update_note` and friends always check normalised titles.
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+use crate::models::{aliases::Alias, error::DbError, external::ExternalReference, internal::InternalReference, note::{Note, NoteListItem}, operation::Operation, sources::Source};
+
+pub mod sqlite;
+pub mod memory;
+
+/// Persistence layer used by `Vault`.
+///
+/// `begin`, `commit` and `rollback` may nest, every `begin` is paired with
+/// exactly one `commit` or `rollback`.
+pub trait Storage {
+    fn begin(&mut self) -> Result<(), DbError>;
+    fn commit(&mut self) -> Result<(), DbError>;
+    fn rollback(&mut self) -> Result<(), DbError>;
+
+    /// Whether any note, source or reference already uses the id.
+    fn id_taken(&self, id: &str) -> Result<bool, DbError>;
+
+    fn add_note(&mut self, note: &Note) -> Result<(), DbError>;
+    fn update_note(&mut self, note: &Note) -> Result<(), DbError>;
+    fn get_note(&self, id: &str) -> Result<Option<Note>, DbError>;
+    /// Note whose normalised title matches, see `util::title::normalize`.
+    fn get_note_by_title(&self, title: &str) -> Result<Option<Note>, DbError>;
+    fn get_note_by_sequence(&self, sequence: &str) -> Result<Option<Note>, DbError>;
+    fn list_notes(&self) -> Result<Vec<NoteListItem>, DbError>;
+    /// Deletes only the note row, references and aliases must be gone already.
+    fn delete_note(&mut self, id: &str) -> Result<(), DbError>;
+    /// Notes whose title, alias or contents contain `query`, ignoring case.
+    fn search_notes(&self, query: &str) -> Result<Vec<NoteListItem>, DbError>;
+
+    fn add_alias(&mut self, alias: &Alias) -> Result<(), DbError>;
+    /// Aliases of the note, sorted.
+    fn aliases_of(&self, note_id: &str) -> Result<Vec<String>, DbError>;
+    /// Notes with a matching normalised alias.
+    fn notes_with_alias(&self, alias: &str) -> Result<Vec<Note>, DbError>;
+    fn delete_aliases_of(&mut self, note_id: &str) -> Result<(), DbError>;
+
+    fn add_source(&mut self, source: &Source) -> Result<(), DbError>;
+    fn update_source(&mut self, source: &Source) -> Result<(), DbError>;
+    fn get_source(&self, id: &str) -> Result<Option<Source>, DbError>;
+    /// Source whose normalised title matches, see `util::title::normalize`.
+    fn get_source_by_title(&self, title: &str) -> Result<Option<Source>, DbError>;
+    fn list_sources(&self) -> Result<Vec<Source>, DbError>;
+    /// Deletes only the source row, references must be gone already.
+    fn delete_source(&mut self, id: &str) -> Result<(), DbError>;
+
+    fn add_internal_reference(&mut self, reference: &InternalReference) -> Result<(), DbError>;
+    fn internal_reference_exists(&self, note_id: &str, reference_id: &str) -> Result<bool, DbError>;
+    /// Notes referenced by the note, sorted by title.
+    fn internal_references_of(&self, note_id: &str) -> Result<Vec<Note>, DbError>;
+    /// Notes referencing the note, sorted by title.
+    fn backlinks_of(&self, note_id: &str) -> Result<Vec<Note>, DbError>;
+    /// Every internal reference in the vault, in no particular order.
+    fn list_internal_references(&self) -> Result<Vec<InternalReference>, DbError>;
+    fn delete_internal_references_of(&mut self, note_id: &str) -> Result<(), DbError>;
+    fn delete_internal_references_to(&mut self, note_id: &str) -> Result<(), DbError>;
+
+    fn add_external_reference(&mut self, reference: &ExternalReference) -> Result<(), DbError>;
+    fn external_reference_exists(&self, note_id: &str, source_id: &str) -> Result<bool, DbError>;
+    /// Sources cited by the note, sorted by title.
+    fn external_references_of(&self, note_id: &str) -> Result<Vec<Source>, DbError>;
+    /// Notes citing the source, sorted by title.
+    fn notes_citing(&self, source_id: &str) -> Result<Vec<Note>, DbError>;
+    /// Every external reference in the vault, in no particular order.
+    fn list_external_references(&self) -> Result<Vec<ExternalReference>, DbError>;
+    fn delete_external_references_of(&mut self, note_id: &str) -> Result<(), DbError>;
+    fn delete_external_references_to(&mut self, source_id: &str) -> Result<(), DbError>;
+
+    /// Logs an operation and returns its id.
+    fn add_operation(&mut self, command: &str, before: &str, after: &str) -> Result<i64, DbError>;
+    /// The `limit` most recent operations, newest first.
+    fn list_operations(&self, limit: usize) -> Result<Vec<Operation>, DbError>;
+    /// Newest operation which is not undone.
+    fn last_done_operation(&self) -> Result<Option<Operation>, DbError>;
+    /// Oldest undone operation, the next one to redo.
+    fn first_undone_operation(&self) -> Result<Option<Operation>, DbError>;
+    fn set_operation_undone(&mut self, id: i64, undone: bool) -> Result<(), DbError>;
+    /// Forgets undone operations, once a new one makes redoing them impossible.
+    fn delete_undone_operations(&mut self) -> Result<(), DbError>;
+
+    /// Enforces unique normalised titles of notes and sources, fails while
+    /// duplicates remain.
+    fn enforce_unique_titles(&mut self) -> Result<(), DbError>;
+}
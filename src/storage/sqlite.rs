@@ -0,0 +1,204 @@
+use rusqlite::Connection;
+
+use crate::models::{aliases::Alias, error::DbError, external::ExternalReference, internal::InternalReference, note::{Note, NoteListItem}, operation::Operation, sources::Source};
+
+use crate::init_db::create_title_indexes;
+
+use super::Storage;
+
+/// Default storage, backed by a SQLite database created by `init_db::setup_database`.
+pub struct SqliteStorage {
+    conn: Connection,
+}
+
+impl SqliteStorage {
+    pub fn new(conn: Connection) -> Self {
+        Self { conn }
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn begin(&mut self) -> Result<(), DbError> {
+        self.conn.execute_batch("SAVEPOINT spark")?;
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<(), DbError> {
+        self.conn.execute_batch("RELEASE spark")?;
+        Ok(())
+    }
+
+    fn rollback(&mut self) -> Result<(), DbError> {
+        self.conn.execute_batch("ROLLBACK TO spark; RELEASE spark")?;
+        Ok(())
+    }
+
+    fn id_taken(&self, id: &str) -> Result<bool, DbError> {
+        let mut statement = self.conn.prepare("
+            select id from notes where id = ?1
+            union all select id from sources where id = ?1
+            union all select id from internal_references where id = ?1
+            union all select id from external_references where id = ?1
+            union all select id from aliases where id = ?1")?;
+        let taken = statement.exists([id])?;
+
+        Ok(taken)
+    }
+
+    fn add_note(&mut self, note: &Note) -> Result<(), DbError> {
+        note.add(&self.conn)
+    }
+
+    fn update_note(&mut self, note: &Note) -> Result<(), DbError> {
+        note.update(&self.conn)
+    }
+
+    fn get_note(&self, id: &str) -> Result<Option<Note>, DbError> {
+        Note::get_by_id(id.to_string(), &self.conn)
+    }
+
+    fn get_note_by_title(&self, title: &str) -> Result<Option<Note>, DbError> {
+        Note::get_by_title(title.to_string(), &self.conn)
+    }
+
+    fn get_note_by_sequence(&self, sequence: &str) -> Result<Option<Note>, DbError> {
+        Note::get_by_sequence(sequence.to_string(), &self.conn)
+    }
+
+    fn list_notes(&self) -> Result<Vec<NoteListItem>, DbError> {
+        Note::list(&self.conn)
+    }
+
+    fn delete_note(&mut self, id: &str) -> Result<(), DbError> {
+        Note::delete(id, &self.conn)
+    }
+
+    fn search_notes(&self, query: &str) -> Result<Vec<NoteListItem>, DbError> {
+        Note::search(query, &self.conn)
+    }
+
+    fn add_alias(&mut self, alias: &Alias) -> Result<(), DbError> {
+        alias.add(&self.conn)
+    }
+
+    fn aliases_of(&self, note_id: &str) -> Result<Vec<String>, DbError> {
+        Alias::get_by_note_id(note_id, &self.conn)
+    }
+
+    fn notes_with_alias(&self, alias: &str) -> Result<Vec<Note>, DbError> {
+        Alias::get_notes_by_alias(alias, &self.conn)
+    }
+
+    fn delete_aliases_of(&mut self, note_id: &str) -> Result<(), DbError> {
+        Alias::delete_by_note_id(note_id, &self.conn)
+    }
+
+    fn add_source(&mut self, source: &Source) -> Result<(), DbError> {
+        source.add(&self.conn)
+    }
+
+    fn update_source(&mut self, source: &Source) -> Result<(), DbError> {
+        source.update(&self.conn)
+    }
+
+    fn get_source(&self, id: &str) -> Result<Option<Source>, DbError> {
+        Source::get_by_id(id.to_string(), &self.conn)
+    }
+
+    fn get_source_by_title(&self, title: &str) -> Result<Option<Source>, DbError> {
+        Source::get_by_title(title.to_string(), &self.conn)
+    }
+
+    fn list_sources(&self) -> Result<Vec<Source>, DbError> {
+        Source::list(&self.conn)
+    }
+
+    fn delete_source(&mut self, id: &str) -> Result<(), DbError> {
+        Source::delete(id, &self.conn)
+    }
+
+    fn add_internal_reference(&mut self, reference: &InternalReference) -> Result<(), DbError> {
+        reference.add(&self.conn)
+    }
+
+    fn internal_reference_exists(&self, note_id: &str, reference_id: &str) -> Result<bool, DbError> {
+        InternalReference::exists(note_id, reference_id, &self.conn)
+    }
+
+    fn internal_references_of(&self, note_id: &str) -> Result<Vec<Note>, DbError> {
+        InternalReference::get_by_note_id(note_id, &self.conn)
+    }
+
+    fn backlinks_of(&self, note_id: &str) -> Result<Vec<Note>, DbError> {
+        InternalReference::get_by_reference_id(note_id, &self.conn)
+    }
+
+    fn list_internal_references(&self) -> Result<Vec<InternalReference>, DbError> {
+        InternalReference::list(&self.conn)
+    }
+
+    fn delete_internal_references_of(&mut self, note_id: &str) -> Result<(), DbError> {
+        InternalReference::delete_by_note_id(note_id, &self.conn)
+    }
+
+    fn delete_internal_references_to(&mut self, note_id: &str) -> Result<(), DbError> {
+        InternalReference::delete_by_reference_id(note_id, &self.conn)
+    }
+
+    fn add_external_reference(&mut self, reference: &ExternalReference) -> Result<(), DbError> {
+        reference.add(&self.conn)
+    }
+
+    fn external_reference_exists(&self, note_id: &str, source_id: &str) -> Result<bool, DbError> {
+        ExternalReference::exists(note_id, source_id, &self.conn)
+    }
+
+    fn external_references_of(&self, note_id: &str) -> Result<Vec<Source>, DbError> {
+        ExternalReference::get_by_note_id(note_id, &self.conn)
+    }
+
+    fn list_external_references(&self) -> Result<Vec<ExternalReference>, DbError> {
+        ExternalReference::list(&self.conn)
+    }
+
+    fn delete_external_references_of(&mut self, note_id: &str) -> Result<(), DbError> {
+        ExternalReference::delete_by_note_id(note_id, &self.conn)
+    }
+
+    fn notes_citing(&self, source_id: &str) -> Result<Vec<Note>, DbError> {
+        ExternalReference::get_by_reference_id(source_id, &self.conn)
+    }
+
+    fn delete_external_references_to(&mut self, source_id: &str) -> Result<(), DbError> {
+        ExternalReference::delete_by_reference_id(source_id, &self.conn)
+    }
+
+    fn add_operation(&mut self, command: &str, before: &str, after: &str) -> Result<i64, DbError> {
+        Operation::add(command, before, after, &self.conn)
+    }
+
+    fn list_operations(&self, limit: usize) -> Result<Vec<Operation>, DbError> {
+        Operation::list(limit, &self.conn)
+    }
+
+    fn last_done_operation(&self) -> Result<Option<Operation>, DbError> {
+        Operation::last_done(&self.conn)
+    }
+
+    fn first_undone_operation(&self) -> Result<Option<Operation>, DbError> {
+        Operation::first_undone(&self.conn)
+    }
+
+    fn set_operation_undone(&mut self, id: i64, undone: bool) -> Result<(), DbError> {
+        Operation::set_undone(id, undone, &self.conn)
+    }
+
+    fn delete_undone_operations(&mut self) -> Result<(), DbError> {
+        Operation::delete_undone(&self.conn)
+    }
+
+    fn enforce_unique_titles(&mut self) -> Result<(), DbError> {
+        create_title_indexes(&self.conn)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,19 @@
+use crate::{vault::error::VaultError, watch::error::WatchError};
+
+#[derive(thiserror::Error, Debug)]
+pub enum SyncError {
+    #[error("Cannot read sync state {0}: {1}")]
+    CannotReadState(String, String),
+
+    #[error("Cannot write {0}: {1}")]
+    CannotWrite(String, String),
+
+    #[error(transparent)]
+    Vault(#[from] VaultError),
+
+    #[error(transparent)]
+    Watch(#[from] WatchError),
+
+    #[error(transparent)]
+    Generic(#[from] anyhow::Error)
+}
@@ -0,0 +1,487 @@
+use std::{collections::{BTreeMap, BTreeSet}, fs, path::{Path, PathBuf}};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{util::{parse, NoteFromMd}, vault::{SetOutcome, Vault}, watch};
+
+use self::error::SyncError;
+
+pub mod error;
+
+/// File in the synced directory remembering, for every note, the revision and
+/// file contents both sides agreed on at the last sync.
+pub const STATE_FILE: &str = ".spark-sync.json";
+
+/// Extension of the database version written next to a conflicting file.
+const CONFLICT_EXTENSION: &str = "conflict.md";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    notes: BTreeMap<String, Entry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    /// Path of the file relative to the synced directory.
+    path: String,
+    revision: i64,
+    hash: String,
+    #[serde(default)]
+    conflict: bool,
+}
+
+/// What `sync` did with a note or file.
+#[derive(Debug, Clone)]
+pub enum Action {
+    /// The note changed in the database and was written to its file.
+    Exported,
+    /// The file changed on disk and was read into the database.
+    Imported,
+    /// The file had no note yet and was added to the database.
+    Added,
+    /// Both changed, the database version was saved to the path given.
+    Conflict(PathBuf),
+    /// A previous conflict whose database version at the path given was not
+    /// removed yet.
+    Unresolved(PathBuf),
+    /// The file was removed and the note deleted.
+    Deleted,
+    /// The note is gone from the database and its unchanged file was removed.
+    Removed,
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Synced {
+    pub path: PathBuf,
+    pub title: String,
+    pub action: Action,
+}
+
+/// Outcome of `sync` whose file changes are not made yet, so that they can
+/// wait until the database changes are committed.
+#[derive(Debug)]
+pub struct Pending {
+    dir: PathBuf,
+    state: State,
+    writes: BTreeMap<PathBuf, String>,
+    removals: BTreeSet<PathBuf>,
+    report: Vec<Synced>,
+}
+
+impl Pending {
+    /// Writes and removes the files, the sync state last, and returns the report.
+    pub fn apply(self) -> Result<Vec<Synced>, SyncError> {
+        for (path, contents) in &self.writes {
+            write(path, contents)?;
+        }
+        for path in &self.removals {
+            fs::remove_file(path)
+                .map_err(|e| SyncError::CannotWrite(path.display().to_string(), e.to_string()))?;
+        }
+        save_state(&self.dir, &self.state)?;
+
+        Ok(self.report)
+    }
+
+    fn write(&mut self, path: &Path, contents: String) {
+        self.writes.insert(path.to_path_buf(), contents);
+    }
+}
+
+/// A markdown file of the synced directory.
+struct File {
+    path: PathBuf,
+    contents: String,
+    note: NoteFromMd,
+}
+
+/// Brings the notes of the vault and the markdown files under `dir` in line,
+/// matching files to notes by the id in their `# [ID]` heading.
+///
+/// A side changed since the last sync when the note's revision or the file's
+/// contents differ from those remembered in `STATE_FILE`. Changes of one side
+/// are copied to the other, notes without a file are written to `ID.md` and
+/// files without an id are added as notes. When both changed, the database
+/// version is saved next to the file as `name.conflict.md`; deleting it
+/// resolves the conflict in favour of the file at the next sync.
+///
+/// A removed file deletes its note only with `delete` and only if the note
+/// did not change since, otherwise the file is written again.
+///
+/// Only the database is changed, the files are changed by `Pending::apply`.
+pub fn sync(vault: &mut Vault, dir: &Path, delete: bool) -> Result<Pending, SyncError> {
+    fs::create_dir_all(dir)
+        .map_err(|e| SyncError::CannotWrite(dir.display().to_string(), e.to_string()))?;
+    let mut pending = Pending {
+        dir: dir.to_path_buf(),
+        state: load_state(dir)?,
+        writes: BTreeMap::new(),
+        removals: BTreeSet::new(),
+        report: vec![],
+    };
+
+    let mut files: BTreeMap<String, File> = BTreeMap::new();
+    let mut new_files = vec![];
+    let mut unreadable = BTreeSet::new();
+    for path in watch::markdown_files(dir)? {
+        if path.to_string_lossy().ends_with(&format!(".{CONFLICT_EXTENSION}")) {
+            continue
+        }
+
+        let file = match read(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                pending.report.push(failed(&path, "", e));
+                unreadable.insert(relative(dir, &path));
+                continue
+            }
+        };
+
+        match file.note.id.clone() {
+            Some(id) if files.contains_key(&id) => {
+                let message = format!("Note [{id}] is also in {}", files[&id].path.display());
+                pending.report.push(failed(&file.path, &file.note.title, message));
+            },
+            Some(id) => {
+                files.insert(id, file);
+            },
+            None => new_files.push(file)
+        }
+    }
+
+    let ids: BTreeSet<String> = vault.list_notes()?.into_iter().map(|note| note.id)
+        .chain(files.keys().cloned())
+        .chain(pending.state.notes.keys().cloned())
+        .collect();
+
+    for id in ids {
+        let file = files.remove(&id);
+        let path = file.as_ref().map(|file| file.path.clone())
+            .or(pending.state.notes.get(&id).map(|entry| dir.join(&entry.path)))
+            .unwrap_or(dir.join(format!("{id}.md")));
+
+        match sync_note(vault, dir, &id, file, &mut pending, &unreadable, delete) {
+            Ok(Some(synced)) => pending.report.push(synced),
+            Ok(None) => { },
+            Err(SyncError::Vault(e)) => pending.report.push(failed(&path, "", e.to_string())),
+            Err(e) => return Err(e)
+        }
+    }
+
+    for file in new_files {
+        let path = file.path.clone();
+        let title = file.note.title.clone();
+
+        match import(vault, dir, file, &mut pending, false) {
+            Ok((path, title, action)) => pending.report.push(Synced { path, title, action }),
+            Err(SyncError::Vault(e)) => pending.report.push(failed(&path, &title, e.to_string())),
+            Err(e) => return Err(e)
+        }
+    }
+
+    Ok(pending)
+}
+
+/// Syncs the note with provided id and its file, either of which may be missing.
+fn sync_note(vault: &mut Vault, dir: &Path, id: &str, file: Option<File>, pending: &mut Pending, unreadable: &BTreeSet<String>, delete: bool) -> Result<Option<Synced>, SyncError> {
+    let note = vault.get_note(id).ok();
+    let entry = pending.state.notes.get(id).cloned();
+
+    let (path, title, action) = match (note, file, entry) {
+        (Some(note), None, entry) => match entry {
+            Some(entry) if unreadable.contains(&entry.path) => return Ok(None),
+            Some(entry) if delete && entry.revision == note.revision => {
+                vault.delete_note(id)?;
+                pending.state.notes.remove(id);
+                (dir.join(entry.path), note.title, Action::Deleted)
+            },
+            entry => {
+                let path = entry.map(|entry| dir.join(entry.path))
+                    .unwrap_or(dir.join(format!("{id}.md")));
+                export(vault, dir, id, &path, pending)?;
+                (path, note.title, Action::Exported)
+            }
+        },
+        (None, Some(file), Some(entry)) => {
+            if hash(&file.contents) != entry.hash {
+                let message = "The note was deleted from the database but its file changed since".to_string();
+                return Ok(Some(failed(&file.path, &file.note.title, message)))
+            }
+
+            pending.state.notes.remove(id);
+            pending.removals.insert(file.path.clone());
+            (file.path, file.note.title, Action::Removed)
+        },
+        (None, Some(file), None) => import(vault, dir, file, pending, false)?,
+        (Some(note), Some(file), entry) => {
+            let file_changed = entry.as_ref().is_none_or(|entry| entry.hash != hash(&file.contents));
+            let db_changed = entry.as_ref().is_none_or(|entry| entry.revision != note.revision);
+            let conflict = file.path.with_extension(CONFLICT_EXTENSION);
+
+            if entry.as_ref().is_some_and(|entry| entry.conflict) {
+                match conflict.exists() {
+                    true => (file.path, note.title, Action::Unresolved(conflict)),
+                    false => import(vault, dir, file, pending, true)?
+                }
+            }
+            else if (!file_changed && !db_changed) || vault.note_to_md(id)? == file.contents {
+                remember(&mut pending.state, dir, id, &file.path, note.revision, &file.contents);
+                return Ok(None)
+            }
+            else if !file_changed {
+                export(vault, dir, id, &file.path, pending)?;
+                (file.path, note.title, Action::Exported)
+            }
+            else if !db_changed {
+                import(vault, dir, file, pending, false)?
+            }
+            else {
+                pending.write(&conflict, vault.note_to_md(id)?);
+                remember(&mut pending.state, dir, id, &file.path, note.revision, &file.contents);
+                if let Some(entry) = pending.state.notes.get_mut(id) {
+                    entry.conflict = true;
+                }
+                (file.path, note.title, Action::Conflict(conflict))
+            }
+        },
+        (None, None, _) => {
+            pending.state.notes.remove(id);
+            return Ok(None)
+        }
+    };
+
+    Ok(Some(Synced { path, title, action }))
+}
+
+/// Reads the file into the database. Files without an id are rewritten with
+/// the one the note got, so they match it from then on. With `force` the
+/// note is overwritten even if it changed since the last sync.
+fn import(vault: &mut Vault, dir: &Path, file: File, pending: &mut Pending, force: bool) -> Result<(PathBuf, String, Action), SyncError> {
+    let File { path, contents, note: note_from_md } = file;
+    let has_id = note_from_md.id.is_some();
+
+    let (note, action) = match note_from_md.id.as_ref().and_then(|id| pending.state.notes.get(id)) {
+        Some(entry) if !force && !entry.conflict => {
+            (vault.update_note_at_revision(note_from_md, entry.revision)?, Action::Imported)
+        },
+        _ => match vault.set_note(note_from_md)? {
+            SetOutcome::Added(note) => (note, Action::Added),
+            SetOutcome::Updated(note) => (note, Action::Imported),
+        }
+    };
+
+    match has_id {
+        true => remember(&mut pending.state, dir, &note.id, &path, note.revision, &contents),
+        false => export(vault, dir, &note.id, &path, pending)?
+    }
+
+    Ok((path, note.title, action))
+}
+
+/// Writes the note to `path`.
+fn export(vault: &Vault, dir: &Path, id: &str, path: &Path, pending: &mut Pending) -> Result<(), SyncError> {
+    let note = vault.get_note(id)?;
+    let contents = vault.note_to_md(id)?;
+    remember(&mut pending.state, dir, id, path, note.revision, &contents);
+    pending.write(path, contents);
+
+    Ok(())
+}
+
+fn remember(state: &mut State, dir: &Path, id: &str, path: &Path, revision: i64, contents: &str) {
+    state.notes.insert(id.to_string(), Entry {
+        path: relative(dir, path),
+        revision,
+        hash: hash(contents),
+        conflict: false,
+    });
+}
+
+fn read(path: &Path) -> Result<File, String> {
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let note = parse::md_to_new_note(contents.clone()).map_err(|e| e.to_string())?;
+
+    Ok(File { path: path.to_path_buf(), contents, note })
+}
+
+fn failed(path: &Path, title: &str, message: String) -> Synced {
+    Synced { path: path.to_path_buf(), title: title.to_string(), action: Action::Failed(message) }
+}
+
+fn relative(dir: &Path, path: &Path) -> String {
+    path.strip_prefix(dir)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// FNV-1a, stable across platforms and Rust versions unlike `DefaultHasher`.
+fn hash(contents: &str) -> String {
+    let hash = contents.bytes()
+        .fold(0xcbf29ce484222325_u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+
+    format!("{hash:016x}")
+}
+
+fn load_state(dir: &Path) -> Result<State, SyncError> {
+    let path = dir.join(STATE_FILE);
+    if !path.exists() {
+        return Ok(State::default())
+    }
+
+    let cannot_read = |e: String| SyncError::CannotReadState(path.display().to_string(), e);
+    let text = fs::read_to_string(&path).map_err(|e| cannot_read(e.to_string()))?;
+
+    serde_json::from_str(&text).map_err(|e| cannot_read(e.to_string()))
+}
+
+fn save_state(dir: &Path, state: &State) -> Result<(), SyncError> {
+    let text = serde_json::to_string_pretty(state)
+        .map_err(|e| SyncError::CannotWrite(STATE_FILE.to_string(), e.to_string()))?;
+
+    write(&dir.join(STATE_FILE), &text)
+}
+
+fn write(path: &Path, contents: &str) -> Result<(), SyncError> {
+    let cannot_write = |e: std::io::Error| SyncError::CannotWrite(path.display().to_string(), e.to_string());
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(cannot_write)?;
+    }
+
+    fs::write(path, contents).map_err(cannot_write)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use crate::{config::Config, init_db::setup_database, storage::sqlite::SqliteStorage};
+
+    use super::*;
+
+    /// Directory removed when the test ends, even if it fails.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let path = env::temp_dir().join(format!("spark-sync-{:016x}", rand::random::<u64>()));
+            fs::create_dir(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn run(vault: &mut Vault, dir: &Path, delete: bool) -> Vec<Synced> {
+        sync(vault, dir, delete).unwrap().apply().unwrap()
+    }
+
+    /// Every file under `dir` with its contents.
+    fn files(dir: &Path) -> BTreeMap<PathBuf, String> {
+        fs::read_dir(dir).unwrap()
+            .map(|entry| entry.unwrap().path())
+            .map(|path| (path.clone(), fs::read_to_string(path).unwrap()))
+            .collect()
+    }
+
+    fn actions(report: &[Synced]) -> Vec<String> {
+        report.iter().map(|synced| format!("{} {:?}", synced.title, synced.action)).collect()
+    }
+
+    fn edit(vault: &mut Vault, id: &str, contents: &str) {
+        let note = parse::md_to_new_note(format!("# [{id}] A\n\n{contents}\n")).unwrap();
+        vault.update_note(note).unwrap();
+    }
+
+    #[test]
+    fn sync_copies_changes_of_one_side_to_the_other() {
+        let dir = TempDir::new();
+        let mut vault = Vault::in_memory();
+        let a = vault.add_note(parse::md_to_new_note("# A\n\nv1\n".to_string()).unwrap()).unwrap();
+        let path = dir.0.join(format!("{}.md", a.id));
+
+        assert_eq!(actions(&run(&mut vault, &dir.0, false)), ["A Exported"]);
+        assert!(run(&mut vault, &dir.0, false).is_empty());
+
+        edit(&mut vault, &a.id, "v2");
+        assert_eq!(actions(&run(&mut vault, &dir.0, false)), ["A Exported"]);
+        assert!(fs::read_to_string(&path).unwrap().contains("v2"));
+
+        fs::write(&path, fs::read_to_string(&path).unwrap().replace("v2", "v3")).unwrap();
+        assert_eq!(actions(&run(&mut vault, &dir.0, false)), ["A Imported"]);
+        assert_eq!(vault.get_note(&a.id).unwrap().contents.trim(), "v3");
+    }
+
+    #[test]
+    fn sync_keeps_both_versions_when_both_changed() {
+        let dir = TempDir::new();
+        let mut vault = Vault::in_memory();
+        let a = vault.add_note(parse::md_to_new_note("# A\n\nv1\n".to_string()).unwrap()).unwrap();
+        let path = dir.0.join(format!("{}.md", a.id));
+        let conflict = path.with_extension(CONFLICT_EXTENSION);
+        run(&mut vault, &dir.0, false);
+
+        fs::write(&path, fs::read_to_string(&path).unwrap().replace("v1", "file")).unwrap();
+        edit(&mut vault, &a.id, "database");
+
+        let report = run(&mut vault, &dir.0, false);
+        assert!(matches!(&report[0].action, Action::Conflict(written) if *written == conflict));
+        assert!(fs::read_to_string(&conflict).unwrap().contains("database"));
+        assert!(fs::read_to_string(&path).unwrap().contains("file"));
+
+        let report = run(&mut vault, &dir.0, false);
+        assert!(matches!(&report[0].action, Action::Unresolved(_)));
+        assert_eq!(vault.get_note(&a.id).unwrap().contents.trim(), "database");
+
+        fs::remove_file(&conflict).unwrap();
+        assert_eq!(actions(&run(&mut vault, &dir.0, false)), ["A Imported"]);
+        assert_eq!(vault.get_note(&a.id).unwrap().contents.trim(), "file");
+    }
+
+    #[test]
+    fn sync_deletes_notes_of_removed_files_only_when_asked_and_unchanged() {
+        let dir = TempDir::new();
+        let mut vault = Vault::in_memory();
+        let a = vault.add_note(parse::md_to_new_note("# A\n\nv1\n".to_string()).unwrap()).unwrap();
+        let path = dir.0.join(format!("{}.md", a.id));
+        run(&mut vault, &dir.0, false);
+
+        fs::remove_file(&path).unwrap();
+        edit(&mut vault, &a.id, "v2");
+        assert_eq!(actions(&run(&mut vault, &dir.0, true)), ["A Exported"]);
+
+        fs::remove_file(&path).unwrap();
+        assert_eq!(actions(&run(&mut vault, &dir.0, true)), ["A Deleted"]);
+        assert!(vault.list_notes().unwrap().is_empty());
+    }
+
+    #[test]
+    fn files_are_left_alone_when_the_database_changes_are_rolled_back() {
+        let dir = TempDir::new();
+        // The operation log is written last, after sync changed the notes.
+        let conn = setup_database(":memory:");
+        conn.execute("CREATE TRIGGER fail BEFORE INSERT ON operations BEGIN SELECT RAISE(ABORT, 'disk is full'); END", ()).unwrap();
+        let mut vault = Vault::new(SqliteStorage::new(conn), Config::default());
+        let a = vault.add_note(parse::md_to_new_note("# A\n\nv1\n".to_string()).unwrap()).unwrap();
+        run(&mut vault, &dir.0, false);
+
+        let path = dir.0.join(format!("{}.md", a.id));
+        fs::write(&path, fs::read_to_string(&path).unwrap().replace("v1", "v2")).unwrap();
+        fs::write(dir.0.join("b.md"), "# B\n").unwrap();
+        vault.add_note(parse::md_to_new_note("# C\n".to_string()).unwrap()).unwrap();
+        let before = files(&dir.0);
+
+        let result = vault.record("sync", &[], &[], |vault| sync(vault, &dir.0, false));
+
+        assert!(matches!(result, Err(SyncError::Vault(_))));
+        assert_eq!(files(&dir.0), before);
+        assert_eq!(vault.get_note(&a.id).unwrap().contents.trim(), "v1");
+        assert_eq!(vault.list_notes().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,304 @@
+use ratatui::widgets::ListState;
+
+use crate::{models::note::NoteListItem, util::parse::md_to_new_note, vault::Vault};
+
+use super::error::TuiError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Focus {
+    Notes,
+    Links,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkKind {
+    Reference,
+    Backlink,
+}
+
+/// State of the terminal UI, independent of drawing and terminal handling.
+pub struct App<'a> {
+    pub vault: &'a mut Vault,
+    pub query: String,
+    pub searching: bool,
+    pub focus: Focus,
+    pub notes: Vec<NoteListItem>,
+    pub notes_state: ListState,
+    pub links: Vec<(LinkKind, NoteListItem)>,
+    pub links_state: ListState,
+    pub preview: String,
+    pub status: Option<String>,
+    /// Ids of previously selected notes, for going back after following a link.
+    history: Vec<String>,
+}
+
+impl<'a> App<'a> {
+    pub fn new(vault: &'a mut Vault) -> Result<Self, TuiError> {
+        let mut app = Self {
+            vault,
+            query: String::new(),
+            searching: false,
+            focus: Focus::Notes,
+            notes: vec![],
+            notes_state: ListState::default(),
+            links: vec![],
+            links_state: ListState::default(),
+            preview: String::new(),
+            status: None,
+            history: vec![],
+        };
+        app.reload()?;
+
+        Ok(app)
+    }
+
+    pub fn selected(&self) -> Option<&NoteListItem> {
+        self.notes_state.selected().and_then(|i| self.notes.get(i))
+    }
+
+    /// Reloads the note list for the current query, keeping the selection if possible.
+    pub fn reload(&mut self) -> Result<(), TuiError> {
+        let selected = self.selected().map(|note| note.id.clone());
+
+        self.notes = match self.query.trim() {
+            "" => self.vault.list_notes()?,
+            query => self.vault.search(query)?,
+        };
+        self.notes.sort_by(|a, b| a.title.cmp(&b.title));
+
+        let index = selected
+            .and_then(|id| self.notes.iter().position(|note| note.id == id))
+            .or(if self.notes.is_empty() { None } else { Some(0) });
+        self.notes_state.select(index);
+
+        self.load_selected()
+    }
+
+    /// Loads preview and links of the selected note.
+    pub fn load_selected(&mut self) -> Result<(), TuiError> {
+        self.links.clear();
+        self.preview.clear();
+
+        let Some(id) = self.selected().map(|note| note.id.clone()) else {
+            self.links_state.select(None);
+            return Ok(())
+        };
+
+        self.preview = self.vault.note_to_md(&id)?;
+
+        let references = self.vault.references_of(&id)?;
+        let backlinks = self.vault.backlinks_of(&id)?;
+        self.links = references.internal.into_iter()
+            .map(|note| (LinkKind::Reference, note))
+            .chain(backlinks.into_iter().map(|note| (LinkKind::Backlink, note)))
+            .map(|(kind, note)| (kind, NoteListItem { id: note.id, title: note.title, sequence: note.sequence }))
+            .collect();
+        self.links_state.select(if self.links.is_empty() { None } else { Some(0) });
+
+        Ok(())
+    }
+
+    pub fn move_selection(&mut self, offset: isize) -> Result<(), TuiError> {
+        match self.focus {
+            Focus::Notes => {
+                Self::offset(&mut self.notes_state, self.notes.len(), offset);
+                self.load_selected()
+            },
+            Focus::Links => {
+                Self::offset(&mut self.links_state, self.links.len(), offset);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn toggle_focus(&mut self) {
+        self.focus = match self.focus {
+            Focus::Notes if !self.links.is_empty() => Focus::Links,
+            _ => Focus::Notes,
+        };
+    }
+
+    /// Selects the note under the cursor of the links pane.
+    pub fn follow_link(&mut self) -> Result<(), TuiError> {
+        let Some((_, note)) = self.links_state.selected().and_then(|i| self.links.get(i)) else {
+            return Ok(())
+        };
+        let id = note.id.clone();
+
+        if let Some(current) = self.selected() {
+            self.history.push(current.id.clone());
+        }
+
+        self.select_note(&id)?;
+        self.focus = Focus::Notes;
+
+        Ok(())
+    }
+
+    pub fn back(&mut self) -> Result<(), TuiError> {
+        match self.history.pop() {
+            Some(id) => self.select_note(&id),
+            None => Ok(())
+        }
+    }
+
+    /// Updates the note with provided id from its edited markdown, refusing
+    /// markdown whose heading names another note.
+    pub fn save_edit(&mut self, id: &str, text: String) -> Result<(), TuiError> {
+        let mut note_from_md = md_to_new_note(text)?;
+        if let Some(edited) = note_from_md.id.take().filter(|edited| edited != id) {
+            return Err(TuiError::IdChanged(id.to_string(), edited))
+        }
+        note_from_md.id = Some(id.to_string());
+
+        let command = format!("tui edit {}", note_from_md.title);
+        self.vault.record(&command, &[id.to_string()], &[], |vault| vault.update_note(note_from_md))?;
+
+        Ok(())
+    }
+
+    /// The `[ID] Title` string used to reference the selected note.
+    pub fn reference(&self) -> Option<String> {
+        self.selected().map(|note| format!("[{}] {}", note.id, note.title))
+    }
+
+    fn select_note(&mut self, id: &str) -> Result<(), TuiError> {
+        if !self.notes.iter().any(|note| note.id == id) {
+            self.query.clear();
+            self.reload()?;
+        }
+
+        let index = self.notes.iter().position(|note| note.id == id);
+        self.notes_state.select(index);
+
+        self.load_selected()
+    }
+
+    fn offset(state: &mut ListState, len: usize, offset: isize) {
+        if len == 0 {
+            state.select(None);
+            return
+        }
+
+        let current = state.selected().unwrap_or(0) as isize;
+        let next = (current + offset).clamp(0, len as isize - 1);
+        state.select(Some(next as usize));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `Zettel` references `Idea`, `Other` stands alone.
+    fn vault() -> Vault {
+        let mut vault = Vault::in_memory();
+        for md in ["# Idea\n\nAn idea.\n", "# Zettel\n\nA card.\n## References\n### Internal\n1. Idea\n", "# Other\n\nSomething else.\n"] {
+            vault.add_note(md_to_new_note(md.to_string()).unwrap()).unwrap();
+        }
+
+        vault
+    }
+
+    fn titles(app: &App) -> Vec<String> {
+        app.notes.iter().map(|note| note.title.clone()).collect()
+    }
+
+    fn selected(app: &App) -> Option<String> {
+        app.selected().map(|note| note.title.clone())
+    }
+
+    #[test]
+    fn query_filters_notes_and_keeps_the_selection() {
+        let mut vault = vault();
+        let mut app = App::new(&mut vault).unwrap();
+        assert_eq!(titles(&app), ["Idea", "Other", "Zettel"]);
+        assert_eq!(selected(&app).as_deref(), Some("Idea"));
+
+        app.move_selection(2).unwrap();
+        app.query = "card".to_string();
+        app.reload().unwrap();
+        assert_eq!(titles(&app), ["Zettel"]);
+        assert_eq!(selected(&app).as_deref(), Some("Zettel"));
+
+        app.query = "nothing like it".to_string();
+        app.reload().unwrap();
+        assert_eq!(selected(&app), None);
+        assert!(app.preview.is_empty());
+    }
+
+    #[test]
+    fn selection_stays_within_the_list() {
+        let mut vault = vault();
+        let mut app = App::new(&mut vault).unwrap();
+
+        app.move_selection(-1).unwrap();
+        assert_eq!(selected(&app).as_deref(), Some("Idea"));
+
+        app.move_selection(10).unwrap();
+        assert_eq!(selected(&app).as_deref(), Some("Zettel"));
+        assert!(app.preview.contains("A card."));
+    }
+
+    #[test]
+    fn links_can_be_followed_and_gone_back_from() {
+        let mut vault = vault();
+        let mut app = App::new(&mut vault).unwrap();
+        app.move_selection(2).unwrap();
+        assert_eq!(app.links.len(), 1);
+        assert_eq!(app.links[0].0, LinkKind::Reference);
+
+        app.toggle_focus();
+        assert_eq!(app.focus, Focus::Links);
+        app.follow_link().unwrap();
+        assert_eq!(selected(&app).as_deref(), Some("Idea"));
+        assert_eq!(app.focus, Focus::Notes);
+        assert_eq!(app.links[0].0, LinkKind::Backlink);
+
+        app.back().unwrap();
+        assert_eq!(selected(&app).as_deref(), Some("Zettel"));
+    }
+
+    #[test]
+    fn following_a_link_clears_a_query_hiding_its_target() {
+        let mut vault = vault();
+        let mut app = App::new(&mut vault).unwrap();
+        app.query = "card".to_string();
+        app.reload().unwrap();
+
+        app.toggle_focus();
+        app.follow_link().unwrap();
+
+        assert!(app.query.is_empty());
+        assert_eq!(selected(&app).as_deref(), Some("Idea"));
+    }
+
+    #[test]
+    fn focus_stays_on_notes_without_links() {
+        let mut vault = vault();
+        let mut app = App::new(&mut vault).unwrap();
+        app.move_selection(1).unwrap();
+
+        app.toggle_focus();
+
+        assert_eq!(app.focus, Focus::Notes);
+        assert_eq!(app.reference(), app.selected().map(|note| format!("[{}] Other", note.id)));
+    }
+
+    #[test]
+    fn save_edit_updates_the_selected_note_only() {
+        let mut vault = vault();
+        let idea = vault.find_note("Idea").unwrap();
+        let other = vault.find_note("Other").unwrap();
+        let mut app = App::new(&mut vault).unwrap();
+
+        app.save_edit(&idea.id, "# Idea\n\nEdited without an id.\n".to_string()).unwrap();
+
+        let changed = app.save_edit(&idea.id, format!("# [{}] Idea\n\nEdited with the wrong id.\n", other.id));
+        assert!(matches!(changed, Err(TuiError::IdChanged(id, edited)) if id == idea.id && edited == other.id));
+
+        assert_eq!(app.vault.get_note(&idea.id).unwrap().contents.trim(), "Edited without an id.");
+        assert_eq!(app.vault.get_note(&other.id).unwrap().contents.trim(), "Something else.");
+        assert_eq!(app.vault.operations(10).unwrap().len(), 1);
+    }
+}
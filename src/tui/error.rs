@@ -0,0 +1,28 @@
+use crate::{util::error::UtilError, vault::error::VaultError};
+
+#[derive(thiserror::Error, Debug)]
+pub enum TuiError {
+    #[error("Cannot start the editor: {0}")]
+    CannotStartEditor(String),
+
+    #[error("The id of note {0} cannot be changed to {1}")]
+    IdChanged(String, String),
+
+    #[error("{0}, the edited note is kept in {1}")]
+    EditNotSaved(String, String),
+
+    #[error(transparent)]
+    Vault(#[from] VaultError),
+
+    #[error(transparent)]
+    Util(#[from] UtilError),
+
+    #[error(transparent)]
+    Generic(#[from] anyhow::Error)
+}
+
+impl From<std::io::Error> for TuiError {
+    fn from(value: std::io::Error) -> Self {
+        TuiError::Generic(value.into())
+    }
+}
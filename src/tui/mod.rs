@@ -0,0 +1,181 @@
+use std::{env, fs::{self, DirBuilder, OpenOptions}, io::{stdout, ErrorKind, Write}, path::{Path, PathBuf}, process::Command};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ratatui::{crossterm::{event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers}, execute, terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen}}, DefaultTerminal};
+
+use crate::vault::Vault;
+
+use self::{app::{App, Focus}, error::TuiError};
+
+pub mod app;
+pub mod error;
+pub mod ui;
+
+/// Runs the interactive browser until the user quits.
+pub fn run(vault: &mut Vault) -> Result<(), TuiError> {
+    let mut terminal = ratatui::init();
+    let result = App::new(vault).and_then(|mut app| event_loop(&mut terminal, &mut app));
+    ratatui::restore();
+
+    result
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App) -> Result<(), TuiError> {
+    loop {
+        terminal.draw(|frame| ui::draw(frame, app))?;
+
+        let Event::Key(key) = event::read()? else {
+            continue
+        };
+        if key.kind != KeyEventKind::Press {
+            continue
+        }
+
+        app.status = None;
+        if app.searching {
+            handle_search_key(app, key)?;
+            continue
+        }
+
+        let result = match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+            KeyCode::Char('/') => {
+                app.searching = true;
+                app.focus = Focus::Notes;
+                Ok(())
+            },
+            KeyCode::Down | KeyCode::Char('j') => app.move_selection(1),
+            KeyCode::Up | KeyCode::Char('k') => app.move_selection(-1),
+            KeyCode::PageDown => app.move_selection(10),
+            KeyCode::PageUp => app.move_selection(-10),
+            KeyCode::Tab => {
+                app.toggle_focus();
+                Ok(())
+            },
+            KeyCode::Enter if app.focus == Focus::Links => app.follow_link(),
+            KeyCode::Char('b') | KeyCode::Backspace => app.back(),
+            KeyCode::Char('r') => app.reload(),
+            KeyCode::Char('y') => copy_reference(app),
+            KeyCode::Char('e') => edit_selected(terminal, app),
+            _ => Ok(())
+        };
+
+        if let Err(e) = result {
+            app.status = Some(e.to_string());
+        }
+    }
+}
+
+fn handle_search_key(app: &mut App, key: KeyEvent) -> Result<(), TuiError> {
+    match key.code {
+        KeyCode::Enter => app.searching = false,
+        KeyCode::Esc => {
+            app.searching = false;
+            app.query.clear();
+        },
+        KeyCode::Backspace => {
+            app.query.pop();
+        },
+        KeyCode::Char(c) => app.query.push(c),
+        _ => return Ok(())
+    }
+
+    app.reload()
+}
+
+/// Copies `[ID] Title` of the selected note with an OSC 52 escape sequence,
+/// which most terminals forward to the system clipboard, also over ssh.
+fn copy_reference(app: &mut App) -> Result<(), TuiError> {
+    let Some(reference) = app.reference() else {
+        return Ok(())
+    };
+
+    let mut out = stdout();
+    write!(out, "\x1b]52;c;{}\x07", STANDARD.encode(&reference))?;
+    out.flush()?;
+
+    app.status = Some(format!("Copied {reference}"));
+
+    Ok(())
+}
+
+/// Opens the selected note in the configured editor and stores the result
+/// once the editor exits.
+fn edit_selected(terminal: &mut DefaultTerminal, app: &mut App) -> Result<(), TuiError> {
+    let Some(note) = app.selected().cloned() else {
+        return Ok(())
+    };
+
+    let dir = private_dir()?;
+    let path = dir.join(format!("{}.md", note.id));
+    let edited = write_new(&path, &app.vault.note_to_md(&note.id)?)
+        .and_then(|_| run_editor(terminal, &app.vault.config().editor, &path))
+        .and_then(|_| Ok(fs::read_to_string(&path)?));
+    let text = match edited {
+        Ok(text) => text,
+        Err(e) => {
+            fs::remove_dir_all(&dir)?;
+            return Err(e)
+        }
+    };
+
+    if let Err(e) = app.save_edit(&note.id, text) {
+        return Err(TuiError::EditNotSaved(e.to_string(), path.display().to_string()))
+    }
+
+    fs::remove_dir_all(&dir)?;
+    app.reload()?;
+    app.status = Some("Note updated successfuly".to_string());
+
+    Ok(())
+}
+
+fn run_editor(terminal: &mut DefaultTerminal, editor: &str, path: &Path) -> Result<(), TuiError> {
+    let mut words = editor.split_whitespace();
+    let program = words.next().ok_or(TuiError::CannotStartEditor(editor.to_string()))?;
+
+    disable_raw_mode()?;
+    execute!(stdout(), LeaveAlternateScreen)?;
+    let status = Command::new(program).args(words).arg(path).status();
+    execute!(stdout(), EnterAlternateScreen)?;
+    enable_raw_mode()?;
+    terminal.clear()?;
+
+    let status = status.map_err(|e| TuiError::CannotStartEditor(format!("{editor}: {e}")))?;
+    if !status.success() {
+        return Err(TuiError::CannotStartEditor(format!("{editor} exited with {status}")))
+    }
+
+    Ok(())
+}
+
+/// Creates a new directory under the temporary directory which only the
+/// current user can enter, so other users cannot swap the edited file.
+fn private_dir() -> Result<PathBuf, TuiError> {
+    loop {
+        let path = env::temp_dir().join(format!("spark-{:016x}", rand::random::<u64>()));
+
+        let mut builder = DirBuilder::new();
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+
+        match builder.create(&path) {
+            Ok(()) => return Ok(path),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into())
+        }
+    }
+}
+
+/// Writes `contents` to `path`, failing instead of following a symlink or
+/// truncating a file already there.
+fn write_new(path: &Path, contents: &str) -> Result<(), TuiError> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)?;
+    file.write_all(contents.as_bytes())?;
+
+    Ok(())
+}
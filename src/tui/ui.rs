@@ -0,0 +1,84 @@
+use ratatui::{layout::{Constraint, Layout}, style::{Color, Modifier, Style}, text::{Line, Span}, widgets::{Block, List, ListItem, Paragraph, Wrap}, Frame};
+
+use super::app::{App, Focus, LinkKind};
+
+const HELP: &str = "/ search  tab links  enter follow  b back  e edit  y copy  q quit";
+
+pub fn draw(frame: &mut Frame, app: &mut App) {
+    let [search, body, status] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Min(0),
+        Constraint::Length(1),
+    ]).areas(frame.area());
+    let [notes, right] = Layout::horizontal([
+        Constraint::Percentage(35),
+        Constraint::Percentage(65),
+    ]).areas(body);
+    let [preview, links] = Layout::vertical([
+        Constraint::Min(0),
+        Constraint::Length(8),
+    ]).areas(right);
+
+    let search_style = if app.searching { Style::new().fg(Color::Yellow) } else { Style::new() };
+    frame.render_widget(
+        Paragraph::new(app.query.as_str())
+            .style(search_style)
+            .block(Block::bordered().title("Search")),
+        search
+    );
+
+    let items: Vec<ListItem> = app.notes.iter()
+        .map(|note| ListItem::new(note.title.as_str()))
+        .collect();
+    let list = List::new(items)
+        .block(pane("Notes", app.focus == Focus::Notes))
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, notes, &mut app.notes_state);
+
+    frame.render_widget(
+        Paragraph::new(render_markdown(&app.preview))
+            .wrap(Wrap { trim: false })
+            .block(Block::bordered().title("Preview")),
+        preview
+    );
+
+    let items: Vec<ListItem> = app.links.iter()
+        .map(|(kind, note)| {
+            let arrow = match kind {
+                LinkKind::Reference => "→ ",
+                LinkKind::Backlink => "← ",
+            };
+            ListItem::new(format!("{arrow}{}", note.title))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(pane("References and backlinks", app.focus == Focus::Links))
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, links, &mut app.links_state);
+
+    let message = app.status.as_deref().unwrap_or(HELP);
+    frame.render_widget(Paragraph::new(message).style(Style::new().fg(Color::DarkGray)), status);
+}
+
+fn pane(title: &str, focused: bool) -> Block<'_> {
+    let style = if focused { Style::new().fg(Color::Cyan) } else { Style::new() };
+
+    Block::bordered().title(title).border_style(style)
+}
+
+/// Light styling of the note markdown: headings and list markers stand out.
+fn render_markdown(text: &str) -> Vec<Line<'_>> {
+    text.lines()
+        .map(|line| {
+            if line.starts_with('#') {
+                Line::styled(line, Style::new().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+            }
+            else if let Some(item) = line.trim_start().strip_prefix("- ") {
+                Line::from(vec![Span::styled("• ", Style::new().fg(Color::Yellow)), Span::raw(item)])
+            }
+            else {
+                Line::raw(line)
+            }
+        })
+        .collect()
+}
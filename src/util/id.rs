@@ -0,0 +1,172 @@
+use std::{sync::LazyLock, time::{SystemTime, UNIX_EPOCH}};
+
+use regex::Regex;
+
+use crate::config::IdFormat;
+
+/// How many candidates are tried before giving up on finding a free id.
+pub const MAX_ATTEMPTS: u32 = 32;
+
+/// Matches an id of any supported format written in square brackets: a
+/// uuid, 12 or 6 characters of base32 or a `YYYYMMDDHHMM` timestamp.
+pub const ID_PATTERN: &str = r"\[(?<id>[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}|[A-Z2-7]{12}|[0-9]{12}|[A-Z2-7]{6})\]";
+
+static ID_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(ID_PATTERN).expect("Id pattern is valid"));
+
+/// Candidate id for the given attempt, `attempt` only matters for formats
+/// which are not random.
+pub fn generate_id_with(format: IdFormat, attempt: u32) -> String {
+    match format {
+        IdFormat::Short => base32_id(&rand::random::<[u8; 4]>(), 6),
+        IdFormat::Long => base32_id(&rand::random::<[u8; 8]>(), 12),
+        IdFormat::Uuid => uuid::Uuid::new_v4().to_string(),
+        IdFormat::Timestamp => timestamp_id(attempt),
+    }
+}
+
+/// First generated id for which `taken` returns false.
+pub fn generate_unique_id<E, F>(format: IdFormat, mut taken: F) -> Result<Option<String>, E>
+    where F: FnMut(&str) -> Result<bool, E> {
+    for attempt in 0..MAX_ATTEMPTS {
+        let id = generate_id_with(format, attempt);
+        if !taken(&id)? {
+            return Ok(Some(id))
+        }
+    }
+
+    Ok(None)
+}
+
+/// Whether `id` is a whole id of one of the supported formats.
+pub fn is_valid(id: &str) -> bool {
+    let text = format!("[{id}]");
+
+    ID_REGEX.find(&text).is_some_and(|found| found.len() == text.len())
+}
+
+pub fn extract_id(text: &str) -> Option<String> {
+    let caps = ID_REGEX.captures(text)?;
+
+    Some(caps["id"].to_string())
+}
+
+fn base32_id(bytes: &[u8], len: usize) -> String {
+    let id = base32::encode(base32::Alphabet::RFC4648 { padding: false }, bytes);
+
+    id[0..len].to_string()
+}
+
+/// Zettelkasten style `YYYYMMDDHHMM` in UTC, every attempt moves one minute forward.
+fn timestamp_id(attempt: u32) -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let minutes = seconds / 60 + attempt as u64;
+
+    let (year, month, day) = civil_from_days((minutes / (60 * 24)) as i64);
+    let hour = minutes / 60 % 24;
+    let minute = minutes % 60;
+
+    format!("{year:04}{month:02}{day:02}{hour:02}{minute:02}")
+}
+
+/// Gregorian date of the given day since the unix epoch.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generate_unique_id_skips_taken_ids() {
+        let mut tried = vec![];
+
+        let id = generate_unique_id(IdFormat::Timestamp, |id| -> Result<bool, ()> {
+            tried.push(id.to_string());
+            Ok(tried.len() < 3)
+        });
+
+        assert_eq!(id.unwrap().as_deref(), tried.last().map(String::as_str));
+        assert_eq!(tried.len(), 3);
+        assert!(tried[0] < tried[1] && tried[1] < tried[2]);
+    }
+
+    #[test]
+    fn generate_unique_id_gives_up_after_max_attempts() {
+        let mut attempts = 0;
+
+        let id = generate_unique_id(IdFormat::Short, |_| -> Result<bool, ()> {
+            attempts += 1;
+            Ok(true)
+        });
+
+        assert_eq!(id, Ok(None));
+        assert_eq!(attempts, MAX_ATTEMPTS);
+    }
+
+    #[test]
+    fn generated_ids_match_the_pattern() {
+        for format in [IdFormat::Short, IdFormat::Long, IdFormat::Uuid, IdFormat::Timestamp] {
+            let id = generate_id_with(format, 0);
+
+            assert_eq!(extract_id(&format!("[{id}] Title")), Some(id));
+        }
+    }
+
+    #[test]
+    fn civil_from_days_handles_epoch_and_leap_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(19782), (2024, 2, 29));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(civil_from_days(11017), (2000, 3, 1));
+    }
+
+    #[test]
+    fn timestamp_id_is_twelve_digits_a_minute_apart() {
+        let first = timestamp_id(0);
+        let second = timestamp_id(1);
+
+        assert_eq!(first.len(), 12);
+        assert!(first.chars().all(|c| c.is_ascii_digit()));
+        assert!(second > first);
+    }
+
+    #[test]
+    fn id_pattern_matches_every_format() {
+        for id in ["ABC234", "ABCDEFGH2345", "202402291305", "0f8fad5b-d9cb-469f-a165-70867728950e"] {
+            assert_eq!(extract_id(&format!("See [{id}] Title")).as_deref(), Some(id));
+        }
+    }
+
+    #[test]
+    fn is_valid_accepts_whole_ids_only() {
+        for id in ["ABC234", "ABCDEFGH2345", "202402291305", "0f8fad5b-d9cb-469f-a165-70867728950e"] {
+            assert!(is_valid(id), "{id}");
+        }
+
+        for id in ["", "../../x", "ABC234/..", "ABC234][ABC234", "abc234", "Introduction"] {
+            assert!(!is_valid(id), "{id}");
+        }
+    }
+
+    #[test]
+    fn id_pattern_ignores_bracketed_words() {
+        for text in ["[Introduction]", "[readme]", "[abc234]", "[ABC189]", "[20240229]", "[ABCDEFGHIJ]", "[2024022913051]", "ABC234"] {
+            assert_eq!(extract_id(text), None, "{text}");
+        }
+    }
+}
@@ -2,36 +2,52 @@ use std::cell::RefCell;
 
 use comrak::{arena_tree::Node, nodes::{Ast, AstNode, NodeHeading, NodeValue}, parse_document, Arena, Options};
 
-use crate::{models::{note::Note, sources::Source}, util::extract_id};
+use crate::{config::{ListStyle, MarkdownLayout}, models::{note::Note, sources::Source}, util::extract_id};
 
 use super::{error::UtilError, NoteFromMd, Reference};
 
 pub fn md_to_new_note(text: String) -> Result<NoteFromMd, UtilError>{
     let parser = MdToNoteParser::default();
 
-    let note = parser.parse(text);
-
-    note
+    parser.parse(text)
 }
 
-pub fn note_to_md(note: Note, internal: Vec<Note>, external: Vec<Source>) -> String {
-    let mut md_note = format!("# [{}] {}{}\n## References\n### Internal\n", note.id, note.title, note.contents);
+pub fn note_to_md(note: Note, aliases: Vec<String>, internal: Vec<Note>, external: Vec<Source>, layout: &MarkdownLayout) -> String {
+    // Parsed contents start with the line break after the title, contents
+    // coming from elsewhere may not.
+    let separator = if note.contents.starts_with('\n') { "" } else { "\n" };
+    let mut md_note = format!("# [{}] {}{separator}{}\n", note.id, note.title, note.contents);
+
+    if !aliases.is_empty() {
+        md_note.push_str("## Aliases\n");
+        for alias in aliases {
+            md_note.push_str(&format!(" - {}\n", alias));
+        }
+        md_note.push('\n');
+    }
+
+    md_note.push_str("## References\n### Internal\n");
 
     for (i, note) in internal.iter().enumerate() {
-        let reference = format!("{}. [{}] {}\n", i + 1, note.id, note.title);
-        md_note.push_str(&reference);
+        md_note.push_str(&list_item(layout.internal_list, i, &note.id, &note.title));
     }
 
     md_note.push_str("\n### External\n");
 
-    for source in external {
-        let reference = format!(" - [{}] {}\n", source.id, source.title);
-        md_note.push_str(&reference);
+    for (i, source) in external.iter().enumerate() {
+        md_note.push_str(&list_item(layout.external_list, i, &source.id, &source.title));
     }
 
     md_note
 }
 
+fn list_item(style: ListStyle, index: usize, id: &str, title: &str) -> String {
+    match style {
+        ListStyle::Numbered => format!("{}. [{}] {}\n", index + 1, id, title),
+        ListStyle::Bulleted => format!(" - [{}] {}\n", id, title),
+    }
+}
+
 #[derive(Default)]
 struct MdToNoteParser {
     note: NoteFromMd,
@@ -75,7 +91,7 @@ impl MdToNoteParser {
                 adding = true;
                 continue;
             }
-            if line.trim().starts_with("## References") {
+            if line.trim().starts_with("## References") || line.trim().starts_with("## Aliases") {
                 break;
             }
             if adding {
@@ -96,32 +112,41 @@ impl MdToNoteParser {
 
 
     fn references_stage(&mut self) -> Result<(), UtilError> {
-        if self.stage != ParsingStage::Title {
-            return Err(UtilError::InvalidNoteMarkdown)
+        match self.stage {
+            ParsingStage::Title => self.stage.next(),
+            ParsingStage::AliasItems => self.stage = ParsingStage::References,
+            _ => return Err(UtilError::InvalidNoteMarkdown)
         }
-        self.stage.next();
         Ok(())
     }
 
     fn handle_current_stage(&mut self, text: &str) {
         match self.stage {
-            ParsingStage::Title => {
-                if self.note.title.is_empty() {
-                    if let Some(id) = extract_id(text) {
-                        self.note.title = text.replace(&format!("[{}]", id), "").trim().to_string();
-                        self.note.id = Some(id);
-                        return
-                    }
-                    self.note.title = text.trim().to_string();
+            ParsingStage::Title if self.note.title.is_empty() => {
+                if let Some(id) = extract_id(text) {
+                    self.note.title = text.replace(&format!("[{}]", id), "").trim().to_string();
+                    self.note.id = Some(id);
+                    return
                 }
+                self.note.title = text.trim().to_string();
             }
             ParsingStage::References => {
                 if text == "References" {
                     return
                 }
+                if text == "Aliases" {
+                    self.stage = ParsingStage::AliasItems;
+                    return
+                }
 
                 self.stage.prev()
             }
+            ParsingStage::AliasItems => {
+                let alias = text.trim();
+                if !alias.is_empty() && !self.note.aliases.iter().any(|a| a == alias) {
+                    self.note.aliases.push(alias.to_string());
+                }
+            }
             ParsingStage::InternalReferences => {
                 if text == "Internal" {
                     self.stage.next();
@@ -168,6 +193,7 @@ enum ParsingStage {
     #[default]
     Start,
     Title,
+    AliasItems,
     References,
     InternalReferences,
     InternalReferenceItems,
@@ -181,6 +207,7 @@ impl ParsingStage {
         *self = match self {
             Self::Start => Self::Title,
             Self::Title => Self::References,
+            Self::AliasItems => Self::References,
             Self::References => Self::InternalReferences,
             Self::InternalReferences => Self::InternalReferenceItems,
             Self::InternalReferenceItems => Self::ExternalReferences,
@@ -198,8 +225,80 @@ impl ParsingStage {
             Self::InternalReferenceItems => Self::InternalReferences,
             Self::InternalReferences => Self::References,
             Self::References => Self::Title,
+            Self::AliasItems => Self::Title,
             Self::Title => Self::Start,
             Self::Start => Self::Start,
         };
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(title: &str, contents: &str) -> Note {
+        Note {
+            id: "ABC234".to_string(),
+            title: title.to_string(),
+            contents: contents.to_string(),
+            sequence: None,
+            revision: 0,
+        }
+    }
+
+    #[test]
+    fn aliases_survive_a_round_trip() {
+        let md = "# [ABC234] Idea\n\nAn idea.\n## Aliases\n - Thought\n - Notion\n - Thought\n\n## References\n### Internal\n1. [DEF567] Other\n\n### External\n - [GHI234] Book\n";
+        let parsed = md_to_new_note(md.to_string()).unwrap();
+        assert_eq!(parsed.aliases, ["Thought", "Notion"]);
+
+        let written = note_to_md(
+            note(&parsed.title, &parsed.contents),
+            parsed.aliases.clone(),
+            vec![note("Other", "")],
+            vec![],
+            &MarkdownLayout::default(),
+        );
+        let reparsed = md_to_new_note(written).unwrap();
+
+        assert_eq!(reparsed.id.as_deref(), Some("ABC234"));
+        assert_eq!(reparsed.title, "Idea");
+        assert_eq!(reparsed.contents, parsed.contents);
+        assert_eq!(reparsed.aliases, parsed.aliases);
+        assert_eq!(reparsed.references.internal.len(), 1);
+    }
+
+    #[test]
+    fn layout_picks_list_styles() {
+        let layout = MarkdownLayout { internal_list: ListStyle::Bulleted, external_list: ListStyle::Numbered };
+        let source = Source { id: "GHI234".to_string(), title: "Book".to_string() };
+
+        let written = note_to_md(note("Idea", "\nAn idea.\n"), vec![], vec![note("Other", "")], vec![source], &layout);
+
+        assert!(written.contains("### Internal\n - [ABC234] Other\n"));
+        assert!(written.contains("### External\n1. [GHI234] Book\n"));
+        assert!(!written.contains("## Aliases"));
+
+        let reparsed = md_to_new_note(written).unwrap();
+        assert_eq!(reparsed.references.internal.len(), 1);
+        assert_eq!(reparsed.references.external.len(), 1);
+    }
+
+    #[test]
+    fn empty_aliases_section_has_no_aliases() {
+        let parsed = md_to_new_note("# Idea\n\nAn idea.\n## Aliases\n\n## References\n### Internal\n1. Other\n".to_string()).unwrap();
+
+        assert!(parsed.aliases.is_empty());
+        assert_eq!(parsed.contents.trim(), "An idea.");
+        assert_eq!(parsed.references.internal[0].title.as_deref(), Some("Other"));
+    }
+
+    #[test]
+    fn aliases_end_at_the_next_section() {
+        let parsed = md_to_new_note("# Idea\n## Aliases\n - Thought\n## References\n### Internal\n1. Other\n\n### External\n - Book\n".to_string()).unwrap();
+
+        assert_eq!(parsed.aliases, ["Thought"]);
+        assert_eq!(parsed.references.internal.len(), 1);
+        assert_eq!(parsed.references.external[0].title.as_deref(), Some("Book"));
+    }
+}
@@ -0,0 +1,157 @@
+use std::cmp::Ordering;
+
+/// Luhmann style sequence position such as `1`, `1a`, `1a1`, `12b3`: numbers and
+/// lowercase letters alternate, every level of branching adds a segment.
+pub fn is_valid(sequence: &str) -> bool {
+    let segments = segments(sequence);
+
+    !segments.is_empty()
+        && segments.iter().all(|s| !s.starts_with('0'))
+        && segments[0].chars().all(|c| c.is_ascii_digit())
+        && segments.concat() == sequence
+}
+
+/// Depth in the sequence tree, top level notes have depth 0.
+pub fn depth(sequence: &str) -> usize {
+    segments(sequence).len().saturating_sub(1)
+}
+
+/// Parent position, `None` for top level notes.
+pub fn parent(sequence: &str) -> Option<String> {
+    let segments = segments(sequence);
+    if segments.len() < 2 {
+        return None
+    }
+
+    Some(segments[..segments.len() - 1].concat())
+}
+
+/// Next free top level position.
+pub fn next_top<'a>(existing: impl Iterator<Item = &'a str>) -> String {
+    let last = existing
+        .filter(|s| depth(s) == 0)
+        .filter_map(|s| s.parse::<u64>().ok())
+        .max()
+        .unwrap_or(0);
+
+    (last + 1).to_string()
+}
+
+/// Next free branch below `parent`, `1` branches into `1a`, `1a` into `1a1`.
+pub fn next_child<'a>(parent: &str, existing: impl Iterator<Item = &'a str>) -> String {
+    let children: Vec<&str> = existing
+        .filter(|s| self::parent(s).as_deref() == Some(parent))
+        .collect();
+
+    let letters = parent.ends_with(|c: char| c.is_ascii_digit());
+    let last = children.iter()
+        .map(|child| &child[parent.len()..])
+        .map(|segment| match letters {
+            true => letters_to_number(segment),
+            false => segment.parse().unwrap_or(0),
+        })
+        .max()
+        .unwrap_or(0);
+
+    match letters {
+        true => format!("{parent}{}", number_to_letters(last + 1)),
+        false => format!("{parent}{}", last + 1),
+    }
+}
+
+/// Orders positions the way they appear in the tree: `1`, `1a`, `1a1`, `1b`, `2`, `10`.
+pub fn compare(a: &str, b: &str) -> Ordering {
+    let a = segments(a);
+    let b = segments(b);
+
+    for (a, b) in a.iter().zip(b.iter()) {
+        let ordering = a.len().cmp(&b.len()).then(a.cmp(b));
+        if ordering != Ordering::Equal {
+            return ordering
+        }
+    }
+
+    a.len().cmp(&b.len())
+}
+
+fn segments(sequence: &str) -> Vec<&str> {
+    let mut segments = vec![];
+    let mut start = 0;
+
+    for (i, c) in sequence.char_indices().skip(1) {
+        let previous = sequence[..i].chars().last().unwrap_or(c);
+        if previous.is_ascii_digit() != c.is_ascii_digit() {
+            segments.push(&sequence[start..i]);
+            start = i;
+        }
+    }
+
+    if !sequence.is_empty() {
+        segments.push(&sequence[start..]);
+    }
+
+    segments.into_iter()
+        .filter(|s| s.chars().all(|c| c.is_ascii_digit()) || s.chars().all(|c| c.is_ascii_lowercase()))
+        .collect()
+}
+
+/// `a` is 1, `z` is 26, `aa` is 27.
+fn letters_to_number(letters: &str) -> u64 {
+    letters.chars()
+        .fold(0, |number, c| number * 26 + (c as u64 - 'a' as u64 + 1))
+}
+
+fn number_to_letters(mut number: u64) -> String {
+    let mut letters = vec![];
+    while number > 0 {
+        number -= 1;
+        letters.push((b'a' + (number % 26) as u8) as char);
+        number /= 26;
+    }
+
+    letters.iter().rev().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_valid_accepts_alternating_numbers_and_letters() {
+        for sequence in ["1", "12", "1a", "1a1", "12b3", "3aa"] {
+            assert!(is_valid(sequence), "{sequence}");
+        }
+
+        for sequence in ["", "a", "a1", "01", "1a01", "1A", "1-a", "1 a"] {
+            assert!(!is_valid(sequence), "{sequence}");
+        }
+    }
+
+    #[test]
+    fn parent_and_depth_follow_segments() {
+        assert_eq!(parent("1"), None);
+        assert_eq!(parent("1a"), Some("1".to_string()));
+        assert_eq!(parent("12b3"), Some("12b".to_string()));
+        assert_eq!(depth("1"), 0);
+        assert_eq!(depth("1a1"), 2);
+    }
+
+    #[test]
+    fn next_positions_skip_taken_ones() {
+        let existing = ["1", "2", "1a", "1b", "1a1", "10"];
+
+        assert_eq!(next_top(existing.into_iter()), "11");
+        assert_eq!(next_child("1", existing.into_iter()), "1c");
+        assert_eq!(next_child("1a", existing.into_iter()), "1a2");
+        assert_eq!(next_child("2", existing.into_iter()), "2a");
+        assert_eq!(next_child("1", ["1z"].into_iter()), "1aa");
+    }
+
+    #[test]
+    fn compare_orders_like_the_tree() {
+        let mut sequences = vec!["10", "1b", "2", "1a1", "1", "1a"];
+        sequences.sort_by(|a, b| compare(a, b));
+
+        assert_eq!(sequences, ["1", "1a", "1a1", "1b", "2", "10"]);
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+const MIN_TERM_LENGTH: usize = 3;
+
+const STOP_WORDS: &[&str] = &[
+    "and", "are", "but", "can", "for", "from", "has", "have", "into", "its", "not",
+    "that", "the", "their", "then", "there", "these", "this", "was", "were", "which",
+    "will", "with", "you", "your",
+];
+
+/// Lowercased words of a text, short words and common English stop words dropped.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .map(str::to_lowercase)
+        .filter(|word| word.chars().count() >= MIN_TERM_LENGTH && !STOP_WORDS.contains(&word.as_str()))
+        .collect()
+}
+
+/// TF-IDF weighted term vectors of a set of documents.
+#[derive(Debug, Default)]
+pub struct Corpus {
+    vectors: BTreeMap<String, BTreeMap<String, f64>>,
+}
+
+impl Corpus {
+    pub fn new<'a>(documents: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        let terms: Vec<(&str, BTreeMap<String, usize>)> = documents.into_iter()
+            .map(|(id, text)| {
+                let mut counts = BTreeMap::new();
+                for term in tokenize(text) {
+                    *counts.entry(term).or_default() += 1;
+                }
+                (id, counts)
+            })
+            .collect();
+
+        let mut frequency: BTreeMap<&str, usize> = BTreeMap::new();
+        for (_, counts) in &terms {
+            for term in counts.keys() {
+                *frequency.entry(term).or_default() += 1;
+            }
+        }
+
+        let documents = terms.len() as f64;
+        let vectors = terms.iter()
+            .map(|(id, counts)| {
+                let total = counts.values().sum::<usize>() as f64;
+                let vector = counts.iter()
+                    .map(|(term, count)| {
+                        let idf = ((1.0 + documents) / (1.0 + frequency[term.as_str()] as f64)).ln() + 1.0;
+                        (term.clone(), *count as f64 / total * idf)
+                    })
+                    .collect();
+                (id.to_string(), vector)
+            })
+            .collect();
+
+        Self { vectors }
+    }
+
+    /// Cosine similarity of two documents with the terms contributing most
+    /// to it, heaviest first.
+    pub fn similarity(&self, a: &str, b: &str) -> (f64, Vec<String>) {
+        let (Some(a), Some(b)) = (self.vectors.get(a), self.vectors.get(b)) else {
+            return (0.0, vec![])
+        };
+
+        let norm = |vector: &BTreeMap<String, f64>| vector.values().map(|w| w * w).sum::<f64>().sqrt();
+        let norms = norm(a) * norm(b);
+        if norms == 0.0 {
+            return (0.0, vec![])
+        }
+
+        let shared: BTreeSet<&String> = a.keys().filter(|term| b.contains_key(*term)).collect();
+        let mut products: Vec<(&String, f64)> = shared.into_iter()
+            .map(|term| (term, a[term] * b[term]))
+            .collect();
+        let dot: f64 = products.iter().map(|(_, product)| product).sum();
+
+        products.sort_by(|x, y| y.1.total_cmp(&x.1));
+        let terms = products.into_iter().map(|(term, _)| term.clone()).collect();
+
+        (dot / norms, terms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenize_drops_short_and_stop_words() {
+        assert_eq!(tokenize("The Zettelkasten, and its NOTES: a method!"), ["zettelkasten", "notes", "method"]);
+        assert!(tokenize("").is_empty());
+    }
+
+    #[test]
+    fn rare_shared_terms_weigh_more_than_common_ones() {
+        let corpus = Corpus::new([
+            ("a", "zettelkasten notes"),
+            ("b", "zettelkasten cooking"),
+            ("c", "notes gardening"),
+            ("d", "notes travel"),
+            ("e", "notes music"),
+        ]);
+
+        let (rare, rare_terms) = corpus.similarity("a", "b");
+        let (common, common_terms) = corpus.similarity("a", "c");
+
+        assert!(rare > common, "{rare} <= {common}");
+        assert_eq!(rare_terms, ["zettelkasten"]);
+        assert_eq!(common_terms, ["notes"]);
+        assert!((corpus.similarity("a", "a").0 - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn empty_documents_are_similar_to_nothing() {
+        let corpus = Corpus::new([("a", "zettelkasten notes"), ("empty", ""), ("stop", "the and of")]);
+
+        assert_eq!(corpus.similarity("a", "empty"), (0.0, vec![]));
+        assert_eq!(corpus.similarity("stop", "empty"), (0.0, vec![]));
+        assert_eq!(corpus.similarity("a", "missing"), (0.0, vec![]));
+        assert_eq!(Corpus::new([]).similarity("a", "b"), (0.0, vec![]));
+    }
+}
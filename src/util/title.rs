@@ -0,0 +1,25 @@
+use unicode_normalization::UnicodeNormalization;
+
+/// Key used to match and deduplicate titles: Unicode NFC, trimmed, inner
+/// whitespace collapsed to single spaces and lowercased. The title itself is
+/// stored as written.
+pub fn normalize(title: &str) -> String {
+    title.nfc()
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_ignores_case_spacing_and_composition() {
+        assert_eq!(normalize("  Some   Title\t"), "some title");
+        assert_eq!(normalize("CAFE\u{301}"), normalize("café"));
+        assert_eq!(normalize("Zettel-Kasten"), "zettel-kasten");
+    }
+}
@@ -0,0 +1,59 @@
+use regex::{Captures, Regex};
+
+use super::title::normalize;
+
+/// Matches `[[Title]]` and `[[Title|label]]`.
+const WIKI_LINK_PATTERN: &str = r"\[\[(?<title>[^\]|]+)(?<label>\|[^\]]*)?\]\]";
+
+/// Replaces wiki-style links to `old` with links to `new`, keeping labels.
+/// Returns `None` when `text` doesn't mention `old`.
+pub fn rewrite_links(text: &str, old: &str, new: &str) -> Option<String> {
+    let re = Regex::new(WIKI_LINK_PATTERN).ok()?;
+    let old = normalize(old);
+    let mut changed = false;
+
+    let rewritten = re.replace_all(text, |caps: &Captures| {
+        if normalize(&caps["title"]) != old {
+            return caps[0].to_string()
+        }
+
+        changed = true;
+        let label = caps.name("label").map(|l| l.as_str()).unwrap_or_default();
+        format!("[[{new}{label}]]")
+    });
+
+    changed.then(|| rewritten.into_owned())
+}
+
+/// Replaces every wiki-style link with what `f` returns for its title and
+/// label, the label without its leading `|`.
+pub fn replace_links(text: &str, f: impl Fn(&str, Option<&str>) -> String) -> String {
+    let Ok(re) = Regex::new(WIKI_LINK_PATTERN) else {
+        return text.to_string()
+    };
+
+    re.replace_all(text, |caps: &Captures| {
+        let label = caps.name("label").map(|l| &l.as_str()[1..]);
+        f(caps["title"].trim(), label)
+    }).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrite_links_keeps_labels_and_other_links() {
+        let text = "[[Old]], [[ old |label]], [[Older]] and [Old]";
+
+        assert_eq!(rewrite_links(text, "OLD", "New").as_deref(), Some("[[New]], [[New|label]], [[Older]] and [Old]"));
+        assert_eq!(rewrite_links("[[Older]]", "Old", "New"), None);
+    }
+
+    #[test]
+    fn replace_links_passes_title_and_label() {
+        let replaced = replace_links("[[ A ]] and [[B|see b]]", |title, label| format!("<{title}:{}>", label.unwrap_or("-")));
+
+        assert_eq!(replaced, "<A:-> and <B:see b>");
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::BTreeSet;
+
+use serde::Serialize;
+
+use crate::{models::{note::Note, sources::Source}, util::NoteFromMd};
+
+use super::{error::VaultError, NoteReferences, Vault};
+
+/// What writing a note changed, see `Vault::write_note`.
+#[derive(Debug, Clone, Serialize)]
+pub struct NoteChanges {
+    pub note: Note,
+    /// The note as it was, `None` if it was created.
+    pub before: Option<Note>,
+    pub internal_added: Vec<Note>,
+    pub internal_removed: Vec<Note>,
+    pub external_added: Vec<Source>,
+    pub external_removed: Vec<Source>,
+    /// Sources which did not exist before and were created for the note.
+    pub new_sources: Vec<Source>,
+}
+
+impl NoteChanges {
+    pub fn created(&self) -> bool {
+        self.before.is_none()
+    }
+
+    /// Old and new title if the title changed.
+    pub fn title_change(&self) -> Option<(&str, &str)> {
+        self.before.as_ref()
+            .filter(|before| before.title != self.note.title)
+            .map(|before| (before.title.as_str(), self.note.title.as_str()))
+    }
+
+    /// Lines of the contents added and removed, ignoring blank lines. Lines
+    /// kept are those of the longest common subsequence of old and new lines.
+    pub fn line_changes(&self) -> (usize, usize) {
+        let lines = |contents: &str| -> Vec<String> {
+            contents.lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| line.trim_end().to_string())
+                .collect()
+        };
+        let old = self.before.as_ref().map(|before| lines(&before.contents)).unwrap_or_default();
+        let new = lines(&self.note.contents);
+
+        let mut previous = vec![0; new.len() + 1];
+        for old_line in &old {
+            let mut row = vec![0; new.len() + 1];
+            for (j, new_line) in new.iter().enumerate() {
+                row[j + 1] = match old_line == new_line {
+                    true => previous[j] + 1,
+                    false => row[j].max(previous[j + 1]),
+                };
+            }
+            previous = row;
+        }
+        let kept = previous[new.len()];
+
+        (new.len() - kept, old.len() - kept)
+    }
+
+    /// Whether the write changed nothing but the revision.
+    pub fn is_empty(&self) -> bool {
+        !self.created()
+            && self.title_change().is_none()
+            && self.line_changes() == (0, 0)
+            && self.internal_added.is_empty()
+            && self.internal_removed.is_empty()
+            && self.external_added.is_empty()
+            && self.external_removed.is_empty()
+    }
+}
+
+impl Vault {
+    /// Runs `write`, which adds or updates the note `note_from_md` describes,
+    /// and compares the note and its references before and after. With
+    /// `dry_run` every check still runs but nothing is kept.
+    pub fn write_note<F>(&mut self, note_from_md: NoteFromMd, dry_run: bool, write: F) -> Result<NoteChanges, VaultError>
+        where F: FnOnce(&mut Self, NoteFromMd) -> Result<Note, VaultError> {
+        let before = self.existing_note(&note_from_md)?;
+        let before_references = match &before {
+            Some(note) => self.references_of(&note.id)?,
+            None => NoteReferences { internal: vec![], external: vec![] },
+        };
+        let sources_before: BTreeSet<String> = self.storage.list_sources()?
+            .into_iter()
+            .map(|source| source.id)
+            .collect();
+
+        self.storage.begin()?;
+        let changes = write(self, note_from_md).and_then(|note| {
+            // A note written next to the one found, rather than over it, is new.
+            let (before, before_references) = match before {
+                Some(before) if before.id == note.id => (Some(before), before_references),
+                _ => (None, NoteReferences { internal: vec![], external: vec![] }),
+            };
+            let after = self.references_of(&note.id)?;
+            let new_sources = self.storage.list_sources()?
+                .into_iter()
+                .filter(|source| !sources_before.contains(&source.id))
+                .collect();
+
+            Ok(NoteChanges {
+                internal_added: added(&after.internal, &before_references.internal, |n| &n.id),
+                internal_removed: added(&before_references.internal, &after.internal, |n| &n.id),
+                external_added: added(&after.external, &before_references.external, |s| &s.id),
+                external_removed: added(&before_references.external, &after.external, |s| &s.id),
+                new_sources,
+                note,
+                before,
+            })
+        });
+
+        match (&changes, dry_run) {
+            (Ok(_), false) => self.storage.commit()?,
+            _ => self.storage.rollback()?,
+        }
+
+        changes
+    }
+}
+
+/// Items of `items` missing from `other`.
+fn added<T: Clone>(items: &[T], other: &[T], id: impl Fn(&T) -> &String) -> Vec<T> {
+    items.iter()
+        .filter(|item| !other.iter().any(|o| id(o) == id(item)))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changes(before: Option<&str>, after: &str) -> NoteChanges {
+        let note = |contents: &str| Note {
+            id: "id".to_string(),
+            title: "Title".to_string(),
+            contents: contents.to_string(),
+            sequence: None,
+            revision: 0,
+        };
+
+        NoteChanges {
+            note: note(after),
+            before: before.map(note),
+            internal_added: vec![],
+            internal_removed: vec![],
+            external_added: vec![],
+            external_removed: vec![],
+            new_sources: vec![],
+        }
+    }
+
+    #[test]
+    fn line_changes_count_lines_outside_the_common_subsequence() {
+        assert_eq!(changes(None, "\na\n\nb\n").line_changes(), (2, 0));
+        assert_eq!(changes(Some("a\nb\nc"), "a\nx\nc\nd").line_changes(), (2, 1));
+        assert_eq!(changes(Some("a\nb"), "b\na").line_changes(), (1, 1));
+    }
+
+    #[test]
+    fn line_changes_ignore_blank_lines_and_trailing_spaces() {
+        let unchanged = changes(Some("a\nb"), "a  \n\n\nb\n");
+
+        assert_eq!(unchanged.line_changes(), (0, 0));
+        assert!(unchanged.is_empty());
+        assert!(!changes(None, "a").is_empty());
+    }
+}
@@ -0,0 +1,70 @@
+use crate::{models::error::DbError, util::error::UtilError};
+
+#[derive(thiserror::Error, Debug)]
+pub enum VaultError {
+    #[error("Note with provided id not found")]
+    NoteNotFound,
+
+    #[error("Invalid note id: {0}")]
+    InvalidId(String),
+
+    #[error("Source with provided id not found")]
+    SourceNotFound,
+
+    #[error("The title of note cannot be empty!")]
+    NoteTitleEmpty,
+
+    #[error("Note titled {0} already exists")]
+    TitleTaken(String),
+
+    #[error("{0} is already an alias of note {1}")]
+    AliasTaken(String, String),
+
+    #[error("Source titled {0} already exists")]
+    SourceTitleTaken(String),
+
+    #[error("The title of source cannot be empty!")]
+    SourceTitleEmpty,
+
+    #[error("Source is still cited by {0} note(s), use --force to delete it anyway")]
+    SourceInUse(usize),
+
+    #[error("Note was changed meanwhile, expected revision {0} but it is at {1}")]
+    RevisionMismatch(i64, i64),
+
+    #[error("Cannot merge an object into itself")]
+    MergeIntoItself,
+
+    #[error("Provided reference must have either title or id")]
+    InvalidReference,
+
+    #[error("Reference with provided title: {0} does not exist.")]
+    ReferenceDoesNotExist(String),
+
+    #[error("Alias {0} is ambiguous, it belongs to: {1}")]
+    AmbiguousAlias(String, String),
+
+    #[error("Invalid sequence position: {0}, expected something like 1, 1a or 1a1")]
+    InvalidSequence(String),
+
+    #[error("Sequence position {0} is already taken")]
+    SequenceTaken(String),
+
+    #[error("Note has no sequence position to branch from")]
+    NoteHasNoSequence,
+
+    #[error("Cannot find a free id, try a longer id_format")]
+    CannotGenerateId,
+
+    #[error("Cannot read or write the operation log: {0}")]
+    InvalidOperation(String),
+
+    #[error("{0} was changed after {1}, use --force to overwrite the change")]
+    ChangedSince(String, String),
+
+    #[error(transparent)]
+    Db(#[from] DbError),
+
+    #[error(transparent)]
+    Util(#[from] UtilError),
+}
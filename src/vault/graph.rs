@@ -0,0 +1,357 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::models::note::NoteListItem;
+
+use super::{error::VaultError, Vault};
+
+/// Internal references of the whole vault as an adjacency list.
+#[derive(Debug, Default)]
+pub struct Graph {
+    notes: BTreeMap<String, NoteListItem>,
+    outgoing: BTreeMap<String, BTreeSet<String>>,
+    incoming: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// Link counts of a single note.
+#[derive(Debug)]
+pub struct Hub {
+    pub note: NoteListItem,
+    pub incoming: usize,
+}
+
+/// Maintenance report over the note graph, every list is ordered by title
+/// except `hubs` (most linked-to first) and `components` (largest first).
+#[derive(Debug, Default)]
+pub struct GraphStats {
+    pub notes: usize,
+    pub links: usize,
+    /// Notes without incoming or outgoing links.
+    pub orphans: Vec<NoteListItem>,
+    /// Notes linked to by at least the minimum number of notes asked for.
+    pub hubs: Vec<Hub>,
+    /// Notes which link to other notes but are never linked to.
+    pub outward_only: Vec<NoteListItem>,
+    /// Notes which are linked to but don't link to any note.
+    pub dead_ends: Vec<NoteListItem>,
+    /// Weakly connected components, orphans included.
+    pub components: Vec<Vec<NoteListItem>>,
+}
+
+impl Graph {
+    pub fn note(&self, id: &str) -> Option<&NoteListItem> {
+        self.notes.get(id)
+    }
+
+    pub fn notes(&self) -> impl Iterator<Item = &NoteListItem> {
+        self.notes.values()
+    }
+
+    /// Ids of the notes `id` links to.
+    pub fn outgoing(&self, id: &str) -> impl Iterator<Item = &String> {
+        self.outgoing.get(id).into_iter().flatten()
+    }
+
+    /// Ids of the notes linking to `id`.
+    pub fn incoming(&self, id: &str) -> impl Iterator<Item = &String> {
+        self.incoming.get(id).into_iter().flatten()
+    }
+
+    /// Ids linked to or from `id`.
+    pub fn neighbours(&self, id: &str) -> BTreeSet<&String> {
+        self.outgoing(id).chain(self.incoming(id)).collect()
+    }
+
+    /// Shortest chain of references leading from `from` to `to`, both ends included.
+    /// With `undirected` a link can be followed against its direction.
+    pub fn path(&self, from: &str, to: &str, undirected: bool) -> Option<Vec<NoteListItem>> {
+        let from = self.notes.get_key_value(from)?.0;
+        let mut previous: BTreeMap<&String, &String> = BTreeMap::new();
+        let mut queue = VecDeque::from([from]);
+
+        while let Some(current) = queue.pop_front() {
+            if current == to {
+                let mut path = vec![self.notes[current].clone()];
+                let mut step = current;
+                while let Some(&before) = previous.get(step) {
+                    path.push(self.notes[before].clone());
+                    step = before;
+                }
+                path.reverse();
+
+                return Some(path)
+            }
+
+            let next: BTreeSet<&String> = match undirected {
+                true => self.neighbours(current),
+                false => self.outgoing(current).collect()
+            };
+
+            for id in next {
+                if id != from && !previous.contains_key(id) {
+                    previous.insert(id, current);
+                    queue.push_back(id);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Notes within `depth` hops of `id` following links in either direction,
+    /// with their distance, nearest first. The note itself is not included.
+    pub fn around(&self, id: &str, depth: usize) -> Vec<(NoteListItem, usize)> {
+        let Some((id, _)) = self.notes.get_key_value(id) else {
+            return vec![]
+        };
+
+        let mut distances: BTreeMap<&String, usize> = BTreeMap::from([(id, 0)]);
+        let mut queue = VecDeque::from([id]);
+        let mut around = vec![];
+
+        while let Some(current) = queue.pop_front() {
+            let distance = distances[current];
+            if distance == depth {
+                continue
+            }
+
+            for next in self.neighbours(current) {
+                if !distances.contains_key(next) {
+                    distances.insert(next, distance + 1);
+                    around.push((self.notes[next].clone(), distance + 1));
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        around.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.title.cmp(&b.0.title)));
+
+        around
+    }
+
+    /// Stats of the graph, `hubs` being at most `max_hubs` notes with at
+    /// least `min_incoming` incoming links.
+    pub fn stats(&self, max_hubs: usize, min_incoming: usize) -> GraphStats {
+        let links = self.outgoing.values().map(BTreeSet::len).sum();
+
+        let mut orphans = vec![];
+        let mut outward_only = vec![];
+        let mut dead_ends = vec![];
+        let mut hubs = vec![];
+        for note in self.notes.values() {
+            let incoming = self.incoming(&note.id).count();
+            let outgoing = self.outgoing(&note.id).count();
+
+            match (incoming, outgoing) {
+                (0, 0) => orphans.push(note.clone()),
+                (0, _) => outward_only.push(note.clone()),
+                (_, 0) => dead_ends.push(note.clone()),
+                _ => { }
+            }
+
+            if incoming > 0 && incoming >= min_incoming {
+                hubs.push(Hub { note: note.clone(), incoming });
+            }
+        }
+
+        hubs.sort_by(|a, b| b.incoming.cmp(&a.incoming).then_with(|| a.note.title.cmp(&b.note.title)));
+        hubs.truncate(max_hubs);
+
+        GraphStats {
+            notes: self.notes.len(),
+            links,
+            orphans: Self::by_title(orphans),
+            hubs,
+            outward_only: Self::by_title(outward_only),
+            dead_ends: Self::by_title(dead_ends),
+            components: self.components(),
+        }
+    }
+
+    fn components(&self) -> Vec<Vec<NoteListItem>> {
+        let mut seen = BTreeSet::new();
+        let mut components = vec![];
+
+        for id in self.notes.keys() {
+            if !seen.insert(id) {
+                continue
+            }
+
+            let mut component = vec![];
+            let mut stack = vec![id];
+            while let Some(current) = stack.pop() {
+                component.push(self.notes[current].clone());
+                for next in self.neighbours(current) {
+                    if seen.insert(next) {
+                        stack.push(next);
+                    }
+                }
+            }
+
+            components.push(Self::by_title(component));
+        }
+
+        components.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a[0].title.cmp(&b[0].title)));
+
+        components
+    }
+
+    fn by_title(mut notes: Vec<NoteListItem>) -> Vec<NoteListItem> {
+        notes.sort_by(|a, b| a.title.cmp(&b.title));
+        notes
+    }
+}
+
+impl Vault {
+    /// Builds the graph of internal references, self references are ignored.
+    pub fn graph(&self) -> Result<Graph, VaultError> {
+        let mut graph = Graph::default();
+
+        for note in self.storage.list_notes()? {
+            graph.notes.insert(note.id.clone(), note);
+        }
+
+        for reference in self.storage.list_internal_references()? {
+            if reference.note_id == reference.reference_id {
+                continue
+            }
+
+            graph.outgoing.entry(reference.note_id.clone())
+                .or_default()
+                .insert(reference.reference_id.clone());
+            graph.incoming.entry(reference.reference_id)
+                .or_default()
+                .insert(reference.note_id);
+        }
+
+        Ok(graph)
+    }
+
+    pub fn graph_stats(&self, max_hubs: usize, min_incoming: usize) -> Result<GraphStats, VaultError> {
+        Ok(self.graph()?.stats(max_hubs, min_incoming))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::parse::md_to_new_note;
+
+    use super::*;
+
+    /// `B` links to `A` and `Idea`, `A` links to `Idea`, `C` and `E` are isolated.
+    fn vault() -> Vault {
+        let mut vault = Vault::in_memory();
+        for md in [
+            "# Idea\n",
+            "# A\n## References\n### Internal\n1. Idea\n",
+            "# B\n## References\n### Internal\n1. Idea\n2. A\n",
+            "# C\n",
+            "# E\n",
+        ] {
+            vault.add_note(md_to_new_note(md.to_string()).unwrap()).unwrap();
+        }
+
+        vault
+    }
+
+    fn titles(notes: &[NoteListItem]) -> Vec<&str> {
+        notes.iter().map(|note| note.title.as_str()).collect()
+    }
+
+    #[test]
+    fn stats_classify_notes_by_links() {
+        let stats = vault().graph_stats(10, 2).unwrap();
+
+        assert_eq!(stats.notes, 5);
+        assert_eq!(stats.links, 3);
+        assert_eq!(titles(&stats.orphans), ["C", "E"]);
+        assert_eq!(titles(&stats.outward_only), ["B"]);
+        assert_eq!(titles(&stats.dead_ends), ["Idea"]);
+        let components: Vec<Vec<&str>> = stats.components.iter().map(|component| titles(component)).collect();
+        assert_eq!(components, [vec!["A", "B", "Idea"], vec!["C"], vec!["E"]]);
+    }
+
+    #[test]
+    fn hubs_need_the_minimum_incoming_links() {
+        let vault = vault();
+        let hubs = |max_hubs, min_incoming| -> Vec<(String, usize)> {
+            vault.graph_stats(max_hubs, min_incoming).unwrap().hubs
+                .into_iter()
+                .map(|hub| (hub.note.title, hub.incoming))
+                .collect()
+        };
+
+        assert_eq!(hubs(10, 2), [("Idea".to_string(), 2)]);
+        assert_eq!(hubs(10, 0), [("Idea".to_string(), 2), ("A".to_string(), 1)]);
+        assert_eq!(hubs(1, 1), [("Idea".to_string(), 2)]);
+        assert!(hubs(10, 3).is_empty());
+    }
+
+    #[test]
+    fn stats_of_an_empty_vault_are_empty() {
+        let stats = Vault::in_memory().graph_stats(10, 2).unwrap();
+
+        assert_eq!(stats.notes, 0);
+        assert!(stats.orphans.is_empty() && stats.hubs.is_empty() && stats.components.is_empty());
+    }
+
+    /// Chain where every note links to the one before: `Four` -> `Three` -> `Two` -> `One`.
+    fn chain() -> (Vault, Vec<String>) {
+        let mut vault = Vault::in_memory();
+        let mut ids: Vec<String> = vec![];
+        for title in ["One", "Two", "Three", "Four"] {
+            let md = match ids.last() {
+                Some(previous) => format!("# {title}\n## References\n### Internal\n1. [{previous}] Previous\n"),
+                None => format!("# {title}\n"),
+            };
+            ids.push(vault.add_note(md_to_new_note(md).unwrap()).unwrap().id);
+        }
+
+        (vault, ids)
+    }
+
+    #[test]
+    fn path_follows_links_in_their_direction() {
+        let (vault, ids) = chain();
+        let graph = vault.graph().unwrap();
+
+        let path = graph.path(&ids[3], &ids[0], false).unwrap();
+
+        assert_eq!(titles(&path), ["Four", "Three", "Two", "One"]);
+        assert!(graph.path(&ids[0], &ids[3], false).is_none());
+        assert_eq!(titles(&graph.path(&ids[0], &ids[2], true).unwrap()), ["One", "Two", "Three"]);
+    }
+
+    #[test]
+    fn path_takes_the_shortest_route() {
+        let vault = vault();
+        let graph = vault.graph().unwrap();
+        let id = |title| vault.find_note(title).unwrap().id;
+
+        assert_eq!(titles(&graph.path(&id("B"), &id("Idea"), false).unwrap()), ["B", "Idea"]);
+        assert!(graph.path(&id("C"), &id("Idea"), true).is_none());
+        assert!(graph.path("missing", &id("Idea"), true).is_none());
+    }
+
+    #[test]
+    fn path_from_a_note_to_itself_is_the_note() {
+        let vault = vault();
+        let graph = vault.graph().unwrap();
+        let c = vault.find_note("C").unwrap().id;
+
+        assert_eq!(titles(&graph.path(&c, &c, false).unwrap()), ["C"]);
+    }
+
+    #[test]
+    fn around_stops_at_depth() {
+        let (vault, ids) = chain();
+        let graph = vault.graph().unwrap();
+        let around = |depth| -> Vec<(String, usize)> {
+            graph.around(&ids[1], depth).into_iter().map(|(note, distance)| (note.title, distance)).collect()
+        };
+
+        assert!(around(0).is_empty());
+        assert_eq!(around(1), [("One".to_string(), 1), ("Three".to_string(), 1)]);
+        assert_eq!(around(5), [("One".to_string(), 1), ("Three".to_string(), 1), ("Four".to_string(), 2)]);
+        assert!(graph.around("missing", 5).is_empty());
+    }
+}
@@ -0,0 +1,432 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config::Config, models::{aliases::Alias, external::ExternalReference, internal::InternalReference, note::Note, operation::Operation, sources::Source}, storage::Storage};
+
+use super::{error::VaultError, Vault};
+
+/// A note with its aliases, the internal references from and to it and the
+/// external references from it. `note` is `None` if it does not exist.
+#[derive(Debug, Serialize, Deserialize)]
+struct NoteState {
+    id: String,
+    note: Option<Note>,
+    aliases: Vec<String>,
+    internal: Vec<InternalReference>,
+    external: Vec<ExternalReference>,
+}
+
+/// A source with the external references to it, `source` is `None` if it
+/// does not exist.
+#[derive(Debug, Serialize, Deserialize)]
+struct SourceState {
+    id: String,
+    source: Option<Source>,
+    external: Vec<ExternalReference>,
+}
+
+/// Notes and sources an operation changed, as they were before or after it.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Snapshot {
+    notes: Vec<NoteState>,
+    sources: Vec<SourceState>,
+}
+
+impl NoteState {
+    fn missing(id: &str) -> Self {
+        Self { id: id.to_string(), note: None, aliases: vec![], internal: vec![], external: vec![] }
+    }
+
+    /// Whether both hold the same note, ignoring revisions and the ids of
+    /// references which only differ when a reference was removed and added again.
+    fn same(&self, other: &Self) -> bool {
+        let note = |state: &Self| state.note.as_ref().map(|n| (n.title.clone(), n.contents.clone(), n.sequence.clone()));
+
+        note(self) == note(other)
+            && self.aliases == other.aliases
+            && pairs(self.internal.iter().map(|r| (&r.note_id, &r.reference_id))) == pairs(other.internal.iter().map(|r| (&r.note_id, &r.reference_id)))
+            && pairs(self.external.iter().map(|r| (&r.note_id, &r.reference_id))) == pairs(other.external.iter().map(|r| (&r.note_id, &r.reference_id)))
+    }
+}
+
+impl SourceState {
+    fn missing(id: &str) -> Self {
+        Self { id: id.to_string(), source: None, external: vec![] }
+    }
+
+    fn same(&self, other: &Self) -> bool {
+        self.source.as_ref().map(|s| &s.title) == other.source.as_ref().map(|s| &s.title)
+            && pairs(self.external.iter().map(|r| (&r.note_id, &r.reference_id))) == pairs(other.external.iter().map(|r| (&r.note_id, &r.reference_id)))
+    }
+}
+
+impl Vault {
+    /// Runs `f`, which changes the notes `notes` and the sources `sources` and
+    /// may create new ones, and logs it as `command` with the state before and
+    /// after of every one of them which changed, so `undo` and `redo` can
+    /// restore it. Nothing is logged if nothing changed.
+    pub fn record<T, E, F>(&mut self, command: &str, notes: &[String], sources: &[String], f: F) -> Result<T, E>
+        where F: FnOnce(&mut Self) -> Result<T, E>, E: From<VaultError> {
+        let notes_before = self.note_ids()?;
+        let sources_before = self.source_ids()?;
+        let before = Self::snapshot(self.storage.as_ref(), notes, sources)?;
+
+        self.storage.begin().map_err(VaultError::from)?;
+        let result = f(self).and_then(|value| {
+            let new_notes: Vec<String> = self.note_ids()?.difference(&notes_before).cloned().collect();
+            let new_sources: Vec<String> = self.source_ids()?.difference(&sources_before).cloned().collect();
+
+            let notes: Vec<String> = notes.iter().cloned().chain(new_notes).collect();
+            let sources: Vec<String> = sources.iter().cloned().chain(new_sources).collect();
+            let after = Self::snapshot(self.storage.as_ref(), &notes, &sources)?;
+
+            self.log(command, before, after)?;
+            Ok(value)
+        });
+
+        match result {
+            Ok(_) => self.storage.commit().map_err(VaultError::from)?,
+            Err(_) => self.storage.rollback().map_err(VaultError::from)?,
+        }
+
+        result
+    }
+
+    /// Restores the state before the newest operation which is not undone yet.
+    /// Unless `force`, fails if anything it changed was changed since.
+    pub fn undo(&mut self, force: bool) -> Result<Option<Operation>, VaultError> {
+        let Some(operation) = self.storage.last_done_operation()? else {
+            return Ok(None)
+        };
+
+        if !force {
+            self.check_unchanged(&parse(&operation.after)?, &operation.command)?;
+        }
+        self.restore(&parse(&operation.before)?, operation.id, true)?;
+
+        Ok(Some(operation))
+    }
+
+    /// Restores the state after the oldest undone operation. Unless `force`,
+    /// fails if anything it changed was changed since it was undone.
+    pub fn redo(&mut self, force: bool) -> Result<Option<Operation>, VaultError> {
+        let Some(operation) = self.storage.first_undone_operation()? else {
+            return Ok(None)
+        };
+
+        if !force {
+            self.check_unchanged(&parse(&operation.before)?, &operation.command)?;
+        }
+        self.restore(&parse(&operation.after)?, operation.id, false)?;
+
+        Ok(Some(operation))
+    }
+
+    /// The `limit` most recent operations, newest first.
+    pub fn operations(&self, limit: usize) -> Result<Vec<Operation>, VaultError> {
+        Ok(self.storage.list_operations(limit)?)
+    }
+
+    /// Logs the states of `after` which differ from those in `before`.
+    fn log(&mut self, command: &str, mut before: Snapshot, after: Snapshot) -> Result<(), VaultError> {
+        let mut changed = (Snapshot::default(), Snapshot::default());
+
+        for state in after.notes {
+            let old = match before.notes.iter().position(|old| old.id == state.id) {
+                Some(index) => before.notes.remove(index),
+                None => NoteState::missing(&state.id),
+            };
+            if !old.same(&state) {
+                changed.0.notes.push(old);
+                changed.1.notes.push(state);
+            }
+        }
+
+        for state in after.sources {
+            let old = match before.sources.iter().position(|old| old.id == state.id) {
+                Some(index) => before.sources.remove(index),
+                None => SourceState::missing(&state.id),
+            };
+            if !old.same(&state) {
+                changed.0.sources.push(old);
+                changed.1.sources.push(state);
+            }
+        }
+
+        if changed.1.notes.is_empty() && changed.1.sources.is_empty() {
+            return Ok(())
+        }
+
+        let before = serde_json::to_string(&changed.0).map_err(|e| VaultError::InvalidOperation(e.to_string()))?;
+        let after = serde_json::to_string(&changed.1).map_err(|e| VaultError::InvalidOperation(e.to_string()))?;
+        self.storage.delete_undone_operations()?;
+        self.storage.add_operation(command, &before, &after)?;
+
+        Ok(())
+    }
+
+    /// Fails if a note or source of `expected` is not the way it describes,
+    /// because something else changed it since the operation `command`.
+    fn check_unchanged(&self, expected: &Snapshot, command: &str) -> Result<(), VaultError> {
+        let notes: Vec<String> = expected.notes.iter().map(|state| state.id.clone()).collect();
+        let sources: Vec<String> = expected.sources.iter().map(|state| state.id.clone()).collect();
+        let current = Self::snapshot(self.storage.as_ref(), &notes, &sources)?;
+
+        for (expected, current) in expected.notes.iter().zip(&current.notes) {
+            if !expected.same(current) {
+                let title = current.note.as_ref().or(expected.note.as_ref()).map_or(&current.id, |note| &note.title);
+                return Err(VaultError::ChangedSince(format!("Note {title}"), command.to_string()))
+            }
+        }
+
+        for (expected, current) in expected.sources.iter().zip(&current.sources) {
+            if !expected.same(current) {
+                let title = current.source.as_ref().or(expected.source.as_ref()).map_or(&current.id, |source| &source.title);
+                return Err(VaultError::ChangedSince(format!("Source {title}"), command.to_string()))
+            }
+        }
+
+        Ok(())
+    }
+
+    fn note_ids(&self) -> Result<BTreeSet<String>, VaultError> {
+        Ok(self.storage.list_notes()?.into_iter().map(|note| note.id).collect())
+    }
+
+    fn source_ids(&self) -> Result<BTreeSet<String>, VaultError> {
+        Ok(self.storage.list_sources()?.into_iter().map(|source| source.id).collect())
+    }
+
+    fn snapshot(storage: &dyn Storage, notes: &[String], sources: &[String]) -> Result<Snapshot, VaultError> {
+        let mut internal = storage.list_internal_references()?;
+        let mut external = storage.list_external_references()?;
+        internal.sort_by(|a, b| a.id.cmp(&b.id));
+        external.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let notes = unique(notes).into_iter()
+            .map(|id| Ok(NoteState {
+                note: storage.get_note(&id)?,
+                aliases: storage.aliases_of(&id)?,
+                internal: internal.iter().filter(|r| r.note_id == id || r.reference_id == id).cloned().collect(),
+                external: external.iter().filter(|r| r.note_id == id).cloned().collect(),
+                id,
+            }))
+            .collect::<Result<Vec<NoteState>, VaultError>>()?;
+
+        let sources = unique(sources).into_iter()
+            .map(|id| Ok(SourceState {
+                source: storage.get_source(&id)?,
+                external: external.iter().filter(|r| r.reference_id == id).cloned().collect(),
+                id,
+            }))
+            .collect::<Result<Vec<SourceState>, VaultError>>()?;
+
+        Ok(Snapshot { notes, sources })
+    }
+
+    /// Puts every note and source of the snapshot back the way it was and
+    /// marks the operation `undone` or not. Restored notes get a new revision
+    /// so edits based on the replaced version are detected.
+    fn restore(&mut self, snapshot: &Snapshot, operation: i64, undone: bool) -> Result<(), VaultError> {
+        let config = &self.config;
+        Self::transaction(self.storage.as_mut(), |storage| {
+            Self::restore_in(snapshot, config, storage)?;
+            storage.set_operation_undone(operation, undone)?;
+            Ok(())
+        })
+    }
+
+    fn restore_in(snapshot: &Snapshot, config: &Config, storage: &mut dyn Storage) -> Result<(), VaultError> {
+        let mut revisions = vec![];
+        for state in &snapshot.notes {
+            storage.delete_internal_references_of(&state.id)?;
+            storage.delete_internal_references_to(&state.id)?;
+            storage.delete_external_references_of(&state.id)?;
+            storage.delete_aliases_of(&state.id)?;
+            revisions.push(storage.get_note(&state.id)?.map(|note| note.revision));
+        }
+        for state in &snapshot.sources {
+            storage.delete_external_references_to(&state.id)?;
+        }
+
+        for (state, revision) in snapshot.notes.iter().zip(&revisions) {
+            if revision.is_some() {
+                storage.delete_note(&state.id)?;
+            }
+        }
+        for state in &snapshot.sources {
+            if storage.get_source(&state.id)?.is_some() {
+                storage.delete_source(&state.id)?;
+            }
+        }
+
+        for source in snapshot.sources.iter().filter_map(|state| state.source.as_ref()) {
+            storage.add_source(source)?;
+        }
+        for (state, revision) in snapshot.notes.iter().zip(&revisions) {
+            if let Some(note) = &state.note {
+                let mut note = note.clone();
+                if let Some(revision) = revision {
+                    note.revision = note.revision.max(*revision) + 1;
+                }
+                storage.add_note(&note)?;
+            }
+        }
+
+        for state in snapshot.notes.iter().filter(|state| state.note.is_some()) {
+            for alias in &state.aliases {
+                storage.add_alias(&Alias::new(Self::new_id(config, storage)?, state.id.clone(), alias.clone()))?;
+            }
+        }
+
+        let mut added = BTreeSet::new();
+        for reference in snapshot.notes.iter().flat_map(|state| &state.internal) {
+            if added.insert(reference.id.clone()) {
+                storage.add_internal_reference(reference)?;
+            }
+        }
+        for reference in snapshot.notes.iter().flat_map(|state| &state.external)
+            .chain(snapshot.sources.iter().flat_map(|state| &state.external)) {
+            if added.insert(reference.id.clone()) {
+                storage.add_external_reference(reference)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn parse(snapshot: &str) -> Result<Snapshot, VaultError> {
+    serde_json::from_str(snapshot).map_err(|e| VaultError::InvalidOperation(e.to_string()))
+}
+
+/// Note and reference ids of references, sorted.
+fn pairs<'a>(references: impl Iterator<Item = (&'a String, &'a String)>) -> Vec<(&'a String, &'a String)> {
+    let mut pairs: Vec<_> = references.collect();
+    pairs.sort();
+    pairs
+}
+
+fn unique(ids: &[String]) -> Vec<String> {
+    let mut seen = BTreeSet::new();
+
+    ids.iter()
+        .filter(|id| seen.insert(*id))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{util::{parse::md_to_new_note, NoteFromMd}, vault::SetOutcome};
+
+    use super::*;
+
+    fn note(md: &str) -> NoteFromMd {
+        md_to_new_note(md.to_string()).unwrap()
+    }
+
+    fn set(vault: &mut Vault, md: &str) -> Note {
+        let note_from_md = note(md);
+        let existing: Vec<String> = vault.existing_note(&note_from_md).unwrap().into_iter().map(|note| note.id).collect();
+
+        let outcome = vault.record(&format!("set {}", note_from_md.title), &existing, &[], |vault| vault.set_note(note_from_md)).unwrap();
+        match outcome {
+            SetOutcome::Added(note) | SetOutcome::Updated(note) => note,
+        }
+    }
+
+    fn contents(vault: &Vault, id: &str) -> String {
+        vault.get_note(id).unwrap().contents.trim().to_string()
+    }
+
+    #[test]
+    fn undo_and_redo_restore_notes() {
+        let mut vault = Vault::in_memory();
+        let a = set(&mut vault, "# A\n\nv1\n");
+        set(&mut vault, &format!("# [{}] A\n\nv2\n", a.id));
+
+        assert_eq!(vault.undo(false).unwrap().unwrap().command, "set A");
+        assert_eq!(contents(&vault, &a.id), "v1");
+
+        vault.undo(false).unwrap();
+        assert!(matches!(vault.get_note(&a.id), Err(VaultError::NoteNotFound)));
+        assert!(vault.undo(false).unwrap().is_none());
+
+        vault.redo(false).unwrap();
+        vault.redo(false).unwrap();
+        assert_eq!(contents(&vault, &a.id), "v2");
+        assert!(vault.redo(false).unwrap().is_none());
+    }
+
+    #[test]
+    fn writes_changing_nothing_are_not_logged() {
+        let mut vault = Vault::in_memory();
+        let a = set(&mut vault, "# A\n\nv1\n");
+
+        set(&mut vault, &format!("# [{}] A\n\nv1\n", a.id));
+        set(&mut vault, &format!("# [{}] A\n\nv1\n", a.id));
+
+        assert_eq!(vault.operations(10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn undo_refuses_to_overwrite_later_changes_unless_forced() {
+        let mut vault = Vault::in_memory();
+        let a = set(&mut vault, "# A\n\nv1\n");
+        set(&mut vault, &format!("# [{}] A\n\nv2\n", a.id));
+        // Changed without being logged, like another program writing to the database.
+        vault.update_note(note(&format!("# [{}] A\n\nv3\n", a.id))).unwrap();
+
+        let refused = vault.undo(false);
+
+        assert!(matches!(refused, Err(VaultError::ChangedSince(what, command)) if what == "Note A" && command == "set A"));
+        assert_eq!(contents(&vault, &a.id), "v3");
+
+        vault.undo(true).unwrap();
+        assert_eq!(contents(&vault, &a.id), "v1");
+    }
+
+    #[test]
+    fn redo_refuses_to_overwrite_changes_made_after_undo() {
+        let mut vault = Vault::in_memory();
+        let a = set(&mut vault, "# A\n\nv1\n");
+        set(&mut vault, &format!("# [{}] A\n\nv2\n", a.id));
+        vault.undo(false).unwrap();
+        vault.update_note(note(&format!("# [{}] A\n\nv3\n", a.id))).unwrap();
+
+        assert!(matches!(vault.redo(false), Err(VaultError::ChangedSince(..))));
+        assert_eq!(contents(&vault, &a.id), "v3");
+    }
+
+    #[test]
+    fn new_operation_drops_undone_ones() {
+        let mut vault = Vault::in_memory();
+        let a = set(&mut vault, "# A\n\nv1\n");
+        set(&mut vault, &format!("# [{}] A\n\nv2\n", a.id));
+        vault.undo(false).unwrap();
+
+        set(&mut vault, "# B\n\nb\n");
+
+        assert!(vault.redo(false).unwrap().is_none());
+        let commands: Vec<String> = vault.operations(10).unwrap().into_iter().map(|operation| operation.command).collect();
+        assert_eq!(commands, ["set B", "set A"]);
+    }
+
+    #[test]
+    fn undo_restores_references_and_created_stubs() {
+        let mut vault = Vault::in_memory();
+        let idea = set(&mut vault, "# Idea\n\nAn idea.\n");
+        let a = set(&mut vault, "# A\n\nv1\n## References\n### Internal\n1. Idea\n");
+        set(&mut vault, &format!("# [{}] A\n\nv2\n## References\n### External\n- Book\n", a.id));
+
+        vault.undo(false).unwrap();
+
+        let references = vault.references_of(&a.id).unwrap();
+        assert_eq!(references.internal.len(), 1);
+        assert_eq!(references.internal[0].id, idea.id);
+        assert!(references.external.is_empty());
+        assert!(vault.list_sources().unwrap().is_empty());
+    }
+}
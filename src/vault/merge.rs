@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+
+use crate::{config::Config, models::{aliases::Alias, note::{Note, NoteListItem}, sources::Source}, storage::Storage, util::title::normalize};
+
+use super::{error::VaultError, Vault};
+
+/// Groups of notes and sources whose titles are equal once normalised,
+/// the first item of every group is the one stored first.
+#[derive(Debug, Default)]
+pub struct Duplicates {
+    pub notes: Vec<Vec<NoteListItem>>,
+    pub sources: Vec<Vec<Source>>,
+}
+
+impl Vault {
+    pub fn find_duplicates(&self) -> Result<Duplicates, VaultError> {
+        let notes = group_by_title(self.storage.list_notes()?, |note| &note.title);
+        let sources = group_by_title(self.storage.list_sources()?, |source| &source.title);
+
+        Ok(Duplicates { notes, sources })
+    }
+
+    /// Merges every group from `find_duplicates` into its first item.
+    pub fn merge_duplicates(&mut self) -> Result<Duplicates, VaultError> {
+        let duplicates = self.find_duplicates()?;
+
+        let config = &self.config;
+        Self::transaction(self.storage.as_mut(), |storage| {
+            for group in &duplicates.notes {
+                for duplicate in &group[1..] {
+                    Self::merge_notes_in(&duplicate.id, &group[0].id, config, storage)?;
+                }
+            }
+
+            for group in &duplicates.sources {
+                for duplicate in &group[1..] {
+                    Self::merge_sources_in(&duplicate.id, &group[0].id, config, storage)?;
+                }
+            }
+
+            Ok(())
+        })?;
+        self.storage.enforce_unique_titles()?;
+
+        Ok(duplicates)
+    }
+
+    /// Moves references, backlinks and aliases of `from` to `into`, appends its
+    /// contents and deletes it.
+    pub fn merge_notes(&mut self, from: &str, into: &str) -> Result<Note, VaultError> {
+        let config = &self.config;
+        Self::transaction(self.storage.as_mut(), |storage| Self::merge_notes_in(from, into, config, storage))
+    }
+
+    /// Repoints every citation of `from` to `into` and deletes `from`.
+    pub fn merge_sources(&mut self, from: &str, into: &str) -> Result<Source, VaultError> {
+        let config = &self.config;
+        Self::transaction(self.storage.as_mut(), |storage| Self::merge_sources_in(from, into, config, storage))
+    }
+
+    fn merge_notes_in(from: &str, into: &str, config: &Config, storage: &mut dyn Storage) -> Result<Note, VaultError> {
+        let from = storage.get_note(from)?.ok_or(VaultError::NoteNotFound)?;
+        let mut into = storage.get_note(into)?.ok_or(VaultError::NoteNotFound)?;
+        if from.id == into.id {
+            return Err(VaultError::MergeIntoItself)
+        }
+
+        for note in storage.backlinks_of(&from.id)? {
+            if note.id != into.id {
+                Self::link_notes(&note.id, &into.id, config, storage)?;
+            }
+        }
+
+        for note in storage.internal_references_of(&from.id)? {
+            if note.id != into.id {
+                Self::link_notes(&into.id, &note.id, config, storage)?;
+            }
+        }
+
+        for source in storage.external_references_of(&from.id)? {
+            Self::cite_source(&into.id, &source.id, config, storage)?;
+        }
+
+        let mut aliases = storage.aliases_of(&into.id)?;
+        for alias in storage.aliases_of(&from.id)?.into_iter().chain([from.title.clone()]) {
+            let key = normalize(&alias);
+            if key == normalize(&into.title) || aliases.iter().any(|a| normalize(a) == key) {
+                continue;
+            }
+
+            storage.add_alias(&Alias::new(Self::new_id(config, storage)?, into.id.clone(), alias.clone()))?;
+            aliases.push(alias);
+        }
+
+        storage.delete_internal_references_of(&from.id)?;
+        storage.delete_internal_references_to(&from.id)?;
+        storage.delete_external_references_of(&from.id)?;
+        storage.delete_aliases_of(&from.id)?;
+        storage.delete_note(&from.id)?;
+
+        if !from.contents.trim().is_empty() && from.contents.trim() != into.contents.trim() {
+            into.contents = format!("{}\n{}", into.contents.trim_end(), from.contents);
+        }
+        into.sequence = into.sequence.or(from.sequence);
+        storage.update_note(&into)?;
+
+        Ok(into)
+    }
+
+    fn merge_sources_in(from: &str, into: &str, config: &Config, storage: &mut dyn Storage) -> Result<Source, VaultError> {
+        let from = storage.get_source(from)?.ok_or(VaultError::SourceNotFound)?;
+        let into = storage.get_source(into)?.ok_or(VaultError::SourceNotFound)?;
+        if from.id == into.id {
+            return Err(VaultError::MergeIntoItself)
+        }
+
+        for note in storage.notes_citing(&from.id)? {
+            Self::cite_source(&note.id, &into.id, config, storage)?;
+        }
+
+        storage.delete_external_references_to(&from.id)?;
+        storage.delete_source(&from.id)?;
+
+        Ok(into)
+    }
+}
+
+fn group_by_title<T, F>(items: Vec<T>, title: F) -> Vec<Vec<T>>
+    where F: Fn(&T) -> &str {
+    let mut groups: BTreeMap<String, Vec<T>> = BTreeMap::new();
+    for item in items {
+        groups.entry(normalize(title(&item)))
+            .or_default()
+            .push(item);
+    }
+
+    groups.into_values()
+        .filter(|group| group.len() > 1)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{init_db::setup_database, storage::sqlite::SqliteStorage};
+
+    use super::*;
+
+    #[test]
+    fn merge_duplicates_keeps_the_first_note_and_restores_unique_titles() {
+        // Databases from before titles were normalised may hold near-duplicates,
+        // which the memory storage cannot represent.
+        let conn = setup_database(":memory:");
+        conn.execute_batch("
+            DROP INDEX notes_title_key;
+            INSERT INTO notes (id, title, title_key, contents) VALUES ('first', 'Idea', 'idea', 'first');
+            INSERT INTO notes (id, title, title_key, contents) VALUES ('second', 'IDEA ', 'idea', 'second');
+            INSERT INTO notes (id, title, title_key, contents) VALUES ('other', 'Other', 'other', 'other');
+            INSERT INTO internal_references (id, note_id, reference_id) VALUES ('ref', 'other', 'second');
+        ").unwrap();
+        let mut vault = Vault { storage: Box::new(SqliteStorage::new(conn)), config: Config::default() };
+
+        let found = vault.find_duplicates().unwrap();
+        let ids: Vec<&str> = found.notes[0].iter().map(|note| note.id.as_str()).collect();
+        assert_eq!(ids, ["first", "second"]);
+
+        vault.merge_duplicates().unwrap();
+
+        assert!(vault.find_duplicates().unwrap().notes.is_empty());
+        assert!(matches!(vault.get_note("second"), Err(VaultError::NoteNotFound)));
+        assert_eq!(vault.references_of("other").unwrap().internal[0].id, "first");
+
+        let again = vault.storage.add_note(&Note {
+            id: "third".to_string(),
+            title: "idea".to_string(),
+            contents: String::new(),
+            sequence: None,
+            revision: 0,
+        });
+        assert!(again.is_err());
+    }
+}
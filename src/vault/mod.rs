@@ -0,0 +1,660 @@
+use serde::Serialize;
+
+use crate::{config::{Config, IdFormat, ReferenceOrder, StubPolicy}, models::{aliases::Alias, external::ExternalReference, internal::InternalReference, note::{Note, NoteListItem}, sources::Source}, storage::{memory::MemoryStorage, Storage}, util::{id::{self, generate_unique_id}, parse::note_to_md, sequence, title::normalize, NoteFromMd, Reference}};
+
+use self::error::VaultError;
+
+pub mod changes;
+pub mod error;
+pub mod graph;
+pub mod history;
+pub mod merge;
+pub mod related;
+pub mod rename;
+pub mod sources;
+
+/// Library entry point to a spark vault, independent of the command line layer.
+pub struct Vault {
+    storage: Box<dyn Storage>,
+    config: Config,
+}
+
+/// Notes and sources referenced by a single note.
+#[derive(Debug, Serialize)]
+pub struct NoteReferences {
+    pub internal: Vec<Note>,
+    pub external: Vec<Source>,
+}
+
+/// Where `Vault::add_note_at` places the note in the sequence of notes.
+#[derive(Debug, Clone)]
+pub enum SequencePosition {
+    /// Next free top level position.
+    Next,
+    /// Next free branch below the note with provided id.
+    After(String),
+    /// Exactly the provided position.
+    At(String),
+}
+
+/// What `Vault::set_note` ended up doing with the note.
+#[derive(Debug)]
+pub enum SetOutcome {
+    Added(Note),
+    Updated(Note),
+}
+
+impl Vault {
+    pub fn new(storage: impl Storage + 'static, config: Config) -> Self {
+        Self { storage: Box::new(storage), config }
+    }
+
+    /// Vault which lives only as long as the value, see `MemoryStorage`.
+    pub fn in_memory() -> Self {
+        Self::new(MemoryStorage::new(), Config::default())
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn add_note(&mut self, note_from_md: NoteFromMd) -> Result<Note, VaultError> {
+        self.insert_note(note_from_md, None)
+    }
+
+    /// Adds the note and gives it a position in the sequence of notes.
+    pub fn add_note_at(&mut self, note_from_md: NoteFromMd, position: SequencePosition) -> Result<Note, VaultError> {
+        let sequence = self.allocate_sequence(position)?;
+
+        self.insert_note(note_from_md, Some(sequence))
+    }
+
+    fn insert_note(&mut self, note_from_md: NoteFromMd, sequence: Option<String>) -> Result<Note, VaultError> {
+        let id = match note_from_md.id {
+            Some(ref id) if !id::is_valid(id) => return Err(VaultError::InvalidId(id.clone())),
+            Some(ref id) => id.clone(),
+            None => Self::new_note_id(&self.config, self.storage.as_ref())?,
+        };
+
+        let note = Self::note_from_md(&note_from_md, id, sequence);
+        self.check_title(&note)?;
+
+        let config = &self.config;
+        Self::transaction(self.storage.as_mut(), |storage| {
+            storage.add_note(&note)?;
+            Self::set_aliases(&note.id, &note_from_md.aliases, config, storage)?;
+            Self::add_references(&note_from_md, &note.id, config, storage)
+        })?;
+
+        Ok(note)
+    }
+
+    pub fn update_note(&mut self, note_from_md: NoteFromMd) -> Result<Note, VaultError> {
+        let id = note_from_md.id.clone().ok_or(VaultError::NoteNotFound)?;
+        let existing = self.get_note(&id)?;
+
+        self.replace_note(note_from_md, existing)
+    }
+
+    /// Updates the note only if it is still at `revision`, so an edit based on
+    /// an older copy does not silently overwrite a newer one.
+    pub fn update_note_at_revision(&mut self, note_from_md: NoteFromMd, revision: i64) -> Result<Note, VaultError> {
+        let id = note_from_md.id.clone().ok_or(VaultError::NoteNotFound)?;
+        let existing = self.get_note(&id)?;
+
+        if existing.revision != revision {
+            return Err(VaultError::RevisionMismatch(revision, existing.revision))
+        }
+
+        self.replace_note(note_from_md, existing)
+    }
+
+    fn replace_note(&mut self, note_from_md: NoteFromMd, existing: Note) -> Result<Note, VaultError> {
+        let mut note = Self::note_from_md(&note_from_md, existing.id, existing.sequence);
+        note.revision = existing.revision + 1;
+        self.check_title(&note)?;
+
+        let config = &self.config;
+        Self::transaction(self.storage.as_mut(), |storage| {
+            storage.update_note(&note)?;
+            Self::set_aliases(&note.id, &note_from_md.aliases, config, storage)?;
+            storage.delete_internal_references_of(&note.id)?;
+            storage.delete_external_references_of(&note.id)?;
+            Self::add_references(&note_from_md, &note.id, config, storage)
+        })?;
+
+        Ok(note)
+    }
+
+    /// Updates the note if it can be found by id or title, adds it otherwise.
+    pub fn set_note(&mut self, mut note_from_md: NoteFromMd) -> Result<SetOutcome, VaultError> {
+        match self.existing_note(&note_from_md)? {
+            Some(note) => {
+                note_from_md.id = Some(note.id);
+                Ok(SetOutcome::Updated(self.update_note(note_from_md)?))
+            },
+            None => Ok(SetOutcome::Added(self.add_note(note_from_md)?)),
+        }
+    }
+
+    /// The note `set_note` would update: the one with the id of the markdown,
+    /// or without an id the one with its title.
+    pub fn existing_note(&self, note_from_md: &NoteFromMd) -> Result<Option<Note>, VaultError> {
+        let existing = match note_from_md.id {
+            Some(ref id) => self.storage.get_note(id)?,
+            None => self.storage.get_note_by_title(&note_from_md.title)?,
+        };
+
+        Ok(existing)
+    }
+
+    /// Deletes the note with its aliases and every reference from or to it.
+    pub fn delete_note(&mut self, id: &str) -> Result<Note, VaultError> {
+        let note = self.get_note(id)?;
+
+        Self::transaction(self.storage.as_mut(), |storage| {
+            storage.delete_internal_references_of(&note.id)?;
+            storage.delete_internal_references_to(&note.id)?;
+            storage.delete_external_references_of(&note.id)?;
+            storage.delete_aliases_of(&note.id)?;
+            storage.delete_note(&note.id)?;
+            Ok(())
+        })?;
+
+        Ok(note)
+    }
+
+    pub fn get_note(&self, id: &str) -> Result<Note, VaultError> {
+        self.storage.get_note(id)?
+            .ok_or(VaultError::NoteNotFound)
+    }
+
+    /// Note with provided id, or else the note whose title or alias matches `id_or_title`.
+    pub fn find_note(&self, id_or_title: &str) -> Result<Note, VaultError> {
+        if let Some(note) = self.storage.get_note(id_or_title)? {
+            return Ok(note)
+        }
+
+        Self::resolve_title(id_or_title, self.storage.as_ref())?
+            .ok_or(VaultError::NoteNotFound)
+    }
+
+    /// Notes `query` could mean: the exact id, title or alias if there is one,
+    /// otherwise every note whose id or normalised title starts with `query`.
+    pub fn match_notes(&self, query: &str) -> Result<Vec<NoteListItem>, VaultError> {
+        match self.find_note(query) {
+            Ok(note) => return Ok(vec![NoteListItem { id: note.id, title: note.title, sequence: note.sequence }]),
+            Err(VaultError::NoteNotFound) => {},
+            Err(VaultError::AmbiguousAlias(..)) => {
+                return Ok(self.storage.notes_with_alias(query)?
+                    .into_iter()
+                    .map(|note| NoteListItem { id: note.id, title: note.title, sequence: note.sequence })
+                    .collect())
+            },
+            Err(e) => return Err(e)
+        }
+
+        let id_prefix = query.trim().to_uppercase();
+        let title_prefix = normalize(query);
+        if title_prefix.is_empty() {
+            return Ok(vec![])
+        }
+
+        let notes = self.storage.list_notes()?
+            .into_iter()
+            .filter(|note| note.id.to_uppercase().starts_with(&id_prefix) || normalize(&note.title).starts_with(&title_prefix))
+            .collect();
+
+        Ok(notes)
+    }
+
+    pub fn aliases_of(&self, id: &str) -> Result<Vec<String>, VaultError> {
+        let note = self.get_note(id)?;
+
+        Ok(self.storage.aliases_of(&note.id)?)
+    }
+
+    /// Notes whose title, alias or contents contain `query`, ignoring case.
+    pub fn search(&self, query: &str) -> Result<Vec<NoteListItem>, VaultError> {
+        Ok(self.storage.search_notes(query)?)
+    }
+
+    pub fn get_source(&self, id: &str) -> Result<Source, VaultError> {
+        self.storage.get_source(id)?
+            .ok_or(VaultError::SourceNotFound)
+    }
+
+    pub fn list_notes(&self) -> Result<Vec<NoteListItem>, VaultError> {
+        Ok(self.storage.list_notes()?)
+    }
+
+    /// Notes which have a sequence position, in the order of the sequence.
+    pub fn list_sequence(&self) -> Result<Vec<NoteListItem>, VaultError> {
+        let mut notes: Vec<NoteListItem> = self.storage.list_notes()?
+            .into_iter()
+            .filter(|note| note.sequence.is_some())
+            .collect();
+
+        notes.sort_by(|a, b| sequence::compare(
+            a.sequence.as_deref().unwrap_or_default(),
+            b.sequence.as_deref().unwrap_or_default()
+        ));
+
+        Ok(notes)
+    }
+
+    pub fn list_sources(&self) -> Result<Vec<Source>, VaultError> {
+        Ok(self.storage.list_sources()?)
+    }
+
+    /// Notes and sources the note with provided id points to.
+    pub fn references_of(&self, id: &str) -> Result<NoteReferences, VaultError> {
+        let note = self.get_note(id)?;
+        let mut internal = self.storage.internal_references_of(&note.id)?;
+        let mut external = self.storage.external_references_of(&note.id)?;
+
+        if self.config.reference_order == ReferenceOrder::Id {
+            internal.sort_by(|a, b| a.id.cmp(&b.id));
+            external.sort_by(|a, b| a.id.cmp(&b.id));
+        }
+
+        Ok(NoteReferences { internal, external })
+    }
+
+    /// Notes which point to the note with provided id.
+    pub fn backlinks_of(&self, id: &str) -> Result<Vec<Note>, VaultError> {
+        let note = self.get_note(id)?;
+
+        Ok(self.storage.backlinks_of(&note.id)?)
+    }
+
+    /// Renders the note in the same markdown format `add`, `update` and `set` accept.
+    pub fn note_to_md(&self, id: &str) -> Result<String, VaultError> {
+        let note = self.get_note(id)?;
+        let aliases = self.storage.aliases_of(&note.id)?;
+        let NoteReferences { internal, external } = self.references_of(&note.id)?;
+
+        Ok(note_to_md(note, aliases, internal, external, &self.config.markdown))
+    }
+
+    /// Unused note id in the configured `id_format`.
+    fn new_note_id(config: &Config, storage: &dyn Storage) -> Result<String, VaultError> {
+        generate_unique_id(config.id_format, |id| storage.id_taken(id))?
+            .ok_or(VaultError::CannotGenerateId)
+    }
+
+    /// Unused id for sources and references, which don't spend timestamps
+    /// meant for notes.
+    fn new_id(config: &Config, storage: &dyn Storage) -> Result<String, VaultError> {
+        let format = match config.id_format {
+            IdFormat::Timestamp => IdFormat::Long,
+            format => format,
+        };
+
+        generate_unique_id(format, |id| storage.id_taken(id))?
+            .ok_or(VaultError::CannotGenerateId)
+    }
+
+    fn note_from_md(note_from_md: &NoteFromMd, id: String, sequence: Option<String>) -> Note {
+        Note {
+            id,
+            title: note_from_md.title.clone(),
+            contents: note_from_md.contents.clone(),
+            sequence,
+            revision: 0
+        }
+    }
+
+    /// Titles must not be empty, nor equal another note's title once normalised.
+    fn check_title(&self, note: &Note) -> Result<(), VaultError> {
+        if note.title.trim().is_empty() {
+            return Err(VaultError::NoteTitleEmpty)
+        }
+
+        match self.storage.get_note_by_title(&note.title)? {
+            Some(existing) if existing.id != note.id => Err(VaultError::TitleTaken(existing.title)),
+            _ => Ok(())
+        }
+    }
+
+    fn allocate_sequence(&self, position: SequencePosition) -> Result<String, VaultError> {
+        let notes = self.storage.list_notes()?;
+        let existing = notes.iter()
+            .filter_map(|note| note.sequence.as_deref());
+
+        match position {
+            SequencePosition::Next => Ok(sequence::next_top(existing)),
+            SequencePosition::After(id) => {
+                let parent = self.get_note(&id)?.sequence
+                    .ok_or(VaultError::NoteHasNoSequence)?;
+
+                Ok(sequence::next_child(&parent, existing))
+            },
+            SequencePosition::At(position) => {
+                if !sequence::is_valid(&position) {
+                    return Err(VaultError::InvalidSequence(position))
+                }
+                if self.storage.get_note_by_sequence(&position)?.is_some() {
+                    return Err(VaultError::SequenceTaken(position))
+                }
+
+                Ok(position)
+            }
+        }
+    }
+
+    /// Runs `f` inside a storage transaction, rolled back when `f` fails.
+    fn transaction<T, F>(storage: &mut dyn Storage, f: F) -> Result<T, VaultError>
+        where F: FnOnce(&mut dyn Storage) -> Result<T, VaultError> {
+        storage.begin()?;
+
+        match f(storage) {
+            Ok(value) => {
+                storage.commit()?;
+                Ok(value)
+            },
+            Err(e) => {
+                storage.rollback()?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Note titled `title`, or else the only note with such an alias.
+    fn resolve_title(title: &str, storage: &dyn Storage) -> Result<Option<Note>, VaultError> {
+        if let Some(note) = storage.get_note_by_title(title)? {
+            return Ok(Some(note))
+        }
+
+        let mut notes = storage.notes_with_alias(title)?;
+        match notes.len() {
+            0 => Ok(None),
+            1 => Ok(notes.pop()),
+            _ => {
+                let candidates = notes.iter()
+                    .map(|note| format!("[{}] {}", note.id, note.title))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+
+                Err(VaultError::AmbiguousAlias(title.to_string(), candidates))
+            }
+        }
+    }
+
+    fn set_aliases(note_id: &str, aliases: &[String], config: &Config, storage: &mut dyn Storage) -> Result<(), VaultError> {
+        storage.delete_aliases_of(note_id)?;
+
+        for alias in aliases {
+            let alias = Alias::new(Self::new_id(config, storage)?, note_id.to_string(), alias.clone());
+            storage.add_alias(&alias)?;
+        }
+
+        Ok(())
+    }
+
+    fn add_references(note_from_md: &NoteFromMd, note_id: &str, config: &Config, storage: &mut dyn Storage) -> Result<(), VaultError> {
+        note_from_md.references.internal.iter()
+            .try_for_each(|r| Self::add_internal_reference(r, note_id, config, storage))?;
+        note_from_md.references.external.iter()
+            .try_for_each(|r| Self::add_external_reference(r, note_id, config, storage))
+    }
+
+    fn add_internal_reference(reference: &Reference, note_id: &str, config: &Config, storage: &mut dyn Storage) -> Result<(), VaultError> {
+        match (&reference.id, &reference.title) {
+            (Some(id), _) => Self::link_notes(note_id, id, config, storage),
+            (_, Some(title)) => Self::add_internal_reference_by_title(title, note_id, config, storage),
+            (None, None) => Err(VaultError::InvalidReference)
+        }
+    }
+
+    fn add_internal_reference_by_title(title: &str, note_id: &str, config: &Config, storage: &mut dyn Storage) -> Result<(), VaultError> {
+        let note = match (Self::resolve_title(title, storage)?, config.stubs) {
+            (Some(note), _) => note,
+            (None, StubPolicy::Create) => {
+                let stub = Note { id: Self::new_note_id(config, storage)?, title: title.to_string(), contents: String::new(), sequence: None, revision: 0 };
+                storage.add_note(&stub)?;
+                stub
+            },
+            (None, StubPolicy::Error) => Err(VaultError::ReferenceDoesNotExist(title.to_string()))?,
+        };
+
+        Self::link_notes(note_id, &note.id, config, storage)
+    }
+
+    fn link_notes(note_id: &str, reference_id: &str, config: &Config, storage: &mut dyn Storage) -> Result<(), VaultError> {
+        if storage.internal_reference_exists(note_id, reference_id)? {
+            return Ok(())
+        }
+
+        let reference = InternalReference::new(Self::new_id(config, storage)?, note_id.to_string(), reference_id.to_string());
+        storage.add_internal_reference(&reference)?;
+
+        Ok(())
+    }
+
+    fn add_external_reference(reference: &Reference, note_id: &str, config: &Config, storage: &mut dyn Storage) -> Result<(), VaultError> {
+        match (&reference.id, &reference.title) {
+            (Some(id), _) => Self::cite_source(note_id, id, config, storage),
+            (_, Some(title)) => Self::add_external_reference_by_title(title, note_id, config, storage),
+            (None, None) => Err(VaultError::InvalidReference)
+        }
+    }
+
+    fn add_external_reference_by_title(title: &str, note_id: &str, config: &Config, storage: &mut dyn Storage) -> Result<(), VaultError> {
+        let source = match storage.get_source_by_title(title)? {
+            Some(source) => source,
+            None => {
+                let source = Source::new(Self::new_id(config, storage)?, title.to_string());
+                storage.add_source(&source)?;
+                source
+            }
+        };
+
+        Self::cite_source(note_id, &source.id, config, storage)
+    }
+
+    fn cite_source(note_id: &str, source_id: &str, config: &Config, storage: &mut dyn Storage) -> Result<(), VaultError> {
+        if storage.external_reference_exists(note_id, source_id)? {
+            return Ok(())
+        }
+
+        let reference = ExternalReference::new(Self::new_id(config, storage)?, note_id.to_string(), source_id.to_string());
+        storage.add_external_reference(&reference)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{init_db::setup_database, models::error::{DbError, FOREIGN_KEY}, storage::sqlite::SqliteStorage, util::parse::md_to_new_note};
+
+    use super::*;
+
+    fn note(md: &str) -> NoteFromMd {
+        md_to_new_note(md.to_string()).unwrap()
+    }
+
+    fn titles(notes: &[Note]) -> Vec<&str> {
+        notes.iter().map(|note| note.title.as_str()).collect()
+    }
+
+    fn titles_of(notes: &[NoteListItem]) -> Vec<&str> {
+        notes.iter().map(|note| note.title.as_str()).collect()
+    }
+
+    #[test]
+    fn add_note_stores_references_and_creates_sources() {
+        let mut vault = Vault::in_memory();
+        vault.add_note(note("# Idea\n\nAn idea.\n")).unwrap();
+
+        let a = vault.add_note(note("# A\n\nAbout a.\n## References\n### Internal\n1. Idea\n\n### External\n- Book\n")).unwrap();
+
+        let references = vault.references_of(&a.id).unwrap();
+        assert_eq!(titles(&references.internal), ["Idea"]);
+        assert_eq!(references.external.len(), 1);
+        assert_eq!(references.external[0].title, "Book");
+        assert_eq!(vault.get_note(&a.id).unwrap().contents.trim(), "About a.");
+    }
+
+    #[test]
+    fn add_note_rejects_missing_references_and_taken_titles() {
+        let mut vault = Vault::in_memory();
+        vault.add_note(note("# Idea\n\nAn idea.\n")).unwrap();
+
+        let missing = vault.add_note(note("# A\n\n## References\n### Internal\n1. Nowhere\n"));
+        assert!(matches!(missing, Err(VaultError::ReferenceDoesNotExist(_))));
+
+        let taken = vault.add_note(note("# Idea\n\nAgain.\n"));
+        assert!(matches!(taken, Err(VaultError::TitleTaken(title)) if title == "Idea"));
+
+        assert_eq!(vault.list_notes().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn update_note_replaces_contents_and_references() {
+        let mut vault = Vault::in_memory();
+        vault.add_note(note("# Idea\n\nAn idea.\n")).unwrap();
+        vault.add_note(note("# Other\n\nAnother idea.\n")).unwrap();
+        let a = vault.add_note(note("# A\n\nv1\n## References\n### Internal\n1. Idea\n")).unwrap();
+
+        let updated = vault.update_note(note(&format!("# [{}] A\n\nv2\n## References\n### Internal\n1. Other\n", a.id))).unwrap();
+
+        assert_eq!(updated.revision, a.revision + 1);
+        assert_eq!(vault.get_note(&a.id).unwrap().contents.trim(), "v2");
+        assert_eq!(titles(&vault.references_of(&a.id).unwrap().internal), ["Other"]);
+    }
+
+    #[test]
+    fn set_note_adds_then_updates_by_title() {
+        let mut vault = Vault::in_memory();
+
+        let added = match vault.set_note(note("# A\n\nv1\n")).unwrap() {
+            SetOutcome::Added(note) => note,
+            SetOutcome::Updated(_) => panic!("expected the note to be added"),
+        };
+        let updated = match vault.set_note(note("# A\n\nv2\n")).unwrap() {
+            SetOutcome::Updated(note) => note,
+            SetOutcome::Added(_) => panic!("expected the note to be updated"),
+        };
+
+        assert_eq!(added.id, updated.id);
+        assert_eq!(vault.list_notes().unwrap().len(), 1);
+        assert_eq!(vault.get_note(&added.id).unwrap().contents.trim(), "v2");
+    }
+
+    #[test]
+    fn storages_refuse_deleting_referenced_rows_alike() {
+        let storages: Vec<Box<dyn Storage>> = vec![
+            Box::new(MemoryStorage::new()),
+            Box::new(SqliteStorage::new(setup_database(":memory:"))),
+        ];
+
+        for storage in storages {
+            let mut vault = Vault { storage, config: Config::default() };
+            let idea = vault.add_note(note("# Idea\n\nAn idea.\n")).unwrap();
+            let a = vault.add_note(note("# A\n\n## References\n### Internal\n1. Idea\n\n### External\n- Book\n")).unwrap();
+            let book = vault.references_of(&a.id).unwrap().external.remove(0);
+
+            let note = vault.storage.delete_note(&idea.id);
+            let source = vault.storage.delete_source(&book.id);
+
+            assert!(matches!(note, Err(DbError::ConstraintViolation(name)) if name == FOREIGN_KEY));
+            assert!(matches!(source, Err(DbError::ConstraintViolation(name)) if name == FOREIGN_KEY));
+        }
+    }
+
+    #[test]
+    fn delete_note_removes_references_to_it() {
+        let mut vault = Vault::in_memory();
+        let idea = vault.add_note(note("# Idea\n\nAn idea.\n")).unwrap();
+        let a = vault.add_note(note("# A\n\n## References\n### Internal\n1. Idea\n")).unwrap();
+
+        vault.delete_note(&idea.id).unwrap();
+
+        assert!(matches!(vault.get_note(&idea.id), Err(VaultError::NoteNotFound)));
+        assert!(vault.references_of(&a.id).unwrap().internal.is_empty());
+    }
+
+    #[test]
+    fn add_note_at_allocates_and_validates_positions() {
+        let mut vault = Vault::in_memory();
+        let one = vault.add_note_at(note("# One\n"), SequencePosition::Next).unwrap();
+        let two = vault.add_note_at(note("# Two\n"), SequencePosition::Next).unwrap();
+        let branch = vault.add_note_at(note("# Branch\n"), SequencePosition::After(one.id.clone())).unwrap();
+        let plain = vault.add_note(note("# Plain\n")).unwrap();
+
+        assert_eq!(one.sequence.as_deref(), Some("1"));
+        assert_eq!(two.sequence.as_deref(), Some("2"));
+        assert_eq!(branch.sequence.as_deref(), Some("1a"));
+
+        let invalid = vault.add_note_at(note("# Invalid\n"), SequencePosition::At("1-a".to_string()));
+        let taken = vault.add_note_at(note("# Taken\n"), SequencePosition::At("1a".to_string()));
+        let unplaced = vault.add_note_at(note("# Unplaced\n"), SequencePosition::After(plain.id));
+
+        assert!(matches!(invalid, Err(VaultError::InvalidSequence(position)) if position == "1-a"));
+        assert!(matches!(taken, Err(VaultError::SequenceTaken(position)) if position == "1a"));
+        assert!(matches!(unplaced, Err(VaultError::NoteHasNoSequence)));
+        assert_eq!(vault.list_notes().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn match_notes_prefers_exact_matches() {
+        let mut vault = Vault::in_memory();
+        let idea = vault.add_note(note("# Idea\n")).unwrap();
+        vault.add_note(note("# Ideas\n")).unwrap();
+
+        assert_eq!(titles_of(&vault.match_notes(" idea ").unwrap()), ["Idea"]);
+        assert_eq!(titles_of(&vault.match_notes(&idea.id).unwrap()), ["Idea"]);
+        assert_eq!(titles_of(&vault.match_notes("ide").unwrap()), ["Idea", "Ideas"]);
+    }
+
+    #[test]
+    fn match_notes_finds_id_and_title_prefixes() {
+        let mut vault = Vault::in_memory();
+        let a = vault.add_note(note("# Zettel one\n")).unwrap();
+        vault.add_note(note("# Zettel two\n")).unwrap();
+        vault.add_note(note("# Other\n")).unwrap();
+
+        assert_eq!(titles_of(&vault.match_notes(&a.id[..4].to_lowercase()).unwrap()), ["Zettel one"]);
+        assert_eq!(titles_of(&vault.match_notes("ZETTEL  ").unwrap()), ["Zettel one", "Zettel two"]);
+        assert!(vault.match_notes("  ").unwrap().is_empty());
+        assert!(vault.match_notes("missing").unwrap().is_empty());
+    }
+
+    #[test]
+    fn match_notes_lists_every_note_of_an_ambiguous_alias() {
+        let mut vault = Vault::in_memory();
+        vault.add_note(note("# One\n## Aliases\n - Shared\n")).unwrap();
+        vault.add_note(note("# Two\n## Aliases\n - Shared\n")).unwrap();
+
+        assert!(matches!(vault.find_note("Shared"), Err(VaultError::AmbiguousAlias(..))));
+        assert_eq!(titles_of(&vault.match_notes("shared").unwrap()), ["One", "Two"]);
+    }
+
+    #[test]
+    fn update_note_at_revision_rejects_stale_revision() {
+        let mut vault = Vault::in_memory();
+        let a = vault.add_note(note("# A\n\nv1\n")).unwrap();
+        vault.update_note(note(&format!("# [{}] A\n\nv2\n", a.id))).unwrap();
+
+        let stale = vault.update_note_at_revision(note(&format!("# [{}] A\n\nv3\n", a.id)), a.revision);
+
+        assert!(matches!(stale, Err(VaultError::RevisionMismatch(expected, actual)) if expected == a.revision && actual == a.revision + 1));
+        assert_eq!(vault.get_note(&a.id).unwrap().contents.trim(), "v2");
+    }
+
+    #[test]
+    fn add_note_accepts_well_formed_ids_only() {
+        let mut vault = Vault::in_memory();
+
+        let added = vault.add_note(NoteFromMd { id: Some("ABC234".to_string()), title: "A".to_string(), ..Default::default() }).unwrap();
+        assert_eq!(added.id, "ABC234");
+
+        for id in ["", "../../x"] {
+            let invalid = vault.add_note(NoteFromMd { id: Some(id.to_string()), title: "B".to_string(), ..Default::default() });
+            assert!(matches!(invalid, Err(VaultError::InvalidId(invalid)) if invalid == id));
+        }
+        assert_eq!(vault.list_notes().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{models::{note::NoteListItem, sources::Source}, util::tfidf::Corpus};
+
+use super::{error::VaultError, Vault};
+
+const SOURCE_WEIGHT: f64 = 1.0;
+const NEIGHBOUR_WEIGHT: f64 = 1.0;
+/// Similarity is at most 1, a close text match counts as much as a few shared links.
+const TEXT_WEIGHT: f64 = 3.0;
+/// Similarities below this are noise and are not reported.
+const MIN_SIMILARITY: f64 = 0.05;
+const SHOWN_TERMS: usize = 5;
+
+/// A note worth linking to, with the reasons it was suggested.
+#[derive(Debug)]
+pub struct Related {
+    pub note: NoteListItem,
+    pub score: f64,
+    pub shared_sources: Vec<Source>,
+    pub shared_neighbours: Vec<NoteListItem>,
+    /// TF-IDF cosine similarity of title and contents.
+    pub similarity: f64,
+    /// Terms contributing most to `similarity`.
+    pub terms: Vec<String>,
+}
+
+impl Vault {
+    /// Notes ranked by shared sources, shared neighbours in the reference
+    /// graph and text similarity, best first. Notes the note already
+    /// references are left out.
+    pub fn related(&self, id: &str, limit: usize) -> Result<Vec<Related>, VaultError> {
+        let note = self.get_note(id)?;
+        let graph = self.graph()?;
+
+        let sources: BTreeMap<String, Source> = self.storage.list_sources()?
+            .into_iter()
+            .map(|source| (source.id.clone(), source))
+            .collect();
+        let mut cited: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for reference in self.storage.list_external_references()? {
+            cited.entry(reference.note_id).or_default().insert(reference.reference_id);
+        }
+
+        let mut texts = vec![];
+        for item in graph.notes() {
+            let note = self.get_note(&item.id)?;
+            texts.push((note.id, format!("{}\n{}", note.title, note.contents)));
+        }
+        let corpus = Corpus::new(texts.iter().map(|(id, text)| (id.as_str(), text.as_str())));
+
+        let empty = BTreeSet::new();
+        let own_sources = cited.get(&note.id).unwrap_or(&empty);
+        let own_neighbours = graph.neighbours(&note.id);
+        let linked: BTreeSet<&String> = graph.outgoing(&note.id).collect();
+
+        let mut related = vec![];
+        for candidate in graph.notes() {
+            if candidate.id == note.id || linked.contains(&candidate.id) {
+                continue
+            }
+
+            let shared_sources: Vec<Source> = cited.get(&candidate.id).unwrap_or(&empty)
+                .intersection(own_sources)
+                .filter_map(|id| sources.get(id).cloned())
+                .collect();
+            let shared_neighbours: Vec<NoteListItem> = graph.neighbours(&candidate.id)
+                .intersection(&own_neighbours)
+                .filter_map(|id| graph.note(id).cloned())
+                .collect();
+            let (similarity, mut terms) = corpus.similarity(&note.id, &candidate.id);
+            let similarity = if similarity < MIN_SIMILARITY { 0.0 } else { similarity };
+            terms.truncate(if similarity > 0.0 { SHOWN_TERMS } else { 0 });
+
+            let score = shared_sources.len() as f64 * SOURCE_WEIGHT
+                + shared_neighbours.len() as f64 * NEIGHBOUR_WEIGHT
+                + similarity * TEXT_WEIGHT;
+
+            if score > 0.0 {
+                related.push(Related {
+                    note: candidate.clone(),
+                    score,
+                    shared_sources,
+                    shared_neighbours,
+                    similarity,
+                    terms,
+                });
+            }
+        }
+
+        related.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.note.title.cmp(&b.note.title)));
+        related.truncate(limit);
+
+        Ok(related)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::parse::md_to_new_note;
+
+    use super::*;
+
+    fn add(vault: &mut Vault, md: &str) -> String {
+        vault.add_note(md_to_new_note(md.to_string()).unwrap()).unwrap().id
+    }
+
+    fn titles(related: &[Related]) -> Vec<&str> {
+        related.iter().map(|related| related.note.title.as_str()).collect()
+    }
+
+    #[test]
+    fn notes_sharing_rare_terms_rank_first() {
+        let mut vault = Vault::in_memory();
+        let a = add(&mut vault, "# A\n\nzettelkasten notes\n");
+        add(&mut vault, "# C\n\nnotes gardening\n");
+        add(&mut vault, "# B\n\nzettelkasten cooking\n");
+        add(&mut vault, "# D\n\nnotes travel\n");
+        add(&mut vault, "# E\n\nnotes music\n");
+
+        let related = vault.related(&a, 10).unwrap();
+
+        assert_eq!(related[0].note.title, "B");
+        assert_eq!(related[0].terms, ["zettelkasten"]);
+        assert!(related[0].score > related[1].score);
+        assert_eq!(titles(&vault.related(&a, 1).unwrap()), ["B"]);
+    }
+
+    #[test]
+    fn shared_sources_and_neighbours_count_and_linked_notes_are_left_out() {
+        let mut vault = Vault::in_memory();
+        let hub = add(&mut vault, "# Hub\n");
+        let a = add(&mut vault, "# A\n## References\n### Internal\n1. Hub\n\n### External\n- Book\n");
+        add(&mut vault, "# B\n## References\n### Internal\n1. Hub\n\n### External\n- Book\n");
+        add(&mut vault, "# C\n## References\n### Internal\n1. Hub\n");
+
+        let related = vault.related(&a, 10).unwrap();
+
+        assert_eq!(titles(&related), ["B", "C"]);
+        assert_eq!(related[0].shared_sources[0].title, "Book");
+        assert_eq!(related[0].shared_neighbours[0].id, hub);
+        assert!(related[1].shared_sources.is_empty());
+    }
+
+    #[test]
+    fn related_of_an_empty_vault_or_note_is_empty() {
+        let mut vault = Vault::in_memory();
+        assert!(matches!(vault.related("missing", 10), Err(VaultError::NoteNotFound)));
+
+        let empty = add(&mut vault, "# Empty\n");
+        assert!(vault.related(&empty, 10).unwrap().is_empty());
+
+        add(&mut vault, "# Other\n\nzettelkasten notes\n");
+        assert!(vault.related(&empty, 10).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,155 @@
+use crate::{models::{aliases::Alias, note::{Note, NoteListItem}}, util::{title::normalize, wiki::rewrite_links}};
+
+use super::{error::VaultError, Vault};
+
+/// Result of `Vault::rename_note`.
+#[derive(Debug)]
+pub struct Rename {
+    pub note: Note,
+    pub old_title: String,
+    /// Notes whose wiki-style mentions of the old title were rewritten.
+    pub rewritten: Vec<NoteListItem>,
+}
+
+impl Vault {
+    /// Renames the note and keeps its old title as an alias. The new title
+    /// cannot be the title or alias of another note. With `rewrite`,
+    /// `[[Old title]]` mentions in every note are changed to the new title.
+    /// With `dry_run` nothing is written.
+    pub fn rename_note(&mut self, id: &str, title: &str, rewrite: bool, dry_run: bool) -> Result<Rename, VaultError> {
+        let mut note = self.get_note(id)?;
+        let old_title = note.title.clone();
+        note.title = title.trim().to_string();
+        self.check_title(&note)?;
+        if let Some(other) = self.storage.notes_with_alias(&note.title)?.into_iter().find(|other| other.id != note.id) {
+            return Err(VaultError::AliasTaken(note.title, other.title))
+        }
+
+        let mut rewrites = vec![];
+        if rewrite {
+            for item in self.storage.list_notes()? {
+                let mut mentioning = match item.id == note.id {
+                    true => note.clone(),
+                    false => self.get_note(&item.id)?,
+                };
+
+                if let Some(contents) = rewrite_links(&mentioning.contents, &old_title, &note.title) {
+                    mentioning.contents = contents;
+                    rewrites.push(mentioning);
+                }
+            }
+        }
+
+        let rewritten = rewrites.iter()
+            .map(|n| NoteListItem { id: n.id.clone(), title: n.title.clone(), sequence: n.sequence.clone() })
+            .collect();
+
+        if dry_run {
+            return Ok(Rename { note, old_title, rewritten })
+        }
+
+        let config = &self.config;
+        Self::transaction(self.storage.as_mut(), |storage| {
+            let mut aliases = storage.aliases_of(&note.id)?;
+            aliases.retain(|alias| normalize(alias) != normalize(&note.title));
+            if normalize(&old_title) != normalize(&note.title) && !aliases.iter().any(|a| normalize(a) == normalize(&old_title)) {
+                aliases.push(old_title.clone());
+            }
+
+            storage.update_note(&note)?;
+            storage.delete_aliases_of(&note.id)?;
+            for alias in aliases {
+                storage.add_alias(&Alias::new(Self::new_id(config, storage)?, note.id.clone(), alias))?;
+            }
+
+            for rewrite in &rewrites {
+                storage.update_note(rewrite)?;
+            }
+
+            Ok(())
+        })?;
+
+        let note = self.get_note(&note.id)?;
+        Ok(Rename { note, old_title, rewritten })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::parse::md_to_new_note;
+
+    use super::*;
+
+    fn add(vault: &mut Vault, md: &str) -> Note {
+        vault.add_note(md_to_new_note(md.to_string()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn rename_keeps_the_old_title_as_alias() {
+        let mut vault = Vault::in_memory();
+        let old = add(&mut vault, "# Old\n\nAn idea.\n");
+
+        let rename = vault.rename_note(&old.id, " New ", false, false).unwrap();
+
+        assert_eq!(rename.old_title, "Old");
+        assert_eq!(rename.note.title, "New");
+        assert_eq!(vault.aliases_of(&old.id).unwrap(), ["Old"]);
+        assert_eq!(vault.find_note("old").unwrap().id, old.id);
+    }
+
+    #[test]
+    fn rename_rewrites_mentions_only_when_asked() {
+        for rewrite in [false, true] {
+            let mut vault = Vault::in_memory();
+            let old = add(&mut vault, "# Old\n\nAn idea.\n");
+            let mentioning = add(&mut vault, "# A\n\nSee [[Old]], [[old|that idea]] and [[Other]].\n");
+
+            let rename = vault.rename_note(&old.id, "New", rewrite, false).unwrap();
+
+            let rewritten: Vec<&str> = rename.rewritten.iter().map(|note| note.id.as_str()).collect();
+            let contents = vault.get_note(&mentioning.id).unwrap().contents;
+            match rewrite {
+                true => {
+                    assert_eq!(rewritten, [mentioning.id.as_str()]);
+                    assert_eq!(contents.trim(), "See [[New]], [[New|that idea]] and [[Other]].");
+                },
+                false => {
+                    assert!(rewritten.is_empty());
+                    assert_eq!(contents.trim(), "See [[Old]], [[old|that idea]] and [[Other]].");
+                },
+            }
+        }
+    }
+
+    #[test]
+    fn dry_run_writes_nothing() {
+        let mut vault = Vault::in_memory();
+        let old = add(&mut vault, "# Old\n\nAn idea.\n");
+        let mentioning = add(&mut vault, "# A\n\nSee [[Old]].\n");
+
+        let rename = vault.rename_note(&old.id, "New", true, true).unwrap();
+
+        assert_eq!(rename.note.title, "New");
+        assert_eq!(rename.rewritten.len(), 1);
+        assert_eq!(vault.get_note(&old.id).unwrap().title, "Old");
+        assert!(vault.aliases_of(&old.id).unwrap().is_empty());
+        assert!(vault.get_note(&mentioning.id).unwrap().contents.contains("[[Old]]"));
+    }
+
+    #[test]
+    fn rename_refuses_titles_and_aliases_of_other_notes() {
+        let mut vault = Vault::in_memory();
+        let old = add(&mut vault, "# Old\n\nAn idea.\n## Aliases\n - Former\n");
+        add(&mut vault, "# Taken\n\nAnother idea.\n## Aliases\n - Thought\n");
+
+        let title = vault.rename_note(&old.id, "taken", false, false);
+        let alias = vault.rename_note(&old.id, "Thought", false, false);
+
+        assert!(matches!(title, Err(VaultError::TitleTaken(title)) if title == "Taken"));
+        assert!(matches!(alias, Err(VaultError::AliasTaken(alias, note)) if alias == "Thought" && note == "Taken"));
+        assert_eq!(vault.get_note(&old.id).unwrap().title, "Old");
+
+        vault.rename_note(&old.id, "Former", false, false).unwrap();
+        assert_eq!(vault.aliases_of(&old.id).unwrap(), ["Old"]);
+    }
+}
@@ -0,0 +1,144 @@
+use crate::models::{note::NoteListItem, sources::Source};
+
+use super::{error::VaultError, Vault};
+
+impl Vault {
+    /// Source with provided id, or else the source whose title matches `id_or_title`.
+    pub fn find_source(&self, id_or_title: &str) -> Result<Source, VaultError> {
+        if let Some(source) = self.storage.get_source(id_or_title)? {
+            return Ok(source)
+        }
+
+        self.storage.get_source_by_title(id_or_title)?
+            .ok_or(VaultError::SourceNotFound)
+    }
+
+    /// Notes citing the source, ordered by title.
+    pub fn citing_notes(&self, id: &str) -> Result<Vec<NoteListItem>, VaultError> {
+        let mut notes: Vec<NoteListItem> = self.storage.notes_citing(id)?
+            .into_iter()
+            .map(|note| NoteListItem { id: note.id, title: note.title, sequence: note.sequence })
+            .collect();
+        notes.sort_by(|a, b| a.title.cmp(&b.title));
+
+        Ok(notes)
+    }
+
+    pub fn rename_source(&mut self, id: &str, title: &str) -> Result<Source, VaultError> {
+        let mut source = self.get_source(id)?;
+        source.title = title.trim().to_string();
+
+        if source.title.is_empty() {
+            return Err(VaultError::SourceTitleEmpty)
+        }
+
+        if let Some(existing) = self.storage.get_source_by_title(&source.title)? {
+            if existing.id != source.id {
+                return Err(VaultError::SourceTitleTaken(existing.title))
+            }
+        }
+
+        self.storage.update_source(&source)?;
+
+        Ok(source)
+    }
+
+    /// Deletes the source, refusing while notes cite it unless `force` is set,
+    /// in which case the citations are deleted with it.
+    pub fn delete_source(&mut self, id: &str, force: bool) -> Result<Source, VaultError> {
+        let source = self.get_source(id)?;
+
+        let citing = self.storage.notes_citing(&source.id)?;
+        if !citing.is_empty() && !force {
+            return Err(VaultError::SourceInUse(citing.len()))
+        }
+
+        Self::transaction(self.storage.as_mut(), |storage| {
+            storage.delete_external_references_to(&source.id)?;
+            storage.delete_source(&source.id)?;
+            Ok(())
+        })?;
+
+        Ok(source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::parse::md_to_new_note;
+
+    use super::*;
+
+    /// Vault with a note citing `Book` and `Paper`, returning the id of `Book`.
+    fn vault() -> (Vault, String) {
+        let mut vault = Vault::in_memory();
+        let note = md_to_new_note("# A\n\n## References\n### Internal\n\n### External\n- Book\n- Paper\n".to_string()).unwrap();
+        let a = vault.add_note(note).unwrap();
+        let book = vault.find_source("Book").unwrap().id;
+        assert_eq!(vault.references_of(&a.id).unwrap().external.len(), 2);
+
+        (vault, book)
+    }
+
+    #[test]
+    fn rename_source_changes_the_title() {
+        let (mut vault, book) = vault();
+
+        let renamed = vault.rename_source(&book, " The Book ").unwrap();
+
+        assert_eq!(renamed.title, "The Book");
+        assert_eq!(vault.find_source("The Book").unwrap().id, book);
+    }
+
+    #[test]
+    fn rename_source_refuses_taken_and_empty_titles() {
+        let (mut vault, book) = vault();
+
+        let taken = vault.rename_source(&book, "paper");
+        let empty = vault.rename_source(&book, "  ");
+
+        assert!(matches!(taken, Err(VaultError::SourceTitleTaken(title)) if title == "Paper"));
+        assert!(matches!(empty, Err(VaultError::SourceTitleEmpty)));
+        assert_eq!(vault.get_source(&book).unwrap().title, "Book");
+    }
+
+    #[test]
+    fn delete_source_refuses_cited_sources_unless_forced() {
+        let (mut vault, book) = vault();
+
+        let refused = vault.delete_source(&book, false);
+        assert!(matches!(refused, Err(VaultError::SourceInUse(1))));
+        assert!(vault.get_source(&book).is_ok());
+
+        vault.delete_source(&book, true).unwrap();
+        assert!(matches!(vault.get_source(&book), Err(VaultError::SourceNotFound)));
+        let titles: Vec<String> = vault.list_sources().unwrap().into_iter().map(|source| source.title).collect();
+        assert_eq!(titles, ["Paper"]);
+        assert!(vault.citing_notes(&book).unwrap().is_empty());
+    }
+
+    #[test]
+    fn merge_sources_moves_citations() {
+        let (mut vault, book) = vault();
+        let paper = vault.find_source("Paper").unwrap().id;
+
+        vault.merge_sources(&paper, &book).unwrap();
+
+        assert!(matches!(vault.get_source(&paper), Err(VaultError::SourceNotFound)));
+        assert_eq!(vault.citing_notes(&book).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn citing_notes_lists_each_citing_note_once_by_title() {
+        let (mut vault, book) = vault();
+        let note = md_to_new_note("# Before\n\n## References\n### Internal\n\n### External\n- Book\n".to_string()).unwrap();
+        vault.add_note(note).unwrap();
+        let other = md_to_new_note("# C\n\n## References\n### Internal\n\n### External\n- Talk\n".to_string()).unwrap();
+        vault.add_note(other).unwrap();
+
+        let titles: Vec<String> = vault.citing_notes(&book).unwrap().into_iter().map(|note| note.title).collect();
+
+        assert_eq!(titles, ["A", "Before"]);
+        assert!(vault.citing_notes("missing").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,11 @@
+#[derive(thiserror::Error, Debug)]
+pub enum WatchError {
+    #[error("Cannot read directory {0}: {1}")]
+    CannotReadDirectory(String, String),
+
+    #[error("Cannot watch {0}: {1}")]
+    CannotWatch(String, String),
+
+    #[error(transparent)]
+    Generic(#[from] anyhow::Error)
+}
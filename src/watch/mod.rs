@@ -0,0 +1,214 @@
+use std::{collections::BTreeSet, fs, path::{Path, PathBuf}, sync::mpsc, time::Duration};
+
+use notify::{event::{AccessKind, AccessMode}, Event, EventKind, RecursiveMode, Watcher};
+
+use self::error::WatchError;
+
+pub mod error;
+
+/// How long to wait for more events after one arrives, so the several writes
+/// and renames of a single save are handled once.
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/// A markdown file which was created or modified, or removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Written(PathBuf),
+    Removed(PathBuf),
+    /// An error reported by the watcher, watching goes on.
+    Failed(String),
+}
+
+/// Markdown files under `dir` and its subdirectories, sorted by path.
+pub fn markdown_files(dir: &Path) -> Result<Vec<PathBuf>, WatchError> {
+    let cannot_read = |e: std::io::Error| WatchError::CannotReadDirectory(dir.display().to_string(), e.to_string());
+
+    let mut files = vec![];
+    for entry in fs::read_dir(dir).map_err(cannot_read)? {
+        let path = entry.map_err(cannot_read)?.path();
+        if path.is_dir() {
+            files.extend(markdown_files(&path)?);
+        }
+        else if is_markdown(&path) {
+            files.push(path);
+        }
+    }
+    files.sort();
+
+    Ok(files)
+}
+
+/// Calls `handle` for every markdown file changed under `dir` until the
+/// process is stopped. Written files of a batch come before removed ones, so
+/// a file moved within `dir` is seen before its old path disappears.
+pub fn watch(dir: &Path, mut handle: impl FnMut(Change)) -> Result<(), WatchError> {
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)
+        .map_err(|e| WatchError::CannotWatch(dir.display().to_string(), e.to_string()))?;
+    watcher.watch(dir, RecursiveMode::Recursive)
+        .map_err(|e| WatchError::CannotWatch(dir.display().to_string(), e.to_string()))?;
+
+    while let Ok(event) = receiver.recv() {
+        let mut events = vec![event];
+        while let Ok(event) = receiver.recv_timeout(SETTLE_TIME) {
+            events.push(event);
+        }
+
+        handle_events(events, &mut handle);
+    }
+
+    Ok(())
+}
+
+/// Calls `handle` with the changes of a batch of events: errors first, then
+/// markdown files which exist, then those which are gone.
+fn handle_events(events: Vec<notify::Result<Event>>, handle: &mut impl FnMut(Change)) {
+    let mut paths = BTreeSet::new();
+    let mut errors = vec![];
+    for event in events {
+        collect(event, &mut paths, &mut errors);
+    }
+
+    let (written, removed): (Vec<PathBuf>, Vec<PathBuf>) = paths.into_iter()
+        .filter(|path| is_markdown(path))
+        .partition(|path| path.is_file());
+
+    errors.into_iter().map(Change::Failed)
+        .chain(written.into_iter().map(Change::Written))
+        .chain(removed.into_iter().map(Change::Removed))
+        .for_each(handle);
+}
+
+fn collect(event: notify::Result<Event>, paths: &mut BTreeSet<PathBuf>, errors: &mut Vec<String>) {
+    match event {
+        Ok(event) => {
+            let relevant = matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+                    | EventKind::Access(AccessKind::Close(AccessMode::Write))
+            );
+            if relevant {
+                paths.extend(event.paths);
+            }
+        },
+        Err(e) => errors.push(e.to_string())
+    }
+}
+
+/// Markdown files, leaving out hidden ones such as the `.#name.md` locks of emacs.
+fn is_markdown(path: &Path) -> bool {
+    let hidden = path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'));
+
+    !hidden && path.extension().is_some_and(|extension| extension == "md")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use notify::event::{CreateKind, DataChange, ModifyKind, RemoveKind};
+
+    use super::*;
+
+    /// Directory removed when the test ends, even if it fails.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let path = env::temp_dir().join(format!("spark-watch-{:016x}", rand::random::<u64>()));
+            fs::create_dir(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn event(kind: EventKind, path: &Path) -> notify::Result<Event> {
+        Ok(Event::new(kind).add_path(path.to_path_buf()))
+    }
+
+    fn changes(events: Vec<notify::Result<Event>>) -> Vec<Change> {
+        let mut changes = vec![];
+        handle_events(events, &mut |change| changes.push(change));
+
+        changes
+    }
+
+    #[test]
+    fn created_and_modified_files_are_written_once() {
+        let dir = TempDir::new();
+        let a = dir.0.join("a.md");
+        let b = dir.0.join("b.md");
+        fs::write(&a, "# A\n").unwrap();
+        fs::write(&b, "# B\n").unwrap();
+
+        let changes = changes(vec![
+            event(EventKind::Create(CreateKind::File), &b),
+            event(EventKind::Modify(ModifyKind::Data(DataChange::Content)), &b),
+            event(EventKind::Access(AccessKind::Close(AccessMode::Write)), &a),
+        ]);
+
+        assert_eq!(changes, [Change::Written(a), Change::Written(b)]);
+    }
+
+    #[test]
+    fn files_which_are_gone_are_removed_after_written_ones() {
+        let dir = TempDir::new();
+        let old = dir.0.join("old.md");
+        let new = dir.0.join("new.md");
+        fs::write(&new, "# A\n").unwrap();
+
+        let changes = changes(vec![
+            event(EventKind::Remove(RemoveKind::File), &old),
+            event(EventKind::Create(CreateKind::File), &new),
+        ]);
+
+        assert_eq!(changes, [Change::Written(new), Change::Removed(old)]);
+    }
+
+    #[test]
+    fn other_files_and_events_are_ignored() {
+        let dir = TempDir::new();
+        let note = dir.0.join("note.md");
+        fs::write(&note, "# A\n").unwrap();
+
+        let changes = changes(vec![
+            event(EventKind::Create(CreateKind::File), &dir.0.join(".note.md.swp")),
+            event(EventKind::Create(CreateKind::File), &dir.0.join(".#note.md")),
+            event(EventKind::Create(CreateKind::File), &dir.0.join("note.md~")),
+            event(EventKind::Create(CreateKind::Folder), &dir.0.join("folder")),
+            event(EventKind::Access(AccessKind::Read), &note),
+        ]);
+
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn errors_reach_the_handler_first() {
+        let dir = TempDir::new();
+        let note = dir.0.join("note.md");
+        fs::write(&note, "# A\n").unwrap();
+
+        let changes = changes(vec![
+            event(EventKind::Create(CreateKind::File), &note),
+            Err(notify::Error::generic("watch limit reached")),
+        ]);
+
+        assert_eq!(changes, [Change::Failed("watch limit reached".to_string()), Change::Written(note)]);
+    }
+
+    #[test]
+    fn markdown_files_are_found_in_subdirectories() {
+        let dir = TempDir::new();
+        fs::create_dir(dir.0.join("sub")).unwrap();
+        for name in ["b.md", "sub/a.md", "c.txt", ".hidden.md"] {
+            fs::write(dir.0.join(name), "").unwrap();
+        }
+
+        assert_eq!(markdown_files(&dir.0).unwrap(), [dir.0.join("b.md"), dir.0.join("sub/a.md")]);
+    }
+}